@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use crate::{locale, repl};
+
+/// What `fox --version` prints, followed with `verbose` by how fox was built,
+/// for bug reports.
+pub(crate) fn version(verbose: bool) -> String {
+    let mut report = format!("fox {}\n", env!("CARGO_PKG_VERSION"));
+    if !verbose {
+        return report;
+    }
+    let features = match env!("FOX_FEATURES") {
+        "" => "none".to_string(),
+        features => features.replace(',', ", "),
+    };
+    for (name, value) in [
+        ("commit", env!("FOX_COMMIT").to_string()),
+        ("target", env!("FOX_TARGET").to_string()),
+        ("backends", "tree, vm".to_string()),
+        ("features", features),
+    ] {
+        report.push_str(&line(name, &value));
+    }
+    report
+}
+
+/// What `fox env` prints: where fox keeps its files and the locale of the
+/// environment.
+pub(crate) fn env() -> String {
+    let path = |path: Option<PathBuf>| match path {
+        Some(path) => path.display().to_string(),
+        None => "unavailable".to_string(),
+    };
+    [
+        ("history", path(repl::history_path())),
+        ("locales", path(locale::catalog_dir())),
+        (
+            "locale",
+            locale::system_locale().unwrap_or_else(|| "none".to_string()),
+        ),
+    ]
+    .iter()
+    .map(|(name, value)| line(name, value))
+    .collect()
+}
+
+fn line(name: &str, value: &str) -> String {
+    format!("{:<10}{value}\n", format!("{name}:"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{env, version};
+
+    #[test]
+    fn check_reports() {
+        assert_eq!(
+            version(false),
+            format!("fox {}\n", env!("CARGO_PKG_VERSION"))
+        );
+        let verbose = version(true);
+        let names: Vec<_> = verbose
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["commit", "target", "backends", "features"]);
+        assert!(verbose.contains(&format!("target:   {}\n", env!("FOX_TARGET"))));
+        assert!(env().starts_with("history:  "));
+    }
+}
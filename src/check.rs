@@ -0,0 +1,270 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{engine, errors::FoxError, source::SourceFile};
+use ignore::WalkBuilder;
+use miette::Report;
+
+/// The diagnostics found in one script. They are rendered by the thread
+/// checking the script, as errors can hold values that can't be sent to
+/// another thread.
+#[derive(Debug)]
+struct FileReport {
+    path: PathBuf,
+    errors: usize,
+    warnings: usize,
+    diagnostics: Vec<String>,
+}
+
+/// Lexes, parses and resolves every script found in `args`, without running
+/// them, and reports their errors and warnings along with a summary per file
+/// and in total. Arguments can be files, directories, which are searched
+/// recursively for `.fox` files, or glob patterns. Unless `no_ignore` is
+/// set, the files and directories matched by a `.gitignore` or `.foxignore`
+/// found along the way are skipped, as are hidden ones. Scripts are checked
+/// in parallel. Returns `Err` if any error was found.
+pub fn check(args: &[String], no_ignore: bool) -> Result<(), ()> {
+    let paths = match discover(args, no_ignore) {
+        Ok(paths) => paths,
+        Err(err) => {
+            eprintln!("{:?}", Report::new(err));
+            return Err(());
+        }
+    };
+    let reports = check_files(&paths);
+    let mut errors = 0;
+    let mut warnings = 0;
+    for report in &reports {
+        for diagnostic in &report.diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        errors += report.errors;
+        warnings += report.warnings;
+        let summary = match (report.errors, report.warnings) {
+            (0, 0) => "ok".to_string(),
+            (errors, warnings) => summarize(errors, warnings),
+        };
+        println!("{}: {summary}", report.path.display());
+    }
+    println!(
+        "Checked {}: {}",
+        count(reports.len(), "file"),
+        summarize(errors, warnings)
+    );
+    if errors > 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+fn summarize(errors: usize, warnings: usize) -> String {
+    format!("{}, {}", count(errors, "error"), count(warnings, "warning"))
+}
+
+fn count(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
+
+/// Finds the scripts named by `args`, sorted and without duplicates. Files
+/// named explicitly are never ignored.
+pub(crate) fn discover(args: &[String], no_ignore: bool) -> Result<Vec<PathBuf>, FoxError> {
+    let io_error = |path: &Path, error| FoxError::Io {
+        path: path.display().to_string(),
+        error,
+    };
+    let mut paths = vec![];
+    for arg in args {
+        if !arg.contains(['*', '?', '[']) {
+            let path = Path::new(arg);
+            if path.is_dir() {
+                walk(path, no_ignore, &mut paths).map_err(|error| io_error(path, error))?;
+            } else if path.exists() {
+                paths.push(path.to_path_buf());
+            } else {
+                return Err(io_error(path, io::ErrorKind::NotFound.into()));
+            }
+            continue;
+        }
+        let matches = glob::glob(arg).map_err(|err| {
+            io_error(
+                Path::new(arg),
+                io::Error::new(io::ErrorKind::InvalidInput, err),
+            )
+        })?;
+        for path in matches {
+            let path = path.map_err(|err| {
+                let path = err.path().to_path_buf();
+                io_error(&path, err.into_error())
+            })?;
+            if path.is_dir() {
+                walk(&path, no_ignore, &mut paths).map_err(|error| io_error(&path, error))?;
+            } else if is_script(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Adds the scripts in `dir` and its subdirectories to `paths`, skipping
+/// the ignored ones unless `no_ignore` is set.
+fn walk(dir: &Path, no_ignore: bool, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut walker = WalkBuilder::new(dir);
+    walker.standard_filters(!no_ignore).require_git(false);
+    if !no_ignore {
+        walker.add_custom_ignore_filename(".foxignore");
+    }
+    for entry in walker.build() {
+        let entry = entry.map_err(io::Error::other)?;
+        let is_file = entry.file_type().is_some_and(|ty| ty.is_file());
+        if is_file && is_script(entry.path()) {
+            paths.push(entry.into_path());
+        }
+    }
+    Ok(())
+}
+
+fn is_script(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "fox")
+}
+
+/// Checks `paths` using a thread per available core, returning the reports
+/// in the same order.
+fn check_files(paths: &[PathBuf]) -> Vec<FileReport> {
+    let threads = thread::available_parallelism().map_or(1, usize::from);
+    in_parallel(paths, threads, |path| check_file(path))
+}
+
+/// The stack size of the threads of `in_parallel`, which is enough for the
+/// interpreter to nest calls up to its limit even in debug builds, where
+/// each call takes tens of kilobytes.
+const STACK_SIZE: usize = 64 << 20;
+
+/// Runs `f` on each of `items` using up to `threads` threads, returning the
+/// results in the order of `items` whichever finishes first.
+pub(crate) fn in_parallel<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, items.len().max(1)) {
+            let worker = || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap().push((index, result));
+            };
+            thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn_scoped(scope, worker)
+                .expect("failed to spawn a worker thread");
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn check_file(path: &Path) -> FileReport {
+    let mut report = FileReport {
+        path: path.to_path_buf(),
+        errors: 0,
+        warnings: 0,
+        diagnostics: vec![],
+    };
+    let result = fs::read_to_string(path)
+        .map_err(|error| FoxError::Io {
+            path: path.display().to_string(),
+            error,
+        })
+        .and_then(|source| engine::parse(&SourceFile::new(path.display().to_string(), source)));
+    match result {
+        Ok((_, warnings)) => {
+            report.warnings = warnings.len();
+            report.diagnostics = warnings
+                .into_iter()
+                .map(|warning| format!("{:?}", Report::new(warning)))
+                .collect();
+        }
+        Err(err) => {
+            let reports = err.into_reports();
+            report.errors = reports.len();
+            report.diagnostics = reports.iter().map(|report| format!("{report:?}")).collect();
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use super::{check_files, discover};
+
+    #[test]
+    fn check_directories() {
+        let dir = std::env::temp_dir().join(format!("fox-check-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::create_dir_all(dir.join("vendor")).unwrap();
+        let files = [
+            ("main.fox", "print 1;"),
+            ("notes.txt", "not a script"),
+            ("lib/broken.fox", "let = 1; print @;"),
+            ("lib/warned.fox", "fn f() { return Error(1, 2, 3); } f();"),
+            (".foxignore", "vendor/\n*.gen.fox\n"),
+            ("vendor/dep.fox", "let = 1;"),
+            ("lib/table.gen.fox", "let = 1;"),
+        ];
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+
+        let paths = discover(&[dir.display().to_string()], false).unwrap();
+        let names = paths
+            .iter()
+            .map(|path| path.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["lib/broken.fox", "lib/warned.fox", "main.fox"].map(PathBuf::from)
+        );
+        let glob = format!("{}/*.fox", dir.display());
+        assert_eq!(discover(&[glob], false).unwrap(), [dir.join("main.fox")]);
+        let missing = dir.join("missing.fox").display().to_string();
+        assert!(discover(&[missing], false).is_err());
+        let all = discover(&[dir.display().to_string()], true).unwrap();
+        assert_eq!(all.len(), 5);
+        let explicit = dir.join("vendor/dep.fox");
+        assert_eq!(
+            discover(&[explicit.display().to_string()], false).unwrap(),
+            [explicit]
+        );
+
+        let counts = check_files(&paths)
+            .into_iter()
+            .map(|report| (report.errors, report.warnings))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [(1, 0), (0, 1), (0, 0)]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use std::{collections::HashMap, rc::Rc};
+
+use miette::SourceSpan;
+
+use crate::interpreter::Value;
+
+/// A single VM instruction. Operands index into the constant pool, the
+/// current call frame's stack slots or its closure's upvalues, or are jump
+/// offsets counted in instructions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(u16),
+    Nil,
+    True,
+    False,
+    Pop,
+    /// Pushes a copy of the value on top of the stack.
+    Dup,
+    GetLocal(u8),
+    SetLocal(u8),
+    GetUpvalue(u8),
+    SetUpvalue(u8),
+    /// Operand is the slot of the variable, see `GlobalSlots`.
+    DefineGlobal(u16),
+    GetGlobal(u16),
+    SetGlobal(u16),
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Range,
+    RangeInclusive,
+    Not,
+    Negate,
+    /// Joins the given number of values on top of the stack into a string.
+    Interpolate(u8),
+    /// Collects the given number of values on top of the stack into a list.
+    List(u8),
+    /// Creates an empty map.
+    Map,
+    /// Inserts the key and value on top of the stack into the map below
+    /// them.
+    MapEntry,
+    /// Replaces the list or map and index on top of the stack with the
+    /// element.
+    GetIndex,
+    /// Sets the element of the list or map at the index below the value on
+    /// top of the stack, leaving just the value.
+    SetIndex,
+    Print,
+    Jump(u16),
+    JumpIfFalse(u16),
+    /// Replaces the value on top of the stack with the list or range of
+    /// values a `for-in` loop iterates over, see `Iter`.
+    Iterate,
+    /// Pushes the next value of the list or range in the given local slot,
+    /// whose position is kept in the slot after it, or jumps by the given
+    /// offset once there are no more.
+    ForIn(u8, u16),
+    /// Fails with the subject on top of the stack matching no arm of a
+    /// `match`.
+    NoMatch,
+    /// Jumps backwards by the given offset.
+    Loop(u16),
+    /// Operand is the number of arguments on top of the callee.
+    Call(u8),
+    /// Operand indexes the chunk's prototypes.
+    Closure(u16),
+    /// Moves the local on top of the stack into the heap so closures
+    /// capturing it outlive the scope that declared it.
+    CloseUpvalue,
+    Return,
+}
+
+/// A compiled sequence of instructions with the data they refer to.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    /// Source span of every instruction in `code`, for diagnostics.
+    pub spans: Vec<SourceSpan>,
+    pub constants: Vec<Value>,
+    /// Functions declared in this chunk, instantiated by `Op::Closure`.
+    pub prototypes: Vec<Rc<Prototype>>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: Op, span: SourceSpan) -> usize {
+        self.code.push(op);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+}
+
+/// The slots of the globals of a `Vm`, which the `Compiler` gives each
+/// global the first time a script refers to it, so that the VM finds them
+/// by index rather than by name. They are kept across scripts, like the
+/// globals themselves.
+#[derive(Debug, Default)]
+pub struct GlobalSlots {
+    slots: HashMap<String, u16>,
+    names: Vec<String>,
+}
+
+impl GlobalSlots {
+    /// The slot of the global `name`, or `None` if it is new and every slot
+    /// is taken.
+    pub fn slot(&mut self, name: &str) -> Option<u16> {
+        if let Some(&slot) = self.slots.get(name) {
+            return Some(slot);
+        }
+        let slot = u16::try_from(self.names.len()).ok()?;
+        self.slots.insert(name.to_string(), slot);
+        self.names.push(name.to_string());
+        Some(slot)
+    }
+
+    /// The name of the global in `slot`.
+    pub fn name(&self, slot: u16) -> &str {
+        &self.names[slot as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+/// Where a closure finds a variable it captures when it is created: either
+/// a local slot of the enclosing function, or one of its upvalues.
+#[derive(Debug, Clone, Copy)]
+pub struct UpvalueSource {
+    pub is_local: bool,
+    pub index: u8,
+}
+
+/// A compiled function, which becomes callable once wrapped in a `Closure`
+/// along with the variables it captures.
+#[derive(Debug, Default)]
+pub struct Prototype {
+    pub name: String,
+    pub arity: usize,
+    pub chunk: Chunk,
+    pub upvalues: Vec<UpvalueSource>,
+}
@@ -0,0 +1,862 @@
+use std::{collections::HashSet, rc::Rc};
+
+use miette::SourceSpan;
+
+use crate::{
+    chunk::{Chunk, GlobalSlots, Op, Prototype, UpvalueSource},
+    diag::Diag,
+    errors::CompileError,
+    expr::{Expr, Literal, Pattern},
+    interpreter::Value,
+    lexer::{Keyword, Token, TokenType},
+    native,
+    source::SourceFile,
+    stmt::{FunctionStmt, Stmt},
+};
+
+struct Local {
+    name: String,
+    depth: usize,
+    /// Whether a closure captures this local, in which case it has to be
+    /// moved off the stack when it goes out of scope.
+    captured: bool,
+}
+
+/// A loop whose body is being compiled.
+struct Loop {
+    /// Scope depth of the locals that live on across iterations, which
+    /// `break` and `continue` keep on the stack.
+    scope_depth: usize,
+    /// The jumps of `break`, patched to land after the loop.
+    breaks: Vec<usize>,
+    /// The jumps of `continue`, patched to land after the body.
+    continues: Vec<usize>,
+}
+
+/// A function whose body is being compiled.
+struct FunctionState {
+    prototype: Prototype,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    /// Loops around the code being compiled, innermost last.
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(name: String, arity: usize) -> Self {
+        FunctionState {
+            prototype: Prototype {
+                name,
+                arity,
+                ..Default::default()
+            },
+            // Slot zero holds the function being called.
+            locals: vec![Local {
+                name: String::new(),
+                depth: 0,
+                captured: false,
+            }],
+            scope_depth: 0,
+            loops: vec![],
+        }
+    }
+}
+
+/// Where the value of a variable lives at runtime.
+enum Variable {
+    Local(u8),
+    Upvalue(u8),
+    /// Operand is the slot of the variable, see `GlobalSlots`.
+    Global(u16),
+}
+
+/// Lowers the statements produced by the `Parser` to bytecode for the `Vm`.
+///
+/// Unlike the `Interpreter`, which looks locals up by the depths recorded by
+/// the `Resolver`, the compiler resolves them itself to stack slots and
+/// upvalues. The resolver should still be run first for its diagnostics.
+/// Globals are resolved to the slots of the `Vm` which runs the script.
+/// Classes, modules, exceptions, `try` expressions and native functions are
+/// not supported yet.
+pub struct Compiler<'a> {
+    source: &'a SourceFile,
+    /// Functions being compiled, innermost last. The first one is the
+    /// top-level script.
+    functions: Vec<FunctionState>,
+    /// Span of the node being compiled, attributed to emitted instructions.
+    span: SourceSpan,
+    /// Globals the script declares at its top level, which shadow the
+    /// natives of the tree interpreter.
+    declared: HashSet<String>,
+    global_slots: &'a mut GlobalSlots,
+}
+
+impl<'a> Compiler<'a> {
+    /// A compiler for `source`, which is to run on the VM whose globals have
+    /// `global_slots`, see `Vm::global_slots`.
+    pub fn new(source: &'a SourceFile, global_slots: &'a mut GlobalSlots) -> Self {
+        Compiler {
+            source,
+            functions: vec![FunctionState::new("script".to_string(), 0)],
+            span: (0, 0).into(),
+            declared: HashSet::new(),
+            global_slots,
+        }
+    }
+
+    /// Compiles the script `stmts` into the prototype of a function taking
+    /// no arguments, which runs the script when called and returns the value
+    /// of its last statement if it is an expression, or else `nil`.
+    pub fn compile(mut self, stmts: &[Stmt]) -> Result<Prototype, CompileError> {
+        // Functions can refer to globals declared after them.
+        for stmt in stmts {
+            match stmt {
+                Stmt::Function(stmt) => self.declared.insert(stmt.name.lexeme()),
+                Stmt::Let(stmt) => self.declared.insert(stmt.name.lexeme()),
+                _ => continue,
+            };
+        }
+        match stmts.split_last() {
+            Some((Stmt::Expression(expr), rest)) => {
+                for stmt in rest {
+                    self.statement(stmt)?;
+                }
+                self.expression(expr)?;
+            }
+            _ => {
+                for stmt in stmts {
+                    self.statement(stmt)?;
+                }
+                self.emit(Op::Nil);
+            }
+        }
+        self.emit(Op::Return);
+        Ok(self.functions.pop().unwrap().prototype)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Block(stmts) => {
+                self.begin_scope();
+                for stmt in stmts {
+                    self.statement(stmt)?;
+                }
+                self.end_scope();
+            }
+            Stmt::Class(stmt) => return Err(self.unsupported(&stmt.name, "classes")),
+            Stmt::Export(stmt) => self.statement(&stmt.declaration)?,
+            Stmt::Import(stmt) => return Err(self.unsupported(&stmt.keyword, "modules")),
+            Stmt::Expression(expr) => {
+                self.expression(expr)?;
+                self.emit(Op::Pop);
+            }
+            Stmt::For(stmt) => {
+                self.begin_scope();
+                if let Some(initializer) = &stmt.initializer {
+                    self.statement(initializer)?;
+                }
+                let start = self.chunk().code.len();
+                let exit = match &stmt.condition {
+                    Some(condition) => {
+                        self.expression(condition)?;
+                        let exit = self.emit(Op::JumpIfFalse(0));
+                        self.emit(Op::Pop);
+                        Some(exit)
+                    }
+                    None => None,
+                };
+                let breaks = self.loop_body(&stmt.body)?;
+                if let Some(increment) = &stmt.increment {
+                    self.expression(increment)?;
+                    self.emit(Op::Pop);
+                }
+                self.emit_loop(start)?;
+                if let Some(exit) = exit {
+                    self.patch_jump(exit)?;
+                    self.emit(Op::Pop);
+                }
+                for jump in breaks {
+                    self.patch_jump(jump)?;
+                }
+                self.end_scope();
+            }
+            Stmt::ForIn(stmt) => {
+                // The list iterated over and the position in it live in
+                // locals no variable can refer to.
+                self.begin_scope();
+                self.expression(&stmt.iterable)?;
+                self.span = stmt.iterable_position.span();
+                self.emit(Op::Iterate);
+                let slot = self.declare_hidden()?;
+                let zero = self.constant(Value::Int(0))?;
+                self.emit(Op::Constant(zero));
+                self.declare_hidden()?;
+                let start = self.chunk().code.len();
+                let exit = self.emit(Op::ForIn(slot, 0));
+                // The variable is declared in the body's scope, so that
+                // every iteration gets its own one to capture.
+                let breaks = self.in_loop(|this| {
+                    this.begin_scope();
+                    this.declare(&stmt.name)?;
+                    this.statement(&stmt.body)?;
+                    this.end_scope();
+                    Ok(())
+                })?;
+                self.emit_loop(start)?;
+                self.patch_jump(exit)?;
+                for jump in breaks {
+                    self.patch_jump(jump)?;
+                }
+                self.end_scope();
+            }
+            Stmt::Function(stmt) => {
+                // Declared before the body is compiled so it can recurse.
+                let global = self.declare(&stmt.name)?;
+                self.function(stmt)?;
+                self.define(global);
+            }
+            Stmt::Guard(stmt) => {
+                self.expression(&stmt.condition)?;
+                let else_jump = self.emit(Op::JumpIfFalse(0));
+                self.emit(Op::Pop);
+                let end_jump = self.emit(Op::Jump(0));
+                self.patch_jump(else_jump)?;
+                self.emit(Op::Pop);
+                self.statement(&stmt.else_branch)?;
+                self.patch_jump(end_jump)?;
+            }
+            Stmt::If(stmt) => {
+                self.expression(&stmt.condition)?;
+                let then_jump = self.emit(Op::JumpIfFalse(0));
+                self.emit(Op::Pop);
+                self.statement(&stmt.then_branch)?;
+                let else_jump = self.emit(Op::Jump(0));
+                self.patch_jump(then_jump)?;
+                self.emit(Op::Pop);
+                if let Some(else_branch) = &stmt.else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(else_jump)?;
+            }
+            Stmt::Let(stmt) => {
+                match &stmt.initializer {
+                    Some(initializer) => self.expression(initializer)?,
+                    None => {
+                        self.emit(Op::Nil);
+                    }
+                }
+                let global = self.declare(&stmt.name)?;
+                self.define(global);
+            }
+            Stmt::Print(stmt) => {
+                self.expression(&stmt.value)?;
+                self.span = stmt.keyword.position.span();
+                self.emit(Op::Print);
+            }
+            Stmt::Return(stmt) => {
+                match &stmt.value {
+                    Some(value) => self.expression(value)?,
+                    None => {
+                        self.emit(Op::Nil);
+                    }
+                }
+                self.span = stmt.keyword.position.span();
+                self.emit(Op::Return);
+            }
+            Stmt::Throw(stmt) => return Err(self.unsupported(&stmt.keyword, "exceptions")),
+            Stmt::Try(stmt) => return Err(self.unsupported(&stmt.name, "exceptions")),
+            Stmt::While(stmt) => {
+                let start = self.chunk().code.len();
+                self.expression(&stmt.condition)?;
+                let exit = self.emit(Op::JumpIfFalse(0));
+                self.emit(Op::Pop);
+                let breaks = self.loop_body(&stmt.body)?;
+                self.emit_loop(start)?;
+                self.patch_jump(exit)?;
+                self.emit(Op::Pop);
+                for jump in breaks {
+                    self.patch_jump(jump)?;
+                }
+            }
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => {
+                self.span = keyword.position.span();
+                let function = self.functions.last().unwrap();
+                let depth = function
+                    .loops
+                    .last()
+                    .expect("the resolver rejects `break` and `continue` outside loops")
+                    .scope_depth;
+                // Leaves the scopes of the body, without ending them, as
+                // the code after the jump still is in them.
+                let ops = function
+                    .locals
+                    .iter()
+                    .rev()
+                    .take_while(|local| local.depth > depth)
+                    .map(|local| {
+                        if local.captured {
+                            Op::CloseUpvalue
+                        } else {
+                            Op::Pop
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                for op in ops {
+                    self.emit(op);
+                }
+                let jump = self.emit(Op::Jump(0));
+                let current = self.functions.last_mut().unwrap().loops.last_mut().unwrap();
+                if let Stmt::Break(_) = stmt {
+                    current.breaks.push(jump);
+                } else {
+                    current.continues.push(jump);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles the body of a loop, patching the jumps of its `continue`
+    /// statements to land right after it. Returns the jumps of its `break`
+    /// statements, to be patched once the loop is compiled.
+    fn loop_body(&mut self, body: &Stmt) -> Result<Vec<usize>, CompileError> {
+        self.in_loop(|this| this.statement(body))
+    }
+
+    /// Compiles the body of a loop with `body`, see `loop_body`.
+    fn in_loop<F>(&mut self, body: F) -> Result<Vec<usize>, CompileError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), CompileError>,
+    {
+        let function = self.functions.last_mut().unwrap();
+        function.loops.push(Loop {
+            scope_depth: function.scope_depth,
+            breaks: vec![],
+            continues: vec![],
+        });
+        let result = body(self);
+        let body = self.functions.last_mut().unwrap().loops.pop().unwrap();
+        result?;
+        for jump in body.continues {
+            self.patch_jump(jump)?;
+        }
+        Ok(body.breaks)
+    }
+
+    /// Compiles the body of `stmt` and emits the instruction creating a
+    /// closure over it.
+    fn function(&mut self, stmt: &FunctionStmt) -> Result<(), CompileError> {
+        if let Some(param) = stmt.params.get(u8::MAX as usize) {
+            self.span = param.position.span();
+            return Err(self.limit("parameters", u8::MAX as usize));
+        }
+        self.functions
+            .push(FunctionState::new(stmt.name.lexeme(), stmt.params.len()));
+        self.begin_scope();
+        for param in &stmt.params {
+            self.declare(param)?;
+        }
+        for stmt in &stmt.body {
+            self.statement(stmt)?;
+        }
+        self.emit(Op::Nil);
+        self.emit(Op::Return);
+        // Returning discards the function's stack slots, so there is no need
+        // to end its scope.
+        let function = self.functions.pop().unwrap();
+
+        self.span = stmt.name.position.span();
+        let index = u16::try_from(self.chunk().prototypes.len())
+            .map_err(|_| self.limit("functions", u16::MAX as usize + 1))?;
+        self.chunk_mut()
+            .prototypes
+            .push(Rc::new(function.prototype));
+        self.emit(Op::Closure(index));
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match expr {
+            Expr::Assign(expr) => {
+                self.expression(&expr.value)?;
+                self.span = expr.name.position.span();
+                let op = match self.resolve(&expr.name)? {
+                    Variable::Local(slot) => Op::SetLocal(slot),
+                    Variable::Upvalue(index) => Op::SetUpvalue(index),
+                    Variable::Global(name) => Op::SetGlobal(name),
+                };
+                self.emit(op);
+            }
+            Expr::Binary(expr) => {
+                self.expression(&expr.lhs)?;
+                self.expression(&expr.rhs)?;
+                self.span = expr.operator.position.span();
+                let op = match expr.operator.ty {
+                    TokenType::BangEq => {
+                        self.emit(Op::Equal);
+                        Op::Not
+                    }
+                    TokenType::EqualEq => Op::Equal,
+                    TokenType::Greater => Op::Greater,
+                    TokenType::GreaterEq => Op::GreaterEqual,
+                    TokenType::Less => Op::Less,
+                    TokenType::LessEq => Op::LessEqual,
+                    TokenType::Minus => Op::Subtract,
+                    TokenType::Plus => Op::Add,
+                    TokenType::Slash => Op::Divide,
+                    TokenType::Star => Op::Multiply,
+                    TokenType::Percent => Op::Remainder,
+                    TokenType::StarStar => Op::Power,
+                    TokenType::Ampersand => Op::BitAnd,
+                    TokenType::Pipe => Op::BitOr,
+                    TokenType::Caret => Op::BitXor,
+                    TokenType::LessLess => Op::ShiftLeft,
+                    TokenType::GreaterGreater => Op::ShiftRight,
+                    TokenType::DotDot => Op::Range,
+                    TokenType::DotDotEq => Op::RangeInclusive,
+                    _ => unreachable!(),
+                };
+                self.emit(op);
+            }
+            Expr::Call(expr) => {
+                self.expression(&expr.callee)?;
+                for argument in &expr.arguments {
+                    self.expression(argument)?;
+                }
+                self.span = expr.paren.position.span();
+                let count = u8::try_from(expr.arguments.len())
+                    .map_err(|_| self.limit("arguments", u8::MAX as usize))?;
+                self.emit(Op::Call(count));
+            }
+            Expr::Conditional(expr) => {
+                self.expression(&expr.condition)?;
+                let then_jump = self.emit(Op::JumpIfFalse(0));
+                self.emit(Op::Pop);
+                self.expression(&expr.then_branch)?;
+                let else_jump = self.emit(Op::Jump(0));
+                self.patch_jump(then_jump)?;
+                self.emit(Op::Pop);
+                self.expression(&expr.else_branch)?;
+                self.patch_jump(else_jump)?;
+            }
+            Expr::Logical(expr) => {
+                self.expression(&expr.lhs)?;
+                let end_jump = if let TokenType::Keyword(Keyword::Or) = expr.operator.ty {
+                    let else_jump = self.emit(Op::JumpIfFalse(0));
+                    let end_jump = self.emit(Op::Jump(0));
+                    self.patch_jump(else_jump)?;
+                    end_jump
+                } else {
+                    self.emit(Op::JumpIfFalse(0))
+                };
+                self.emit(Op::Pop);
+                self.expression(&expr.rhs)?;
+                self.patch_jump(end_jump)?;
+            }
+            // Primitive values have methods too, like `"fox".len()`.
+            Expr::Get(expr) => {
+                return Err(self.unsupported(&expr.name, "methods and module members"))
+            }
+            Expr::Grouping(expr) => self.expression(&expr.expr)?,
+            Expr::Index(expr) => {
+                self.expression(&expr.object)?;
+                self.expression(&expr.index)?;
+                self.span = expr.index_position.span();
+                self.emit(Op::GetIndex);
+            }
+            Expr::IndexSet(expr) => {
+                self.expression(&expr.object)?;
+                self.expression(&expr.index)?;
+                self.expression(&expr.value)?;
+                self.span = expr.index_position.span();
+                self.emit(Op::SetIndex);
+            }
+            Expr::List(expr) => {
+                for element in &expr.elements {
+                    self.expression(element)?;
+                }
+                self.span = expr.bracket.position.span();
+                let count = u8::try_from(expr.elements.len())
+                    .map_err(|_| self.limit("list elements", u8::MAX as usize))?;
+                self.emit(Op::List(count));
+            }
+            Expr::Map(expr) => {
+                self.span = expr.brace.position.span();
+                self.emit(Op::Map);
+                for entry in &expr.entries {
+                    self.expression(&entry.key)?;
+                    self.expression(&entry.value)?;
+                    self.span = entry.key_position.span();
+                    self.emit(Op::MapEntry);
+                }
+            }
+            Expr::Interpolation(expr) => {
+                for part in &expr.parts {
+                    self.expression(part)?;
+                }
+                self.span = expr.start.position.span();
+                let count = u8::try_from(expr.parts.len())
+                    .map_err(|_| self.limit("interpolated parts", u8::MAX as usize))?;
+                self.emit(Op::Interpolate(count));
+            }
+            Expr::Literal(literal) => self.literal(literal)?,
+            Expr::Match(expr) => {
+                // The subject stays on top of the stack while the arms are
+                // tried, and is replaced by the body of the chosen one.
+                self.expression(&expr.subject)?;
+                let mut ends = vec![];
+                for arm in &expr.arms {
+                    let mut fails = vec![];
+                    match &arm.pattern {
+                        Pattern::Literal { value, position } => {
+                            self.emit(Op::Dup);
+                            self.literal(value)?;
+                            self.span = position.span();
+                            self.emit(Op::Equal);
+                            fails.push(self.emit(Op::JumpIfFalse(0)));
+                            self.emit(Op::Pop);
+                        }
+                        Pattern::Wildcard(_) => {}
+                        Pattern::Binding(name) => {
+                            return Err(self.unsupported(name, "binding patterns"))
+                        }
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard)?;
+                        fails.push(self.emit(Op::JumpIfFalse(0)));
+                        self.emit(Op::Pop);
+                    }
+                    self.emit(Op::Pop);
+                    self.expression(&arm.body)?;
+                    ends.push(self.emit(Op::Jump(0)));
+                    if !fails.is_empty() {
+                        for jump in fails {
+                            self.patch_jump(jump)?;
+                        }
+                        self.emit(Op::Pop);
+                    }
+                }
+                self.span = expr.subject_position.span();
+                self.emit(Op::NoMatch);
+                for jump in ends {
+                    self.patch_jump(jump)?;
+                }
+            }
+            Expr::Set(expr) => return Err(self.unsupported(&expr.name, "classes")),
+            Expr::Super(expr) => return Err(self.unsupported(&expr.keyword, "classes")),
+            Expr::This(expr) => return Err(self.unsupported(&expr.keyword, "classes")),
+            Expr::Try(expr) => return Err(self.unsupported(&expr.keyword, "`try` expressions")),
+            Expr::Unary(expr) => {
+                self.expression(&expr.rhs)?;
+                self.span = expr.operator.position.span();
+                let op = match expr.operator.ty {
+                    TokenType::Bang => Op::Not,
+                    TokenType::Minus => Op::Negate,
+                    _ => unreachable!(),
+                };
+                self.emit(op);
+            }
+            Expr::Variable(expr) => {
+                self.span = expr.name.position.span();
+                let op = match self.resolve(&expr.name)? {
+                    Variable::Local(slot) => Op::GetLocal(slot),
+                    Variable::Upvalue(index) => Op::GetUpvalue(index),
+                    Variable::Global(name) => Op::GetGlobal(name),
+                };
+                self.emit(op);
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares `name` in the current scope, returning its slot if it is a
+    /// global. Locals need no instructions as they simply live in the stack
+    /// slot their value was pushed to.
+    fn declare(&mut self, name: &Token) -> Result<Option<u16>, CompileError> {
+        self.span = name.position.span();
+        let function = self.functions.last().unwrap();
+        if function.scope_depth == 0 {
+            return self.global_slot(&name.lexeme()).map(Some);
+        }
+        if function.locals.len() > u8::MAX as usize {
+            return Err(self.limit("local variables", u8::MAX as usize + 1));
+        }
+        let depth = function.scope_depth;
+        self.functions.last_mut().unwrap().locals.push(Local {
+            name: name.lexeme(),
+            depth,
+            captured: false,
+        });
+        Ok(None)
+    }
+
+    /// Declares a local in the current scope that no variable refers to,
+    /// returning its slot.
+    fn declare_hidden(&mut self) -> Result<u8, CompileError> {
+        let function = self.functions.last().unwrap();
+        if function.locals.len() > u8::MAX as usize {
+            return Err(self.limit("local variables", u8::MAX as usize + 1));
+        }
+        let depth = function.scope_depth;
+        let locals = &mut self.functions.last_mut().unwrap().locals;
+        locals.push(Local {
+            name: String::new(),
+            depth,
+            captured: false,
+        });
+        Ok((locals.len() - 1) as u8)
+    }
+
+    fn define(&mut self, global: Option<u16>) {
+        if let Some(slot) = global {
+            self.emit(Op::DefineGlobal(slot));
+        }
+    }
+
+    fn global_slot(&mut self, name: &str) -> Result<u16, CompileError> {
+        match self.global_slots.slot(name) {
+            Some(slot) => Ok(slot),
+            None => Err(self.limit("global variables", u16::MAX as usize + 1)),
+        }
+    }
+
+    fn resolve(&mut self, token: &Token) -> Result<Variable, CompileError> {
+        let name = token.lexeme();
+        let current = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(current, &name) {
+            return Ok(Variable::Local(slot));
+        }
+        if let Some(index) = self.resolve_upvalue(current, &name)? {
+            return Ok(Variable::Upvalue(index));
+        }
+        if !self.declared.contains(&name) {
+            if name == "std" {
+                return Err(self.unsupported(token, "modules"));
+            }
+            if name == "debug" || native::builtins().iter().any(|f| f.name == name) {
+                return Err(self.unsupported(token, "native functions"));
+            }
+        }
+        self.global_slot(&name).map(Variable::Global)
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    /// Looks `name` up in the functions enclosing `function`, capturing it
+    /// in every function in between.
+    fn resolve_upvalue(&mut self, function: usize, name: &str) -> Result<Option<u8>, CompileError> {
+        if function == 0 {
+            return Ok(None);
+        }
+        let enclosing = function - 1;
+        let source = if let Some(slot) = self.resolve_local(enclosing, name) {
+            self.functions[enclosing].locals[slot as usize].captured = true;
+            UpvalueSource {
+                is_local: true,
+                index: slot,
+            }
+        } else if let Some(index) = self.resolve_upvalue(enclosing, name)? {
+            UpvalueSource {
+                is_local: false,
+                index,
+            }
+        } else {
+            return Ok(None);
+        };
+
+        let upvalues = &self.functions[function].prototype.upvalues;
+        if let Some(index) = upvalues
+            .iter()
+            .position(|u| u.is_local == source.is_local && u.index == source.index)
+        {
+            return Ok(Some(index as u8));
+        }
+        if upvalues.len() > u8::MAX as usize {
+            return Err(self.limit("captured variables", u8::MAX as usize + 1));
+        }
+        let upvalues = &mut self.functions[function].prototype.upvalues;
+        upvalues.push(source);
+        Ok(Some((upvalues.len() - 1) as u8))
+    }
+
+    fn begin_scope(&mut self) {
+        self.functions.last_mut().unwrap().scope_depth += 1;
+    }
+
+    /// Pops the locals declared in the scope being ended off the stack.
+    fn end_scope(&mut self) {
+        let function = self.functions.last_mut().unwrap();
+        function.scope_depth -= 1;
+        let mut ops = vec![];
+        while let Some(local) = function.locals.last() {
+            if local.depth <= function.scope_depth {
+                break;
+            }
+            ops.push(if local.captured {
+                Op::CloseUpvalue
+            } else {
+                Op::Pop
+            });
+            function.locals.pop();
+        }
+        for op in ops {
+            self.emit(op);
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> Result<u16, CompileError> {
+        let constants = &self.chunk().constants;
+        if let Some(index) = constants.iter().position(|constant| constant == &value) {
+            return Ok(index as u16);
+        }
+        let index = u16::try_from(constants.len())
+            .map_err(|_| self.limit("constants", u16::MAX as usize + 1))?;
+        self.chunk_mut().constants.push(value);
+        Ok(index)
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let span = self.span;
+        self.chunk_mut().write(op, span)
+    }
+
+    /// Makes the jump at `at` land right after the last emitted instruction.
+    fn patch_jump(&mut self, at: usize) -> Result<(), CompileError> {
+        let offset = u16::try_from(self.chunk().code.len() - at - 1)
+            .map_err(|_| self.limit("instructions in a jump", u16::MAX as usize))?;
+        match &mut self.chunk_mut().code[at] {
+            Op::Jump(jump) | Op::JumpIfFalse(jump) | Op::ForIn(_, jump) => *jump = offset,
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize) -> Result<(), CompileError> {
+        let offset = u16::try_from(self.chunk().code.len() - start + 1)
+            .map_err(|_| self.limit("instructions in a loop", u16::MAX as usize))?;
+        self.emit(Op::Loop(offset));
+        Ok(())
+    }
+
+    fn chunk(&self) -> &Chunk {
+        &self.functions.last().unwrap().prototype.chunk
+    }
+
+    fn chunk_mut(&mut self) -> &mut Chunk {
+        &mut self.functions.last_mut().unwrap().prototype.chunk
+    }
+
+    fn literal(&mut self, literal: &Literal) -> Result<(), CompileError> {
+        let op = match literal {
+            Literal::Int(int) => Op::Constant(self.constant(Value::Int(*int))?),
+            Literal::Number(Some(num)) => Op::Constant(self.constant(Value::Float(*num))?),
+            Literal::String(Some(str)) => {
+                Op::Constant(self.constant(Value::String(str.to_string()))?)
+            }
+            Literal::Bool(true) => Op::True,
+            Literal::Bool(false) => Op::False,
+            Literal::Number(None) | Literal::String(None) | Literal::Nil => Op::Nil,
+        };
+        self.emit(op);
+        Ok(())
+    }
+
+    fn unsupported(&self, token: &Token, feature: &'static str) -> CompileError {
+        Diag::error("compile::unsupported")
+            .arg("feature", feature)
+            .msg("{feature} are not supported by the VM backend yet")
+            .source(self.source)
+            .primary(token.position.span(), "used here")
+            .help("run the script with `--backend=tree` instead")
+            .into()
+    }
+
+    fn limit(&self, what: &'static str, limit: usize) -> CompileError {
+        Diag::error("compile::limit_exceeded")
+            .arg("what", what)
+            .arg("limit", limit)
+            .msg("Too many {what} in one function")
+            .source(self.source)
+            .primary(self.span, "limit exceeded here")
+            .help("at most {limit} are allowed")
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        chunk::{GlobalSlots, Op, Prototype},
+        errors::CompileError,
+        lexer::Lexer,
+        parser::Parser,
+        source::SourceFile,
+    };
+
+    use super::Compiler;
+
+    fn compile(source: &str) -> Result<Prototype, CompileError> {
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Compiler::new(source, &mut GlobalSlots::default()).compile(&stmts)
+    }
+
+    #[test]
+    fn check_code() {
+        let script = compile("let a = 1; { let b = a >= 2; print !b; }").unwrap();
+        assert_eq!(
+            script.chunk.code,
+            [
+                Op::Constant(0),
+                Op::DefineGlobal(0),
+                Op::GetGlobal(0),
+                Op::Constant(1),
+                Op::GreaterEqual,
+                Op::GetLocal(1),
+                Op::Not,
+                Op::Print,
+                Op::Pop,
+                Op::Nil,
+                Op::Return,
+            ]
+        );
+
+        let script = compile("fn f(x) { fn g() { return x; } return g; }").unwrap();
+        let f = &script.chunk.prototypes[0];
+        assert_eq!(f.arity, 1);
+        let g = &f.chunk.prototypes[0];
+        assert!(matches!(g.upvalues[..], [u] if u.is_local && u.index == 1));
+        assert_eq!(g.chunk.code[..2], [Op::GetUpvalue(0), Op::Return]);
+    }
+
+    #[test]
+    fn check_errors() {
+        let error = |source: &str| match compile(source) {
+            Ok(_) => panic!("`{source}` compiled"),
+            Err(err) => err,
+        };
+        for (source, feature) in [
+            ("class A {}", "classes"),
+            ("fn f() { return this; }", "classes"),
+            ("fn f(m) { return keys(m); }", "native functions"),
+            ("print std;", "modules"),
+            ("\"fox\".len();", "methods and module members"),
+        ] {
+            let err = error(source);
+            assert_eq!(err.code, "compile::unsupported", "{source}");
+            assert_eq!(err.value("feature"), Some(feature), "{source}");
+        }
+        assert!(compile("fn f(m) { return keys(m); } fn keys(m) { return m; }").is_ok());
+
+        let params = (0..256).map(|i| format!("p{i}")).collect::<Vec<_>>();
+        let source = format!("fn f({}) {{}}", params.join(", "));
+        assert_eq!(error(&source).code, "compile::limit_exceeded");
+    }
+}
@@ -0,0 +1,390 @@
+use std::{error::Error, fmt, marker::PhantomData};
+
+use miette::{Diagnostic, LabeledSpan, NamedSource, Report, Severity, SourceCode, SourceSpan};
+use serde::Serialize;
+
+use crate::{locale, source::SourceFile};
+
+/// How bad a `Diag` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagLevel {
+    Error,
+    Warning,
+}
+
+/// A piece of source code pointed at by a `Diag`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiagLabel {
+    pub offset: usize,
+    pub len: usize,
+    pub text: String,
+    /// Whether this is the span the diagnostic is about, rather than one
+    /// giving context to it.
+    pub primary: bool,
+}
+
+/// The state of a `Diag` without source code yet, see `Diag::source`.
+#[derive(Debug)]
+pub struct NoSource;
+
+/// The state of a `Diag` with source code but no primary label pointing
+/// into it yet, see `Diag::primary`.
+#[derive(Debug)]
+pub struct NoPrimary;
+
+/// The state of a `Diag` that is complete and can be raised.
+#[derive(Debug)]
+pub struct Complete;
+
+/// A diagnostic built piece by piece, which every phase of Fox raises its
+/// errors and warnings with:
+///
+/// ```text
+/// Diag::error("runtime::index_out_of_bounds")
+///     .arg("index", 3)
+///     .arg("len", 2)
+///     .msg("Index {index} is out of bounds for a list of length {len}")
+///     .source(source)
+///     .primary(span, "evaluates to {index}")
+///     .help("indices start at 0 and go up to the length of the list minus one")
+/// ```
+///
+/// The code names the diagnostic, and its part before `::` is the phase
+/// raising it. Texts are filled with the args named in their braces, so
+/// args are given before the texts using them, and `{{` and `}}` stand
+/// for braces. Each text is replaced by its translation in the installed
+/// `Catalog` where it has one, keyed by the code.
+///
+/// Diagnostics follow a style guide: messages read `<Phase> error: Message`
+/// or `<Phase> warning: Message`, labels and help are lowercase, and none
+/// of them end with a period, which debug builds assert. The state `S`
+/// makes sure a diagnostic with source code can only be raised once its
+/// primary label points into it.
+#[derive(Debug, Serialize)]
+pub struct Diag<S = Complete> {
+    pub level: DiagLevel,
+    pub code: &'static str,
+    /// Like `Runtime error`, written before the message.
+    pub prefix: String,
+    pub message: String,
+    pub labels: Vec<DiagLabel>,
+    pub help: Option<String>,
+    #[serde(skip)]
+    args: Vec<(&'static str, String)>,
+    #[serde(skip)]
+    src: Option<NamedSource>,
+    /// The errors that caused this one, like those of an imported module.
+    #[serde(skip)]
+    related: Vec<Report>,
+    #[serde(skip)]
+    state: PhantomData<S>,
+}
+
+impl Diag<NoSource> {
+    /// An error named `code`, like `parse::unclosed`.
+    pub fn error(code: &'static str) -> Self {
+        Diag::new(DiagLevel::Error, code)
+    }
+
+    pub fn warning(code: &'static str) -> Self {
+        Diag::new(DiagLevel::Warning, code)
+    }
+
+    fn new(level: DiagLevel, code: &'static str) -> Self {
+        let phase = code.split_once("::").map_or(code, |(phase, _)| phase);
+        let level_name = match level {
+            DiagLevel::Error => "error",
+            DiagLevel::Warning => "warning",
+        };
+        let prefix = locale::catalog()
+            .and_then(|catalog| catalog.translate(&format!("{phase}::{level_name}"), &[]))
+            .unwrap_or_else(|| format!("{} {level_name}", capitalize(phase)));
+        Diag {
+            level,
+            code,
+            prefix,
+            message: String::new(),
+            labels: vec![],
+            help: None,
+            args: vec![],
+            src: None,
+            related: vec![],
+            state: PhantomData,
+        }
+    }
+
+    pub fn source(self, source: &SourceFile) -> Diag<NoPrimary> {
+        let mut diag = self.into_state();
+        diag.src = Some(source.named_source());
+        diag
+    }
+
+    /// Completes a diagnostic which isn't about any code in particular, like
+    /// running out of time.
+    pub fn without_source(self) -> Diag {
+        self.into_state()
+    }
+}
+
+impl Diag<NoPrimary> {
+    /// Points at the span the diagnostic is about.
+    pub fn primary(self, span: SourceSpan, text: &str) -> Diag {
+        self.label(span, ".primary", text, true).into_state()
+    }
+}
+
+impl Diag {
+    /// Points at a span giving context to the primary one.
+    pub fn secondary(self, span: SourceSpan, text: &str) -> Self {
+        self.label(span, ".secondary", text, false)
+    }
+}
+
+impl<S> Diag<S> {
+    /// Sets the value of `{name}` in the texts given from now on.
+    pub fn arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+
+    /// Sets the message, after the prefix. Its first letter is capitalized.
+    pub fn msg(mut self, message: &str) -> Self {
+        debug_assert_eq!(ends_with_period(message), None);
+        self.message = capitalize(&self.text("", message));
+        self
+    }
+
+    pub fn help(mut self, help: &str) -> Self {
+        debug_assert_eq!(text_violation(help), None);
+        self.help = Some(self.text(".help", help));
+        self
+    }
+
+    pub fn related(mut self, errors: Vec<Report>) -> Self {
+        self.related = errors;
+        self
+    }
+
+    /// The value given to the arg `name`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        let (_, value) = self.args.iter().rev().find(|(arg, _)| *arg == name)?;
+        Some(value)
+    }
+
+    /// The span pointed at by the primary label, if there is one.
+    pub fn span(&self) -> Option<SourceSpan> {
+        let label = self.labels.iter().find(|label| label.primary)?;
+        Some(SourceSpan::new(label.offset.into(), label.len))
+    }
+
+    fn label(mut self, span: SourceSpan, part: &str, text: &str, primary: bool) -> Self {
+        debug_assert_eq!(text_violation(text), None);
+        let text = self.text(part, text);
+        self.labels.push(DiagLabel {
+            offset: span.offset(),
+            len: span.len(),
+            text,
+            primary,
+        });
+        self
+    }
+
+    /// The translation of the `part` of the diagnostic, like `.help`, or
+    /// `template` filled with the args where there is none.
+    fn text(&self, part: &str, template: &str) -> String {
+        let key = format!("{}{part}", self.code);
+        if let Some(translation) =
+            locale::catalog().and_then(|catalog| catalog.translate(&key, &self.args))
+        {
+            return translation;
+        }
+        let filled = fill(template, &self.args);
+        debug_assert!(filled.is_some(), "`{template}` names an arg not given");
+        filled.unwrap_or_else(|| template.to_string())
+    }
+
+    fn into_state<T>(self) -> Diag<T> {
+        Diag {
+            level: self.level,
+            code: self.code,
+            prefix: self.prefix,
+            message: self.message,
+            labels: self.labels,
+            help: self.help,
+            args: self.args,
+            src: self.src,
+            related: self.related,
+            state: PhantomData,
+        }
+    }
+}
+
+/// Fills the values of `args` into the `{name}` of `template`, or `None` if
+/// one of them isn't given.
+pub(crate) fn fill(template: &str, args: &[(&str, String)]) -> Option<String> {
+    let mut filled = String::new();
+    let mut chars = template.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                filled.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                filled.push('}');
+            }
+            '{' => {
+                let (name, rest) = chars.as_str().split_once('}')?;
+                let (_, value) = args.iter().rev().find(|(arg, _)| *arg == name)?;
+                filled.push_str(value);
+                chars = rest.chars();
+            }
+            ch => filled.push(ch),
+        }
+    }
+    Some(filled)
+}
+
+/// Why the label or help `text` isn't lowercase without a trailing period.
+pub(crate) fn text_violation(text: &str) -> Option<String> {
+    if text.starts_with(char::is_uppercase) {
+        return Some(format!("`{text}` isn't lowercase"));
+    }
+    ends_with_period(text)
+}
+
+fn ends_with_period(text: &str) -> Option<String> {
+    (text.ends_with('.') && !text.ends_with("...")).then(|| format!("`{text}` ends with a period"))
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl fmt::Display for Diag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.prefix, self.message)
+    }
+}
+
+impl Error for Diag {}
+
+impl Diagnostic for Diag {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code))
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        match self.level {
+            DiagLevel::Error => Some(Severity::Error),
+            DiagLevel::Warning => Some(Severity::Warning),
+        }
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = self.help.as_ref()?;
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.src.as_ref().map(|src| src as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        if self.labels.is_empty() {
+            return None;
+        }
+        let labels = self.labels.iter().map(|label| {
+            let span = SourceSpan::new(label.offset.into(), label.len);
+            let text = Some(label.text.clone());
+            if label.primary {
+                LabeledSpan::new_primary_with_span(text, span)
+            } else {
+                LabeledSpan::new_with_span(text, span)
+            }
+        });
+        Some(Box::new(labels))
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        if self.related.is_empty() {
+            return None;
+        }
+        let related = self
+            .related
+            .iter()
+            .map(|report| &**report as &dyn Diagnostic);
+        Some(Box::new(related))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use miette::Diagnostic;
+
+    use crate::source::SourceFile;
+
+    use super::{fill, text_violation, Diag, DiagLevel};
+
+    #[test]
+    fn check_builder() {
+        let source = SourceFile::new("test.fox", "xs[3];");
+        let diag = Diag::error("runtime::index_out_of_bounds")
+            .arg("index", 3)
+            .arg("len", 2)
+            .msg("index {index} is out of bounds for a list of length {len}")
+            .source(&source)
+            .primary((3, 1).into(), "evaluates to {index}")
+            .secondary((0, 2).into(), "has {len} elements")
+            .help("indices start at 0");
+        assert_eq!(
+            diag.to_string(),
+            "Runtime error: Index 3 is out of bounds for a list of length 2"
+        );
+        assert_eq!(diag.labels[0].text, "evaluates to 3");
+        assert!(diag.labels[0].primary && !diag.labels[1].primary);
+        assert_eq!(diag.span(), Some((3, 1).into()));
+        assert_eq!(diag.value("len"), Some("2"));
+        assert_eq!(diag.value("found"), None);
+        assert_eq!(
+            diag.code().unwrap().to_string(),
+            "runtime::index_out_of_bounds"
+        );
+        assert!(diag.source_code().is_some());
+
+        let diag = Diag::warning("resolve::unused")
+            .msg("unused")
+            .without_source();
+        assert_eq!(diag.to_string(), "Resolve warning: Unused");
+        assert_eq!(diag.level, DiagLevel::Warning);
+        assert_eq!(diag.span(), None);
+    }
+
+    #[test]
+    fn check_fill() {
+        let args = [("name", "fox".to_string())];
+        assert_eq!(fill("`{name}` {{ {{}} }}", &args).unwrap(), "`fox` { {} }");
+        assert_eq!(fill("`{kind}`", &args), None);
+        assert_eq!(fill("`{name`", &args), None);
+    }
+
+    #[test]
+    fn check_style() {
+        assert_eq!(text_violation("not defined"), None);
+        assert_eq!(text_violation("and so on..."), None);
+        assert!(text_violation("Not defined").is_some());
+        assert!(text_violation("not defined.").is_some());
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_style_asserted() {
+        let _ = Diag::error("runtime::undefined_variable").help("Declare it first.");
+    }
+}
@@ -0,0 +1,319 @@
+use std::{collections::HashSet, fmt::Display, rc::Rc};
+
+use crate::interpreter::Value;
+
+/// A single difference between two values, as found by `diff`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Fields, list indices and map keys leading from the compared values to
+    /// the differing ones, like `.config.ports[0]` or `["db"].port`. Empty
+    /// if the compared values differ themselves.
+    pub path: String,
+    pub kind: DifferenceKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DifferenceKind {
+    /// The field or element is only set in the first value.
+    Missing(Value),
+    /// The field or element is only set in the second value.
+    Added(Value),
+    /// The values have the same type but are not equal.
+    Changed(Value, Value),
+    /// The values have different types, or are instances of different
+    /// classes.
+    TypeChanged(Value, Value),
+}
+
+/// Compares `a` to `b`, walking into the fields of instances of the same
+/// class and the elements of lists and maps to report every differing field
+/// and element rather than just the instances, lists and maps.
+/// Returns no differences if the values are equal.
+pub fn diff(a: &Value, b: &Value) -> Vec<Difference> {
+    let mut differences = vec![];
+    diff_at(
+        a,
+        b,
+        &mut String::new(),
+        &mut HashSet::new(),
+        &mut differences,
+    );
+    differences
+}
+
+fn diff_at(
+    a: &Value,
+    b: &Value,
+    path: &mut String,
+    // Instance, list and map pairs already compared, so that cyclic values
+    // terminate.
+    visited: &mut HashSet<(*const (), *const ())>,
+    differences: &mut Vec<Difference>,
+) {
+    let kind = match (a, b) {
+        (Value::Instance(a), Value::Instance(b)) if Rc::ptr_eq(&a.class, &b.class) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+            if Rc::ptr_eq(a, b) || !visited.insert(pair) {
+                return;
+            }
+            let a_fields = a.fields.borrow();
+            let b_fields = b.fields.borrow();
+            let mut names = a_fields.keys().chain(b_fields.keys()).collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let len = path.len();
+                path.push('.');
+                path.push_str(name);
+                match (a_fields.get(name), b_fields.get(name)) {
+                    (Some(a), Some(b)) => diff_at(a, b, path, visited, differences),
+                    (Some(a), None) => differences.push(Difference {
+                        path: path.to_string(),
+                        kind: DifferenceKind::Missing(a.clone()),
+                    }),
+                    (None, Some(b)) => differences.push(Difference {
+                        path: path.to_string(),
+                        kind: DifferenceKind::Added(b.clone()),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+            return;
+        }
+        (Value::List(a), Value::List(b)) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+            if Rc::ptr_eq(a, b) || !visited.insert(pair) {
+                return;
+            }
+            let a_elements = a.elements.borrow();
+            let b_elements = b.elements.borrow();
+            for i in 0..a_elements.len().max(b_elements.len()) {
+                let len = path.len();
+                path.push_str(&format!("[{i}]"));
+                match (a_elements.get(i), b_elements.get(i)) {
+                    (Some(a), Some(b)) => diff_at(a, b, path, visited, differences),
+                    (Some(a), None) => differences.push(Difference {
+                        path: path.to_string(),
+                        kind: DifferenceKind::Missing(a.clone()),
+                    }),
+                    (None, Some(b)) => differences.push(Difference {
+                        path: path.to_string(),
+                        kind: DifferenceKind::Added(b.clone()),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+            return;
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+            if Rc::ptr_eq(a, b) || !visited.insert(pair) {
+                return;
+            }
+            let a_entries = a.entries.borrow();
+            let b_entries = b.entries.borrow();
+            let added = b_entries
+                .iter()
+                .filter(|(key, _)| !a_entries.contains_key(key));
+            for (key, _) in a_entries.iter().chain(added) {
+                let len = path.len();
+                path.push_str(&format!("[{key}]"));
+                match (a_entries.get(key), b_entries.get(key)) {
+                    (Some(a), Some(b)) => diff_at(a, b, path, visited, differences),
+                    (Some(a), None) => differences.push(Difference {
+                        path: path.to_string(),
+                        kind: DifferenceKind::Missing(a.clone()),
+                    }),
+                    (None, Some(b)) => differences.push(Difference {
+                        path: path.to_string(),
+                        kind: DifferenceKind::Added(b.clone()),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+                path.truncate(len);
+            }
+            return;
+        }
+        (Value::Instance(_), Value::Instance(_)) => {
+            DifferenceKind::TypeChanged(a.clone(), b.clone())
+        }
+        (a, b) if a.type_name() != b.type_name() => {
+            DifferenceKind::TypeChanged(a.clone(), b.clone())
+        }
+        (a, b) if a != b => DifferenceKind::Changed(a.clone(), b.clone()),
+        _ => return,
+    };
+    differences.push(Difference {
+        path: path.to_string(),
+        kind,
+    });
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() {
+            "value"
+        } else {
+            &self.path
+        };
+        match &self.kind {
+            DifferenceKind::Missing(a) => write!(f, "{path}: removed, was {a}"),
+            DifferenceKind::Added(b) => write!(f, "{path}: added as {b}"),
+            DifferenceKind::Changed(a, b) => write!(f, "{path}: {a} -> {b}"),
+            DifferenceKind::TypeChanged(a, b) => write!(
+                f,
+                "{path}: {a} ({}) -> {b} ({})",
+                a.type_name(),
+                b.type_name()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, rc::Rc};
+
+    use crate::{
+        class::{Class, Instance},
+        interpreter::Value,
+        list::List,
+        map::{Entries, Key, Map},
+    };
+
+    use super::{diff, DifferenceKind};
+
+    fn list(elements: Vec<Value>) -> Value {
+        Value::List(Rc::new(List::new(elements)))
+    }
+
+    fn instance(class: &Rc<Class>, fields: Vec<(&str, Value)>) -> Value {
+        let instance = Instance::new(Rc::clone(class));
+        for (name, value) in fields {
+            instance.fields.borrow_mut().insert(name.to_string(), value);
+        }
+        Value::Instance(Rc::new(instance))
+    }
+
+    #[test]
+    fn check_diff() {
+        let class = |name: &str| {
+            Rc::new(Class {
+                name: name.to_string(),
+                superclass: None,
+                methods: HashMap::new(),
+            })
+        };
+        let (config, db) = (class("Config"), class("Db"));
+
+        assert!(diff(&Value::Float(1.0), &Value::Float(1.0)).is_empty());
+        assert!(matches!(
+            diff(&Value::Float(1.0), &Value::Float(2.0))[..],
+            [ref d] if d.path.is_empty() && matches!(d.kind, DifferenceKind::Changed(..))
+        ));
+
+        let a = instance(
+            &config,
+            vec![
+                ("name", Value::String("a".to_string())),
+                ("db", instance(&db, vec![("port", Value::Float(1.0))])),
+                ("debug", Value::Bool(true)),
+            ],
+        );
+        let b = instance(
+            &config,
+            vec![
+                ("name", Value::Nil),
+                ("db", instance(&db, vec![("port", Value::Float(2.0))])),
+                ("verbose", Value::Bool(true)),
+            ],
+        );
+        let differences = diff(&a, &b)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            differences,
+            [
+                ".db.port: 1 -> 2",
+                ".debug: removed, was true",
+                ".name: a (string) -> nil (nil)",
+                ".verbose: added as true",
+            ]
+        );
+
+        let empty_db = instance(&db, vec![]);
+        assert!(matches!(
+            diff(&a, &empty_db)[..],
+            [ref d] if matches!(d.kind, DifferenceKind::TypeChanged(..))
+        ));
+    }
+
+    #[test]
+    fn check_lists() {
+        let a = list(vec![
+            Value::Int(1),
+            list(vec![Value::Int(2), Value::Int(3)]),
+        ]);
+        let b = list(vec![
+            Value::Int(1),
+            list(vec![Value::Int(2), Value::Int(4)]),
+            Value::Nil,
+        ]);
+        let differences = diff(&a, &b)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(differences, ["[1][1]: 3 -> 4", "[2]: added as nil"]);
+
+        let map = |entries: Vec<(&str, Value)>| {
+            let mut map = Entries::default();
+            for (key, value) in entries {
+                map.insert(Key::String(key.to_string()), value);
+            }
+            Value::Map(Rc::new(Map::new(map)))
+        };
+        let a = map(vec![("b", Value::Int(1)), ("a", list(vec![]))]);
+        let b = map(vec![("a", list(vec![Value::Int(1)])), ("c", Value::Nil)]);
+        let differences = diff(&a, &b)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            differences,
+            [
+                "[\"b\"]: removed, was 1",
+                "[\"a\"][0]: added as 1",
+                "[\"c\"]: added as nil"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_cycles() {
+        let node = Rc::new(Class {
+            name: "Node".to_string(),
+            superclass: None,
+            methods: HashMap::new(),
+        });
+        let a = instance(&node, vec![]);
+        let b = instance(&node, vec![]);
+        for value in [&a, &b] {
+            if let Value::Instance(instance) = value {
+                instance
+                    .fields
+                    .borrow_mut()
+                    .insert("next".to_string(), value.clone());
+            }
+        }
+        assert!(diff(&a, &b).is_empty());
+        // Break the cycles so the instances are freed.
+        for value in [&a, &b] {
+            if let Value::Instance(instance) = value {
+                instance.fields.borrow_mut().clear();
+            }
+        }
+    }
+}
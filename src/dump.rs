@@ -0,0 +1,443 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    errors::FoxError,
+    expr::{Expr, Literal, Pattern},
+    lexer::{Lexer, Token, TokenType},
+    parser::Parser,
+    report_error,
+    source::SourceFile,
+    stmt::{FunctionStmt, Stmt},
+};
+
+/// How `fox lex` prints tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TokenFormat {
+    /// One token per line, aligned for reading.
+    #[default]
+    Human,
+    /// A JSON array of tokens, for tools consuming the lexer.
+    Json,
+}
+
+impl std::str::FromStr for TokenFormat {
+    type Err = fmt::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(TokenFormat::Human),
+            "json" => Ok(TokenFormat::Json),
+            _ => Err(fmt::Error),
+        }
+    }
+}
+
+/// How `fox ast` prints syntax trees.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AstFormat {
+    /// One S-expression per statement.
+    #[default]
+    Sexpr,
+    /// A JSON array of statements, for tools consuming the parser.
+    Json,
+    /// A GraphViz graph, e.g. for `fox ast --format=dot script.fox | dot -Tsvg`.
+    Dot,
+}
+
+impl std::str::FromStr for AstFormat {
+    type Err = fmt::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sexpr" => Ok(AstFormat::Sexpr),
+            "json" => Ok(AstFormat::Json),
+            "dot" => Ok(AstFormat::Dot),
+            _ => Err(fmt::Error),
+        }
+    }
+}
+
+/// A token as printed by `fox lex --format=json`.
+#[derive(Serialize)]
+struct TokenDump<'a> {
+    #[serde(rename = "type")]
+    ty: &'a TokenType,
+    lexeme: String,
+    line: usize,
+    column: usize,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> From<&'a Token> for TokenDump<'a> {
+    fn from(token: &'a Token) -> Self {
+        TokenDump {
+            ty: &token.ty,
+            lexeme: token.lexeme(),
+            line: token.position.line,
+            column: token.position.column,
+            start: token.position.start,
+            end: token.position.end,
+        }
+    }
+}
+
+/// Formats `tokens` in `format`.
+fn format_tokens(tokens: &[Token], format: TokenFormat) -> String {
+    match format {
+        TokenFormat::Human => tokens
+            .iter()
+            .map(|token| {
+                let span = format!("{}..{}", token.position.start, token.position.end);
+                format!(
+                    "{:>4}:{:<3} {span:<11} {:?} `{}`",
+                    token.position.line,
+                    token.position.column,
+                    token.ty,
+                    token.lexeme()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        TokenFormat::Json => {
+            let tokens = tokens.iter().map(TokenDump::from).collect::<Vec<_>>();
+            serde_json::to_string_pretty(&tokens).unwrap()
+        }
+    }
+}
+
+/// Prints the tokens of `source` in `format`, reporting the syntax errors to
+/// stderr. Returns `Err` if there were any.
+pub(crate) fn print_tokens(source: &SourceFile, format: TokenFormat) -> Result<(), ()> {
+    let (tokens, errors) = Lexer::new(source).scan_all();
+    println!("{}", format_tokens(&tokens, format));
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        report_error(FoxError::Syntax(errors));
+        Err(())
+    }
+}
+
+/// Prints the statements of `source` in `format`, reporting the parse
+/// errors to stderr. Returns `Err` if there were any.
+pub(crate) fn print_ast(source: &SourceFile, format: AstFormat) -> Result<(), ()> {
+    let (tokens, _) = Lexer::new(source).scan_all();
+    match Parser::new(source, tokens).parse() {
+        Ok(stmts) => {
+            println!("{}", format_ast(&stmts, format));
+            Ok(())
+        }
+        Err(errors) => {
+            report_error(FoxError::Parse(errors));
+            Err(())
+        }
+    }
+}
+
+/// Formats `stmts` in `format`.
+fn format_ast(stmts: &[Stmt], format: AstFormat) -> String {
+    match format {
+        AstFormat::Sexpr => stmts
+            .iter()
+            .map(|stmt| stmt.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        AstFormat::Json => serde_json::to_string_pretty(stmts).unwrap(),
+        AstFormat::Dot => {
+            let mut dot = String::from("digraph ast {\n    node [shape=box];\n");
+            let root = Node::new("script", stmts.iter().map(Node::stmt).collect());
+            root.write_dot(&mut dot, &mut 0);
+            dot.push('}');
+            dot
+        }
+    }
+}
+
+/// A node of the syntax tree as drawn by `fox ast --format=dot`.
+struct Node {
+    label: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(label: impl Into<String>, children: Vec<Node>) -> Self {
+        Node {
+            label: label.into(),
+            children,
+        }
+    }
+
+    fn leaf(label: impl Into<String>) -> Self {
+        Node::new(label, vec![])
+    }
+
+    fn literal(literal: &Literal) -> Self {
+        Node::leaf(match literal {
+            Literal::String(Some(string)) => format!("{string:?}"),
+            Literal::Int(int) => int.to_string(),
+            Literal::Number(Some(number)) => number.to_string(),
+            Literal::Bool(bool) => bool.to_string(),
+            _ => "nil".to_string(),
+        })
+    }
+
+    fn block(label: &str, stmts: &[Stmt]) -> Self {
+        Node::new(label, stmts.iter().map(Node::stmt).collect())
+    }
+
+    fn function(function: &FunctionStmt) -> Self {
+        let params = function
+            .params
+            .iter()
+            .map(Token::lexeme)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let label = format!("fn {}({params})", function.name.lexeme());
+        Node::block(&label, &function.body)
+    }
+
+    fn stmt(stmt: &Stmt) -> Self {
+        match stmt {
+            Stmt::Block(stmts) => Node::block("block", stmts),
+            Stmt::Break(_) => Node::leaf("break"),
+            Stmt::Continue(_) => Node::leaf("continue"),
+            Stmt::Class(stmt) => {
+                let mut label = format!("class {}", stmt.name.lexeme());
+                if let Some(superclass) = &stmt.superclass {
+                    label = format!("{label} < {}", superclass.name.lexeme());
+                }
+                Node::new(
+                    label,
+                    stmt.methods.iter().map(|m| Node::function(m)).collect(),
+                )
+            }
+            Stmt::Export(stmt) => Node::new("export", vec![Node::stmt(&stmt.declaration)]),
+            Stmt::Expression(expr) => Node::expr(expr),
+            Stmt::For(stmt) => {
+                let mut children = vec![];
+                if let Some(initializer) = &stmt.initializer {
+                    children.push(Node::stmt(initializer));
+                }
+                if let Some(condition) = &stmt.condition {
+                    children.push(Node::expr(condition));
+                }
+                if let Some(increment) = &stmt.increment {
+                    children.push(Node::expr(increment));
+                }
+                children.push(Node::stmt(&stmt.body));
+                Node::new("for", children)
+            }
+            Stmt::ForIn(stmt) => Node::new(
+                format!("for {} in", stmt.name.lexeme()),
+                vec![Node::expr(&stmt.iterable), Node::stmt(&stmt.body)],
+            ),
+            Stmt::Function(function) => Node::function(function),
+            Stmt::Guard(stmt) => Node::new(
+                "guard",
+                vec![Node::expr(&stmt.condition), Node::stmt(&stmt.else_branch)],
+            ),
+            Stmt::If(stmt) => {
+                let mut children = vec![Node::expr(&stmt.condition), Node::stmt(&stmt.then_branch)];
+                if let Some(else_branch) = &stmt.else_branch {
+                    children.push(Node::stmt(else_branch));
+                }
+                Node::new("if", children)
+            }
+            Stmt::Import(stmt) => Node::leaf(format!("import {:?}", stmt.path)),
+            Stmt::Let(stmt) => Node::new(
+                format!("let {}", stmt.name.lexeme()),
+                stmt.initializer.iter().map(Node::expr).collect(),
+            ),
+            Stmt::Print(stmt) => Node::new("print", vec![Node::expr(&stmt.value)]),
+            Stmt::Return(stmt) => Node::new("return", stmt.value.iter().map(Node::expr).collect()),
+            Stmt::Throw(stmt) => Node::new("throw", vec![Node::expr(&stmt.value)]),
+            Stmt::Try(stmt) => Node::new(
+                "try",
+                vec![
+                    Node::block("block", &stmt.body),
+                    Node::block(&format!("catch {}", stmt.name.lexeme()), &stmt.handler),
+                ],
+            ),
+            Stmt::While(stmt) => Node::new(
+                "while",
+                vec![Node::expr(&stmt.condition), Node::stmt(&stmt.body)],
+            ),
+        }
+    }
+
+    fn expr(expr: &Expr) -> Self {
+        match expr {
+            Expr::Assign(expr) => Node::new(
+                format!("= {}", expr.name.lexeme()),
+                vec![Node::expr(&expr.value)],
+            ),
+            Expr::Binary(expr) => Node::new(
+                expr.operator.lexeme(),
+                vec![Node::expr(&expr.lhs), Node::expr(&expr.rhs)],
+            ),
+            Expr::Call(expr) => {
+                let mut children = vec![Node::expr(&expr.callee)];
+                children.extend(expr.arguments.iter().map(Node::expr));
+                Node::new("call", children)
+            }
+            Expr::Get(expr) => Node::new(
+                format!(".{}", expr.name.lexeme()),
+                vec![Node::expr(&expr.object)],
+            ),
+            Expr::Conditional(expr) => Node::new(
+                "?:",
+                vec![
+                    Node::expr(&expr.condition),
+                    Node::expr(&expr.then_branch),
+                    Node::expr(&expr.else_branch),
+                ],
+            ),
+            Expr::Logical(expr) => Node::new(
+                expr.operator.lexeme(),
+                vec![Node::expr(&expr.lhs), Node::expr(&expr.rhs)],
+            ),
+            Expr::Grouping(expr) => Node::new("group", vec![Node::expr(&expr.expr)]),
+            Expr::Index(expr) => Node::new(
+                "[]",
+                vec![Node::expr(&expr.object), Node::expr(&expr.index)],
+            ),
+            Expr::IndexSet(expr) => Node::new(
+                "[] =",
+                vec![
+                    Node::expr(&expr.object),
+                    Node::expr(&expr.index),
+                    Node::expr(&expr.value),
+                ],
+            ),
+            Expr::Interpolation(expr) => {
+                Node::new("interpolate", expr.parts.iter().map(Node::expr).collect())
+            }
+            Expr::List(expr) => Node::new("list", expr.elements.iter().map(Node::expr).collect()),
+            Expr::Map(expr) => Node::new(
+                "map",
+                expr.entries
+                    .iter()
+                    .map(|entry| {
+                        Node::new(":", vec![Node::expr(&entry.key), Node::expr(&entry.value)])
+                    })
+                    .collect(),
+            ),
+            Expr::Literal(literal) => Node::literal(literal),
+            Expr::Match(expr) => {
+                let mut children = vec![Node::expr(&expr.subject)];
+                children.extend(expr.arms.iter().map(|arm| {
+                    let pattern = match &arm.pattern {
+                        Pattern::Literal { value, .. } => Node::literal(value),
+                        pattern => Node::leaf(pattern.to_string()),
+                    };
+                    let mut children = vec![pattern];
+                    if let Some(guard) = &arm.guard {
+                        children.push(Node::new("if", vec![Node::expr(guard)]));
+                    }
+                    children.push(Node::expr(&arm.body));
+                    Node::new("=>", children)
+                }));
+                Node::new("match", children)
+            }
+            Expr::Set(expr) => Node::new(
+                format!(".{} =", expr.name.lexeme()),
+                vec![Node::expr(&expr.object), Node::expr(&expr.value)],
+            ),
+            Expr::Super(expr) => Node::leaf(format!("super.{}", expr.method.lexeme())),
+            Expr::This(_) => Node::leaf("this"),
+            Expr::Try(expr) => Node::new("try", vec![Node::expr(&expr.expr)]),
+            Expr::Unary(expr) => Node::new(expr.operator.lexeme(), vec![Node::expr(&expr.rhs)]),
+            Expr::Variable(expr) => Node::leaf(expr.name.lexeme()),
+        }
+    }
+
+    /// Writes the node and its descendants as DOT statements, numbering them
+    /// from `next`. Returns the id of the node.
+    fn write_dot(&self, dot: &mut String, next: &mut usize) -> usize {
+        let id = *next;
+        *next += 1;
+        let label = self.label.replace('\\', "\\\\").replace('"', "\\\"");
+        dot.push_str(&format!("    n{id} [label=\"{label}\"];\n"));
+        for child in &self.children {
+            let child = child.write_dot(dot, next);
+            dot.push_str(&format!("    n{id} -> n{child};\n"));
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{lexer::Lexer, parser::Parser, source::SourceFile};
+
+    use super::{format_ast, format_tokens, AstFormat, TokenFormat};
+
+    #[test]
+    fn check_tokens() {
+        let source = SourceFile::new("test.fox", "let fox = \"fox\";\nfox.say(1.5);");
+        let (tokens, _) = Lexer::new(&source).scan_all();
+        let human = format_tokens(&tokens, TokenFormat::Human);
+        let lines = human.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), tokens.len());
+        assert_eq!(lines[0], "   1:1   0..3        Keyword(Let) `let`");
+        assert_eq!(lines[9], "   2:9   25..28      Number(1.5) `1.5`");
+
+        let json = format_tokens(&tokens[..4], TokenFormat::Json);
+        let json = json.split_whitespace().collect::<String>();
+        assert_eq!(
+            json,
+            "[{\"type\":{\"Keyword\":\"Let\"},\"lexeme\":\"let\",\"line\":1,\"column\":1,\"start\":0,\"end\":3},\
+             {\"type\":{\"Identifier\":\"fox\"},\"lexeme\":\"fox\",\"line\":1,\"column\":5,\"start\":4,\"end\":7},\
+             {\"type\":\"Equal\",\"lexeme\":\"=\",\"line\":1,\"column\":9,\"start\":8,\"end\":9},\
+             {\"type\":{\"String\":\"fox\"},\"lexeme\":\"fox\",\"line\":1,\"column\":11,\"start\":10,\"end\":15}]"
+        );
+    }
+
+    #[test]
+    fn check_ast() {
+        let source = &SourceFile::new("test.fox", "let fox = \"fox\";\nif fox { print -1; }");
+        let (tokens, _) = Lexer::new(source).scan_all();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+
+        let sexpr = format_ast(&stmts, AstFormat::Sexpr);
+        let lines = stmts.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(sexpr, lines.join("\n"));
+
+        let json = format_ast(&stmts[..1], AstFormat::Json);
+        let json = json.split_whitespace().collect::<String>();
+        assert_eq!(
+            json,
+            "[{\"Let\":{\"name\":{\"ty\":{\"Identifier\":\"fox\"},\
+             \"position\":{\"line\":1,\"column\":5,\"start\":4,\"end\":7}},\
+             \"initializer\":{\"Literal\":{\"String\":\"fox\"}}}}]"
+        );
+
+        let dot = format_ast(&stmts, AstFormat::Dot);
+        let expected = [
+            "digraph ast {",
+            "    node [shape=box];",
+            "    n0 [label=\"script\"];",
+            "    n1 [label=\"let fox\"];",
+            "    n2 [label=\"\\\"fox\\\"\"];",
+            "    n1 -> n2;",
+            "    n0 -> n1;",
+            "    n3 [label=\"if\"];",
+            "    n4 [label=\"fox\"];",
+            "    n3 -> n4;",
+            "    n5 [label=\"block\"];",
+            "    n6 [label=\"print\"];",
+            "    n7 [label=\"-\"];",
+            "    n8 [label=\"1\"];",
+            "    n7 -> n8;",
+            "    n6 -> n7;",
+            "    n5 -> n6;",
+            "    n3 -> n5;",
+            "    n0 -> n3;",
+            "}",
+        ];
+        assert_eq!(dot, expected.join("\n"));
+    }
+}
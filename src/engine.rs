@@ -0,0 +1,227 @@
+use std::{fs, path::Path};
+
+use crate::{
+    errors::{FoxError, ResolveWarning},
+    interpreter::{Interpreter, Value},
+    lexer::Lexer,
+    parser::Parser,
+    resolver::Resolver,
+    source::SourceFile,
+    stmt::Stmt,
+    suppress,
+};
+
+/// Fox as a scripting engine for Rust programs. Unlike `run_file` and
+/// `run_prompt`, nothing is printed: evaluating a script returns its value,
+/// every error is returned as a structured `FoxError`, and warnings are kept
+/// until taken with `Fox::take_warnings`.
+///
+/// Globals defined by a script stay defined for the scripts evaluated after
+/// it, so a host can load a library once and then use it.
+///
+/// ```ignore
+/// let mut fox = Fox::new();
+/// fox.eval("fn square(x) { return x * x; }")?;
+/// assert_eq!(fox.eval("square(4);")?, Value::Int(16));
+/// ```
+pub struct Fox {
+    interpreter: Interpreter,
+    warnings: Vec<ResolveWarning>,
+}
+
+impl Fox {
+    pub fn new() -> Self {
+        Fox {
+            interpreter: Interpreter::new(),
+            warnings: vec![],
+        }
+    }
+
+    /// Runs `source`, returning the value of its last statement if it is an
+    /// expression, or else `nil`. Diagnostics call the script `<eval>`.
+    pub fn eval(&mut self, source: &str) -> Result<Value, FoxError> {
+        self.eval_source(&SourceFile::new("<eval>", source))
+    }
+
+    /// Like `Fox::eval`, but with diagnostics using the name of `source`.
+    pub fn eval_source(&mut self, source: &SourceFile) -> Result<Value, FoxError> {
+        let (stmts, warnings) = parse(source)?;
+        self.warnings.extend(warnings);
+        Ok(self.interpreter.eval(source, &stmts)?)
+    }
+
+    /// Like `Fox::eval`, but reads the script from the file at `path`.
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Value, FoxError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| FoxError::Io {
+            path: path.display().to_string(),
+            error,
+        })?;
+        self.eval_source(&SourceFile::new(path.display().to_string(), source))
+    }
+
+    /// Returns the warnings about the scripts evaluated since the last call.
+    pub fn take_warnings(&mut self) -> Vec<ResolveWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// The interpreter running the scripts, e.g. to define native functions
+    /// or call functions declared by a script.
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+}
+
+impl Default for Fox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lexes, parses and resolves `source`, failing with every error of the
+/// first stage that finds any. Returns the statements along with the
+/// warnings found, except those suppressed by comments.
+pub(crate) fn parse(source: &SourceFile) -> Result<(Vec<Stmt>, Vec<ResolveWarning>), FoxError> {
+    let (tokens, errors) = Lexer::new(source).scan_all();
+    if !errors.is_empty() {
+        return Err(FoxError::Syntax(errors));
+    }
+    let stmts = Parser::new(source, tokens)
+        .parse()
+        .map_err(FoxError::Parse)?;
+    let warnings = Resolver::new(source)
+        .resolve(&stmts)
+        .map_err(FoxError::Resolve)?;
+    Ok((stmts, suppress::apply(source, warnings)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use miette::Diagnostic;
+
+    use crate::{errors::FoxError, interpreter::Value, output::OutputLimits};
+
+    use super::Fox;
+
+    #[test]
+    fn check_eval() {
+        let mut fox = Fox::new();
+        assert_eq!(fox.eval("1 + 2;").unwrap(), Value::Int(3));
+        assert_eq!(fox.eval("let a = 1;").unwrap(), Value::Nil);
+        assert_eq!(
+            fox.eval("fn double(x) { return x * 2; }").unwrap(),
+            Value::Nil
+        );
+        assert_eq!(fox.eval("double(a); 5;").unwrap(), Value::Int(5));
+        fox.interpreter()
+            .define_native("triple", 1, |args| Ok(f64::try_from(&args[0])? * 3.0));
+        assert_eq!(fox.eval("double(triple(a));").unwrap(), Value::Float(6.0));
+    }
+
+    #[test]
+    fn check_errors() {
+        let mut fox = Fox::new();
+        assert!(matches!(
+            fox.eval("let a = @ #;"),
+            Err(FoxError::Syntax(errors)) if errors.len() == 2
+        ));
+        assert!(matches!(fox.eval("let = 1;"), Err(FoxError::Parse(_))));
+        assert!(matches!(
+            fox.eval("return; { let a = 1; let a = 2; }"),
+            Err(FoxError::Resolve(errors)) if errors.len() == 2
+        ));
+        assert!(matches!(fox.eval("missing;"), Err(FoxError::Runtime(_))));
+        assert!(fox.take_warnings().is_empty());
+        fox.eval("fn f() { return Error(1, 2, 3); } f();").unwrap();
+        assert_eq!(fox.take_warnings().len(), 1);
+        assert!(fox.take_warnings().is_empty());
+        assert!(matches!(
+            fox.eval_file("does/not/exist.fox"),
+            Err(FoxError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn check_modules() {
+        let dir = std::env::temp_dir().join(format!("fox-modules-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        let files = [
+            (
+                "lib/math.fox",
+                "let calls = 0;
+                fn count() { calls += 1; }
+                export fn square(x) { count(); return x * x; }
+                export fn calls_made() { return calls; }
+                export let name = \"math\";",
+            ),
+            (
+                "lib/stats.fox",
+                "import math; export fn calls() { return math.calls_made(); }",
+            ),
+            (
+                "main.fox",
+                "import \"lib/math.fox\"; import \"lib/stats.fox\";
+                math.square(3) + math.square(2) + stats.calls();",
+            ),
+            ("a.fox", "import b;"),
+            ("b.fox", "import a;"),
+            ("cycle.fox", "import a;"),
+            ("broken.fox", "let = 1;"),
+            ("uses_broken.fox", "import broken;"),
+        ];
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+
+        let mut fox = Fox::new();
+        assert_eq!(fox.eval_file(dir.join("main.fox")).unwrap(), Value::Int(15));
+        assert_eq!(
+            fox.eval("\"${math.name} ${math}\";").unwrap(),
+            Value::String("math <module math>".to_string())
+        );
+        assert!(matches!(
+            fox.eval("math.count();"),
+            Err(FoxError::Runtime(err))
+                if err.code == "runtime::not_exported" && err.value("name") == Some("count")
+        ));
+        assert!(matches!(
+            fox.eval("calls;"),
+            Err(FoxError::Runtime(err)) if err.code == "runtime::undefined_variable"
+        ));
+        let (a, b) = (dir.join("a.fox"), dir.join("b.fox"));
+        let (a, b) = (a.display(), b.display());
+        assert!(matches!(
+            fox.eval_file(dir.join("cycle.fox")),
+            Err(FoxError::Runtime(err)) if err.code == "runtime::import_cycle"
+                && err.value("chain") == Some(&format!("{a} -> {b} -> {a}"))
+        ));
+        assert!(matches!(
+            fox.eval_file(dir.join("uses_broken.fox")),
+            Err(FoxError::Runtime(err)) if err.code == "runtime::import_failed"
+                && Diagnostic::related(&*err).unwrap().count() == 1
+        ));
+    }
+
+    #[test]
+    fn check_output_limits() {
+        let mut fox = Fox::new();
+        fox.interpreter().set_output_limits(OutputLimits {
+            max_output: Some(16),
+            max_print: Some(8),
+        });
+        let caught =
+            "let kind; try { print \"too long to print\"; } catch e { kind = e.kind; } kind;";
+        assert_eq!(
+            fox.eval(caught).unwrap(),
+            Value::String("output_limit".to_string())
+        );
+        fox.eval("print 1234567; print 1234567;").unwrap();
+        assert!(matches!(
+            fox.eval("print 1;"),
+            Err(FoxError::Runtime(err))
+                if err.code == "runtime::output_limit_exceeded" && err.value("limit") == Some("16")
+        ));
+    }
+}
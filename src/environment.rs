@@ -0,0 +1,141 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::interpreter::Value;
+
+/// A single scope of variable bindings. Every `{}` block gets its own
+/// environment whose `enclosing` environment is the scope it appears in, all
+/// the way up to the global scope which has none.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Binds `name` in this scope, shadowing any binding of the same name in
+    /// the enclosing scopes and replacing the one in this scope.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn enclosing(&self) -> Option<&Rc<RefCell<Environment>>> {
+        self.enclosing.as_ref()
+    }
+
+    /// The values bound in this scope.
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.values.values()
+    }
+
+    /// The names bound in this scope along with their values.
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values.iter()
+    }
+
+    /// Removes every binding in this scope, returning their values.
+    pub fn take_values(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.values).into_values().collect()
+    }
+
+    /// Looks `name` up starting from this scope and walking outwards.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Looks `name` up in the scope `distance` levels out from this one.
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Value> {
+        if distance == 0 {
+            return self.values.get(name).cloned();
+        }
+        self.enclosing.as_ref()?.borrow().get_at(distance - 1, name)
+    }
+
+    /// Updates the innermost binding of `name`. Returns `false` if `name` is
+    /// not defined in any scope.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        if let Some(slot) = self.values.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => false,
+        }
+    }
+
+    /// Updates `name` in the scope `distance` levels out from this one.
+    /// Returns `false` if there is no such scope or binding.
+    pub fn assign_at(&mut self, distance: usize, name: &str, value: Value) -> bool {
+        if distance == 0 {
+            return match self.values.get_mut(name) {
+                Some(slot) => {
+                    *slot = value;
+                    true
+                }
+                None => false,
+            };
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::interpreter::Value;
+
+    use super::Environment;
+
+    #[test]
+    fn check_scopes() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Float(1.0));
+        globals.borrow_mut().define("b", Value::Float(2.0));
+
+        let mut local = Environment::with_enclosing(Rc::clone(&globals));
+        local.define("a", Value::Bool(true));
+        assert_eq!(local.get("a"), Some(Value::Bool(true)));
+        assert_eq!(local.get("b"), Some(Value::Float(2.0)));
+        assert_eq!(local.get("c"), None);
+
+        assert!(local.assign("b", Value::Nil));
+        assert!(!local.assign("c", Value::Nil));
+        assert_eq!(globals.borrow().get("a"), Some(Value::Float(1.0)));
+        assert_eq!(globals.borrow().get("b"), Some(Value::Nil));
+    }
+
+    #[test]
+    fn check_distances() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Float(1.0));
+        let mut local = Environment::with_enclosing(Rc::clone(&globals));
+        local.define("a", Value::Float(2.0));
+
+        assert_eq!(local.get_at(0, "a"), Some(Value::Float(2.0)));
+        assert_eq!(local.get_at(1, "a"), Some(Value::Float(1.0)));
+        assert_eq!(local.get_at(2, "a"), None);
+
+        assert!(local.assign_at(1, "a", Value::Nil));
+        assert!(!local.assign_at(0, "b", Value::Nil));
+        assert_eq!(local.get("a"), Some(Value::Float(2.0)));
+        assert_eq!(globals.borrow().get("a"), Some(Value::Nil));
+    }
+}
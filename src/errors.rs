@@ -1,35 +1,318 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, io, ops::Deref, time::Duration};
 
-use miette::{Diagnostic, NamedSource, SourceSpan};
+use miette::{Diagnostic, Report, SourceSpan};
 use thiserror::Error;
 
+use crate::{diag::Diag, source::SourceFile};
+
+/// Defines `$name`, the errors one phase raises, each of which is a `Diag`
+/// whose code tells what went wrong. The `Diag` is boxed to keep the
+/// results carrying these errors small.
+macro_rules! phase_error {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Error, Diagnostic)]
+        #[error(transparent)]
+        #[diagnostic(transparent)]
+        pub struct $name(Box<Diag>);
+
+        impl From<Diag> for $name {
+            fn from(diag: Diag) -> Self {
+                $name(Box::new(diag))
+            }
+        }
+
+        impl Deref for $name {
+            type Target = Diag;
+
+            fn deref(&self) -> &Diag {
+                &self.0
+            }
+        }
+    };
+}
+
+phase_error! {
+    /// An error found by the `Lexer`, with a `syntax::` code.
+    SyntaxError
+}
+
+phase_error! {
+    /// An error found by the `Parser`, with a `parse::` code.
+    ParseError
+}
+
+phase_error! {
+    /// An error raised while running a script, with a `runtime::` code.
+    RuntimeError
+}
+
+phase_error! {
+    /// An error found by the `Resolver`, with a `resolve::` code.
+    ResolveError
+}
+
+phase_error! {
+    /// A likely mistake found by the `Resolver` that doesn't stop the
+    /// script from running, with a `resolve::` code.
+    ResolveWarning
+}
+
+phase_error! {
+    /// An error found while compiling a script for the `Vm`, with a
+    /// `compile::` code.
+    CompileError
+}
+
+/// The runtime errors raised by both the `Interpreter` and the `Vm`.
+impl RuntimeError {
+    pub(crate) fn invalid_operand(
+        source: &SourceFile,
+        span: SourceSpan,
+        operator: &str,
+        found: &str,
+    ) -> Self {
+        Diag::error("runtime::invalid_operand")
+            .arg("operator", operator)
+            .arg("found", found)
+            .msg("Operand of `{operator}` must be a number")
+            .source(source)
+            .primary(span, "found {found} here")
+            .into()
+    }
+
+    pub(crate) fn invalid_operands(
+        source: &SourceFile,
+        span: SourceSpan,
+        operator: &str,
+        expected: &str,
+        found: &str,
+    ) -> Self {
+        Diag::error("runtime::invalid_operands")
+            .arg("operator", operator)
+            .arg("expected", expected)
+            .arg("found", found)
+            .msg("Operands of `{operator}` must be {expected}")
+            .source(source)
+            .primary(span, "found {found} here")
+            .into()
+    }
+
+    pub(crate) fn undefined_variable(source: &SourceFile, span: SourceSpan, name: &str) -> Self {
+        Diag::error("runtime::undefined_variable")
+            .arg("name", name)
+            .msg("Undefined variable `{name}`")
+            .source(source)
+            .primary(span, "not defined")
+            .help("consider declaring it first using `let {name} = ...;`")
+            .into()
+    }
+
+    pub(crate) fn not_callable(source: &SourceFile, span: SourceSpan, found: &str) -> Self {
+        Diag::error("runtime::not_callable")
+            .arg("found", found)
+            .msg("Can only call functions and classes, found {found}")
+            .source(source)
+            .primary(span, "called here")
+            .into()
+    }
+
+    pub(crate) fn arity_mismatch(
+        source: &SourceFile,
+        span: SourceSpan,
+        name: &str,
+        expected: usize,
+        found: usize,
+    ) -> Self {
+        Diag::error("runtime::arity_mismatch")
+            .arg("name", name)
+            .arg("expected", expected)
+            .arg("found", found)
+            .msg("Expected {expected} arguments but got {found}")
+            .source(source)
+            .primary(span, "called with {found} arguments here")
+            .help("`{name}` is declared with {expected} parameters")
+            .into()
+    }
+
+    pub(crate) fn no_match(source: &SourceFile, span: SourceSpan, value: &str) -> Self {
+        Diag::error("runtime::no_match")
+            .arg("value", value)
+            .msg("No arm of `match` matches {value}")
+            .source(source)
+            .primary(span, "evaluates to {value}")
+            .help("add a `_ => ...` arm to handle every other value")
+            .into()
+    }
+
+    pub(crate) fn not_iterable(source: &SourceFile, span: SourceSpan, found: &str) -> Self {
+        Diag::error("runtime::not_iterable")
+            .arg("found", found)
+            .msg("Can only iterate over lists, maps and strings, found {found}")
+            .source(source)
+            .primary(span, "iterated over here")
+            .into()
+    }
+
+    pub(crate) fn integer_overflow(source: &SourceFile, span: SourceSpan, operator: &str) -> Self {
+        Diag::error("runtime::integer_overflow")
+            .arg("operator", operator)
+            .msg("Integer overflow")
+            .source(source)
+            .primary(span, "`{operator}` overflows here")
+            .help("the result doesn't fit in an int, consider using floats")
+            .into()
+    }
+
+    pub(crate) fn division_by_zero(source: &SourceFile, span: SourceSpan, operator: &str) -> Self {
+        Diag::error("runtime::division_by_zero")
+            .arg("operator", operator)
+            .msg("Division by zero")
+            .source(source)
+            .primary(span, "`{operator}` by zero here")
+            .help("the remainder of an int divided by zero is undefined")
+            .into()
+    }
+
+    pub(crate) fn stack_overflow(source: &SourceFile, span: SourceSpan, limit: usize) -> Self {
+        Diag::error("runtime::stack_overflow")
+            .arg("limit", limit)
+            .msg("Stack overflow")
+            .source(source)
+            .primary(span, "called here")
+            .help("calls are nested more than {limit} levels deep, check for unbounded recursion")
+            .into()
+    }
+
+    pub(crate) fn timed_out(limit: Duration) -> Self {
+        Diag::error("runtime::timed_out")
+            .arg("limit", format!("{limit:?}"))
+            .msg("Timed out after {limit}")
+            .help("the host running the script limits how long it can run")
+            .without_source()
+            .into()
+    }
+
+    /// Short machine-readable name for the category of the error, which is
+    /// the `kind` of the `Error` value Fox code catches it as.
+    pub fn kind(&self) -> &str {
+        match self.code {
+            "runtime::invalid_operand" | "runtime::invalid_operands" | "runtime::not_iterable" => {
+                "type"
+            }
+            "runtime::not_callable" | "runtime::arity_mismatch" | "runtime::invalid_call" => "call",
+            "runtime::not_an_instance"
+            | "runtime::undefined_property"
+            | "runtime::undefined_method"
+            | "runtime::not_exported" => "property",
+            "runtime::not_indexable"
+            | "runtime::invalid_index"
+            | "runtime::invalid_key"
+            | "runtime::index_out_of_bounds"
+            | "runtime::range_out_of_bounds"
+            | "runtime::missing_key" => "index",
+            "runtime::frozen_instance" | "runtime::frozen_collection" => "frozen",
+            "runtime::invalid_superclass" => "inheritance",
+            "runtime::no_match" => "match",
+            "runtime::import_failed" | "runtime::import_cycle" => "import",
+            "runtime::native_failed" => "native",
+            "runtime::integer_overflow" => "overflow",
+            "runtime::output_limit_exceeded" => "output_limit",
+            "runtime::timed_out" => "timeout",
+            "runtime::invalid_throw" => "throw",
+            "runtime::thrown" => self.value("kind").unwrap_or_default(),
+            code => code.trim_start_matches("runtime::"),
+        }
+    }
+
+    /// Whether the script ran out of time, which ends it whatever catches
+    /// errors.
+    pub fn is_timeout(&self) -> bool {
+        self.code == "runtime::timed_out"
+    }
+}
+
+/// The names of the warnings that `// fox: allow(...)` comments can
+/// suppress, see `ResolveWarning::rule`.
+pub const RULES: &[&str] = &["ignored_error", "unreachable_arm"];
+
+impl ResolveWarning {
+    /// The name used to suppress the warning, which is its code without
+    /// the phase, or `None` for warnings about suppressions themselves,
+    /// which can't be.
+    pub fn rule(&self) -> Option<&'static str> {
+        let name = self.code.trim_start_matches("resolve::");
+        RULES.iter().copied().find(|rule| *rule == name)
+    }
+
+    pub fn span(&self) -> SourceSpan {
+        self.0
+            .span()
+            .expect("warnings point at the code they are about")
+    }
+}
+
+/// Any error that can occur while running a script with `Fox`, grouping the
+/// errors of every stage. Stages before evaluation report all the errors
+/// they find, which can be rendered one by one using `FoxError::into_reports`.
 #[derive(Debug, Error, Diagnostic)]
-pub enum SyntaxError {
-    #[error("Syntax error: Unexpected character `{char}` found")]
+pub enum FoxError {
+    #[error("Error: Couldn't read `{path}`: {error}")]
     #[diagnostic()]
-    UnexpectedCharacter {
-        #[source_code]
-        src: NamedSource,
-        #[label(primary, "this one right here")]
-        span: SourceSpan,
-        char: char,
-    },
+    Io { path: String, error: io::Error },
 
-    #[error("Syntax error: Missing trailing `\"` to terminate the string")]
-    #[diagnostic(help("consider adding a `\"` after the string literal"))]
-    UnterminatedString {
-        #[source_code]
-        src: NamedSource,
-        #[label(primary, "opening `\"` found here")]
-        leading_quote: SourceSpan,
+    #[error("Error: Invalid message catalog `{path}` at line {line}: {reason}")]
+    #[diagnostic()]
+    InvalidCatalog {
+        path: String,
+        line: usize,
+        reason: String,
     },
 
-    #[error("Unterminated block comment: Missing trailing `*/` to terminate the block comment")]
-    #[diagnostic(help("consider adding `*/` at the end of the block comment"))]
-    UnterminatedBlockComment {
-        #[source_code]
-        src: NamedSource,
-        #[label(primary, "start of the block comment")]
-        comment_start: SourceSpan
+    #[error("Error: Found {} syntax errors", .0.len())]
+    #[diagnostic()]
+    Syntax(Vec<SyntaxError>),
+
+    #[error("Error: Found {} parse errors", .0.len())]
+    #[diagnostic()]
+    Parse(Vec<ParseError>),
+
+    #[error("Error: Found {} resolve errors", .0.len())]
+    #[diagnostic()]
+    Resolve(Vec<ResolveError>),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Compile(CompileError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Runtime(RuntimeError),
+}
+
+impl FoxError {
+    /// Splits the error into one report per underlying error, for printing.
+    pub fn into_reports(self) -> Vec<Report> {
+        fn reports<E: Diagnostic + Send + Sync + 'static>(errors: Vec<E>) -> Vec<Report> {
+            errors.into_iter().map(Report::new).collect()
+        }
+        match self {
+            FoxError::Syntax(errors) => reports(errors),
+            FoxError::Parse(errors) => reports(errors),
+            FoxError::Resolve(errors) => reports(errors),
+            err => vec![Report::new(err)],
+        }
+    }
+}
+
+impl From<CompileError> for FoxError {
+    fn from(err: CompileError) -> Self {
+        FoxError::Compile(err)
+    }
+}
+
+impl From<RuntimeError> for FoxError {
+    fn from(err: RuntimeError) -> Self {
+        FoxError::Runtime(err)
     }
 }
@@ -1,38 +1,243 @@
-use std::fmt::Display;
+use std::{cell::Cell, fmt::Display};
 
-use crate::lexer::Token;
+use serde::Serialize;
 
+use crate::lexer::{Position, Token};
+
+#[derive(Clone, Serialize)]
 pub enum Expr {
+    Assign(AssignExpr),
     Binary(BinaryExpr),
+    Call(CallExpr),
+    Conditional(ConditionalExpr),
+    Get(GetExpr),
     Grouping(GroupingExpr),
+    Index(IndexExpr),
+    IndexSet(IndexSetExpr),
+    Interpolation(InterpolationExpr),
+    List(ListExpr),
     Literal(Literal),
+    Map(MapExpr),
+    Match(MatchExpr),
+    Logical(LogicalExpr),
+    Set(SetExpr),
+    Super(SuperExpr),
+    This(ThisExpr),
+    Try(TryExpr),
     Unary(UnaryExpr),
+    Variable(VariableExpr),
+}
+
+#[derive(Clone, Serialize)]
+pub struct AssignExpr {
+    pub name: Token,
+    pub value: Box<Expr>,
+    /// Number of scopes between this use and the declaration it refers to,
+    /// filled in by the `Resolver`. `None` for globals.
+    #[serde(skip)]
+    pub depth: Cell<Option<usize>>,
 }
 
+#[derive(Clone, Serialize)]
 pub struct BinaryExpr {
     pub lhs: Box<Expr>,
     pub operator: Token,
     pub rhs: Box<Expr>,
 }
 
+#[derive(Clone, Serialize)]
+pub struct CallExpr {
+    pub callee: Box<Expr>,
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+
+/// `condition ? then_branch : else_branch`, which only evaluates the branch
+/// chosen.
+#[derive(Clone, Serialize)]
+pub struct ConditionalExpr {
+    pub condition: Box<Expr>,
+    pub question: Token,
+    pub then_branch: Box<Expr>,
+    pub else_branch: Box<Expr>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct GetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
+#[derive(Clone, Serialize)]
 pub struct GroupingExpr {
     pub expr: Box<Expr>,
 }
 
+/// `object[index]`.
+#[derive(Clone, Serialize)]
+pub struct IndexExpr {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub index: Box<Expr>,
+    /// Where `index` is in the source, which errors about it point to.
+    pub index_position: Position,
+}
+
+/// `object[index] = value`.
+#[derive(Clone, Serialize)]
+pub struct IndexSetExpr {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub index: Box<Expr>,
+    pub index_position: Position,
+    pub value: Box<Expr>,
+}
+
+/// `"...${expr}..."`, which converts its parts to strings and joins them.
+/// The parts are the embedded expressions and the string literals between
+/// them, in order.
+#[derive(Clone, Serialize)]
+pub struct InterpolationExpr {
+    /// The token opening the string, up to its first `${`.
+    pub start: Token,
+    pub parts: Vec<Expr>,
+}
+
+/// `lhs and rhs` or `lhs or rhs`, which evaluates to the operand deciding
+/// the result, and only evaluates `rhs` if `lhs` doesn't.
+#[derive(Clone, Serialize)]
+pub struct LogicalExpr {
+    pub lhs: Box<Expr>,
+    pub operator: Token,
+    pub rhs: Box<Expr>,
+}
+
+/// `[element, ...]`, which creates a new list.
+#[derive(Clone, Serialize)]
+pub struct ListExpr {
+    pub bracket: Token,
+    pub elements: Vec<Expr>,
+}
+
+/// `{key: value, ...}`, which creates a new map.
+#[derive(Clone, Serialize)]
+pub struct MapExpr {
+    pub brace: Token,
+    pub entries: Vec<MapEntry>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MapEntry {
+    pub key: Expr,
+    /// Where `key` is in the source, which errors about it point to.
+    pub key_position: Position,
+    pub value: Expr,
+}
+
+/// `match subject { pattern => value, ... }`, which evaluates to the value
+/// of the first arm whose pattern matches `subject` and whose guard, if it
+/// has one, is truthy.
+#[derive(Clone, Serialize)]
+pub struct MatchExpr {
+    pub keyword: Token,
+    pub subject: Box<Expr>,
+    /// Where `subject` is in the source, which errors about it point to.
+    pub subject_position: Position,
+    pub arms: Vec<MatchArm>,
+}
+
+/// `pattern => body` or `pattern if guard => body`.
+#[derive(Clone, Serialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+/// What a `match` arm compares the matched value against.
+#[derive(Clone, Serialize)]
+pub enum Pattern {
+    /// Matches values equal to `value`.
+    Literal { value: Literal, position: Position },
+    /// `_`, which matches anything.
+    Wildcard(Token),
+    /// A name, which matches anything and binds it in the guard and body of
+    /// the arm.
+    Binding(Token),
+}
+
+impl Pattern {
+    pub fn position(&self) -> &Position {
+        match self {
+            Pattern::Literal { position, .. } => position,
+            Pattern::Wildcard(token) | Pattern::Binding(token) => &token.position,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct SetExpr {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SuperExpr {
+    pub keyword: Token,
+    pub method: Token,
+    #[serde(skip)]
+    pub depth: Cell<Option<usize>>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ThisExpr {
+    pub keyword: Token,
+    #[serde(skip)]
+    pub depth: Cell<Option<usize>>,
+}
+
+/// `try expr`, which returns `expr` from the enclosing function if it is an
+/// `Error`, and evaluates to it otherwise.
+#[derive(Clone, Serialize)]
+pub struct TryExpr {
+    pub keyword: Token,
+    pub expr: Box<Expr>,
+}
+
+#[derive(Clone, Serialize)]
 pub struct UnaryExpr {
     pub operator: Token,
     pub rhs: Box<Expr>,
 }
 
+#[derive(Clone, Serialize)]
+pub struct VariableExpr {
+    pub name: Token,
+    #[serde(skip)]
+    pub depth: Cell<Option<usize>>,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
 pub enum Literal {
     String(Option<String>),
+    Int(i64),
     Number(Option<f64>),
+    Bool(bool),
+    Nil,
 }
 
 impl Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut string = String::new();
         match self {
+            Expr::Assign(expr) => {
+                string.push_str("(= ");
+                string.push_str(&expr.name.lexeme());
+                string.push(' ');
+                string.push_str(&expr.value.to_string());
+                string.push(')');
+            }
             Expr::Binary(expr) => {
                 string.push('(');
                 string.push_str(&expr.operator.lexeme());
@@ -42,19 +247,125 @@ impl Display for Expr {
                 string.push_str(&expr.rhs.to_string());
                 string.push(')');
             }
+            Expr::Call(expr) => {
+                string.push_str("(call ");
+                string.push_str(&expr.callee.to_string());
+                for argument in &expr.arguments {
+                    string.push(' ');
+                    string.push_str(&argument.to_string());
+                }
+                string.push(')');
+            }
+            Expr::Get(expr) => {
+                string.push_str("(. ");
+                string.push_str(&expr.object.to_string());
+                string.push(' ');
+                string.push_str(&expr.name.lexeme());
+                string.push(')');
+            }
             Expr::Grouping(expr) => {
                 string.push('(');
                 string.push_str("group ");
                 string.push_str(&expr.expr.to_string());
                 string.push(')');
             }
-            Expr::Literal(expr) => {
-                let str = match expr {
-                    Literal::String(val) if let Some(val) = val => val.to_string(),
-                    Literal::Number(val) if let Some(val) = val => val.to_string(),
-                    _ => "nil".to_string(),
-                };
-                string.push_str(&str);
+            Expr::Index(expr) => {
+                string.push_str("([] ");
+                string.push_str(&expr.object.to_string());
+                string.push(' ');
+                string.push_str(&expr.index.to_string());
+                string.push(')');
+            }
+            Expr::IndexSet(expr) => {
+                string.push_str("(= ([] ");
+                string.push_str(&expr.object.to_string());
+                string.push(' ');
+                string.push_str(&expr.index.to_string());
+                string.push_str(") ");
+                string.push_str(&expr.value.to_string());
+                string.push(')');
+            }
+            Expr::List(expr) => {
+                string.push_str("(list");
+                for element in &expr.elements {
+                    string.push(' ');
+                    string.push_str(&element.to_string());
+                }
+                string.push(')');
+            }
+            Expr::Map(expr) => {
+                string.push_str("(map");
+                for entry in &expr.entries {
+                    string.push_str(" (");
+                    string.push_str(&entry.key.to_string());
+                    string.push(' ');
+                    string.push_str(&entry.value.to_string());
+                    string.push(')');
+                }
+                string.push(')');
+            }
+            Expr::Interpolation(expr) => {
+                string.push_str("(interpolate");
+                for part in &expr.parts {
+                    string.push(' ');
+                    string.push_str(&part.to_string());
+                }
+                string.push(')');
+            }
+            Expr::Literal(expr) => string.push_str(&expr.to_string()),
+            Expr::Match(expr) => {
+                string.push_str("(match ");
+                string.push_str(&expr.subject.to_string());
+                for arm in &expr.arms {
+                    string.push_str(" (");
+                    string.push_str(&arm.pattern.to_string());
+                    if let Some(guard) = &arm.guard {
+                        string.push_str(" if ");
+                        string.push_str(&guard.to_string());
+                    }
+                    string.push(' ');
+                    string.push_str(&arm.body.to_string());
+                    string.push(')');
+                }
+                string.push(')');
+            }
+            Expr::Logical(expr) => {
+                string.push('(');
+                string.push_str(&expr.operator.lexeme());
+                string.push(' ');
+                string.push_str(&expr.lhs.to_string());
+                string.push(' ');
+                string.push_str(&expr.rhs.to_string());
+                string.push(')');
+            }
+            Expr::Conditional(expr) => {
+                string.push_str("(?: ");
+                string.push_str(&expr.condition.to_string());
+                string.push(' ');
+                string.push_str(&expr.then_branch.to_string());
+                string.push(' ');
+                string.push_str(&expr.else_branch.to_string());
+                string.push(')');
+            }
+            Expr::Set(expr) => {
+                string.push_str("(= (. ");
+                string.push_str(&expr.object.to_string());
+                string.push(' ');
+                string.push_str(&expr.name.lexeme());
+                string.push_str(") ");
+                string.push_str(&expr.value.to_string());
+                string.push(')');
+            }
+            Expr::Super(expr) => {
+                string.push_str("(. super ");
+                string.push_str(&expr.method.lexeme());
+                string.push(')');
+            }
+            Expr::This(_) => string.push_str("this"),
+            Expr::Try(expr) => {
+                string.push_str("(try ");
+                string.push_str(&expr.expr.to_string());
+                string.push(')');
             }
             Expr::Unary(expr) => {
                 string.push('(');
@@ -63,11 +374,34 @@ impl Display for Expr {
                 string.push_str(&expr.rhs.to_string());
                 string.push(')');
             }
+            Expr::Variable(expr) => string.push_str(&expr.name.lexeme()),
         }
         write!(f, "{string}")
     }
 }
 
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::String(Some(val)) => write!(f, "{val}"),
+            Literal::Int(val) => write!(f, "{val}"),
+            Literal::Number(Some(val)) => write!(f, "{val}"),
+            Literal::Bool(val) => write!(f, "{val}"),
+            _ => write!(f, "nil"),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Literal { value, .. } => write!(f, "{value}"),
+            Pattern::Wildcard(_) => write!(f, "_"),
+            Pattern::Binding(name) => write!(f, "{}", name.lexeme()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -87,6 +421,7 @@ mod test {
                     start: 0,
                     end: 0,
                     line: 0,
+                    column: 0,
                 },
             },
             rhs: Box::new(Expr::Literal(Literal::Number(Some(2.0)))),
@@ -99,6 +434,7 @@ mod test {
                     start: 0,
                     end: 0,
                     line: 0,
+                    column: 0,
                 },
             },
             rhs: Box::new(Expr::Binary(BinaryExpr {
@@ -109,6 +445,7 @@ mod test {
                         start: 0,
                         end: 0,
                         line: 0,
+                        column: 0,
                     },
                 },
                 rhs: Box::new(Expr::Literal(Literal::Number(Some(3.0)))),
@@ -122,6 +459,7 @@ mod test {
                     start: 0,
                     end: 0,
                     line: 0,
+                    column: 0,
                 },
             },
             rhs: Box::new(Expr::Binary(BinaryExpr {
@@ -132,6 +470,7 @@ mod test {
                         start: 0,
                         end: 0,
                         line: 0,
+                        column: 0,
                     },
                 },
                 rhs: Box::new(Expr::Grouping(GroupingExpr {
@@ -142,6 +481,7 @@ mod test {
                                 start: 0,
                                 end: 0,
                                 line: 0,
+                                column: 0,
                             },
                         },
                         rhs: Box::new(Expr::Literal(Literal::Number(Some(3.0)))),
@@ -0,0 +1,47 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{
+    class::Instance, environment::Environment, heap::Heap, interpreter::Value, module::Module,
+    stmt::FunctionStmt,
+};
+
+/// A user-defined function along with the environment it was declared in,
+/// which it closes over.
+pub struct Function {
+    pub declaration: Rc<FunctionStmt>,
+    pub closure: Rc<RefCell<Environment>>,
+    /// Whether this is a class' `init` method, which always returns `this`.
+    pub is_initializer: bool,
+    /// The module the function was declared in, whose globals it sees, or
+    /// `None` for the main script.
+    pub module: Option<Rc<Module>>,
+}
+
+impl Function {
+    /// Returns a copy of this method whose closure binds `this` to
+    /// `instance`.
+    pub fn bind(&self, instance: Rc<Instance>, heap: &mut Heap) -> Function {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", Value::Instance(instance));
+        Function {
+            declaration: Rc::clone(&self.declaration),
+            closure: heap.environment(environment),
+            is_initializer: self.is_initializer,
+            module: self.module.clone(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.declaration.name.lexeme()
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
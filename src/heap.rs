@@ -0,0 +1,323 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    class::{Class, Instance},
+    environment::Environment,
+    function::Function,
+    interpreter::Value,
+    list::List,
+    map::Map,
+};
+
+/// Number of tracked objects below which no collection is attempted.
+const MIN_THRESHOLD: usize = 1024;
+
+/// Keeps track of the reference counted objects allocated by the
+/// `Interpreter` so that cycles among them, like a closure stored in the
+/// environment it closes over, can be collected.
+///
+/// Collection uses trial deletion: an object referenced more times than the
+/// tracked objects account for is referenced from outside the heap, e.g. by
+/// the interpreter or the host, and is a root. Objects not reachable from a
+/// root can only be referenced by each other and have their contents cleared,
+/// which breaks the cycles and lets `Rc` free them. Untracked references only
+/// ever make the collector keep more alive, so collecting is safe at any time.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Tracked>,
+    /// Number of tracked objects at which to collect next.
+    threshold: usize,
+}
+
+#[derive(Debug)]
+enum Tracked {
+    Environment(Weak<RefCell<Environment>>),
+    Function(Weak<Function>),
+    Class(Weak<Class>),
+    Instance(Weak<Instance>),
+    List(Weak<List>),
+    Map(Weak<Map>),
+}
+
+/// A live tracked object.
+enum Object {
+    Environment(Rc<RefCell<Environment>>),
+    Function(Rc<Function>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    List(Rc<List>),
+    Map(Rc<Map>),
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            objects: vec![],
+            threshold: MIN_THRESHOLD,
+        }
+    }
+
+    pub fn environment(&mut self, environment: Environment) -> Rc<RefCell<Environment>> {
+        let environment = Rc::new(RefCell::new(environment));
+        self.track(Tracked::Environment(Rc::downgrade(&environment)));
+        environment
+    }
+
+    pub fn function(&mut self, function: Function) -> Rc<Function> {
+        let function = Rc::new(function);
+        self.track(Tracked::Function(Rc::downgrade(&function)));
+        function
+    }
+
+    pub fn class(&mut self, class: Class) -> Rc<Class> {
+        let class = Rc::new(class);
+        self.track(Tracked::Class(Rc::downgrade(&class)));
+        class
+    }
+
+    pub fn instance(&mut self, instance: Instance) -> Rc<Instance> {
+        let instance = Rc::new(instance);
+        self.track(Tracked::Instance(Rc::downgrade(&instance)));
+        instance
+    }
+
+    pub fn list(&mut self, list: List) -> Rc<List> {
+        let list = Rc::new(list);
+        self.track(Tracked::List(Rc::downgrade(&list)));
+        list
+    }
+
+    pub fn map(&mut self, map: Map) -> Rc<Map> {
+        let map = Rc::new(map);
+        self.track(Tracked::Map(Rc::downgrade(&map)));
+        map
+    }
+
+    fn track(&mut self, object: Tracked) {
+        self.objects.push(object);
+        if self.objects.len() >= self.threshold {
+            self.collect();
+        }
+    }
+
+    /// Frees every tracked object that is only referenced from cycles,
+    /// returning the number of objects freed since the last collection.
+    pub fn collect(&mut self) -> usize {
+        let before = self.objects.len();
+        let objects = self
+            .objects
+            .iter()
+            .filter_map(Tracked::upgrade)
+            .collect::<Vec<_>>();
+        let indices = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| (object.address(), i))
+            .collect::<HashMap<_, _>>();
+        let children = objects.iter().map(Object::children).collect::<Vec<_>>();
+
+        let mut internal = vec![0; objects.len()];
+        for address in children.iter().flatten().flatten() {
+            if let Some(&i) = indices.get(address) {
+                internal[i] += 1;
+            }
+        }
+        // Objects currently borrowed can't be traced, so they are treated as
+        // roots. The extra reference is the one held by `objects`.
+        let mut pending = (0..objects.len())
+            .filter(|&i| children[i].is_none() || objects[i].strong_count() - 1 > internal[i])
+            .collect::<Vec<_>>();
+        let mut reachable = vec![false; objects.len()];
+        while let Some(i) = pending.pop() {
+            if reachable[i] {
+                continue;
+            }
+            reachable[i] = true;
+            for address in children[i].iter().flatten() {
+                if let Some(&child) = indices.get(address) {
+                    pending.push(child);
+                }
+            }
+        }
+
+        let garbage = objects
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, reachable)| !**reachable)
+            .map(|(object, _)| object.clear())
+            .collect::<Vec<_>>();
+        drop(garbage);
+        drop(objects);
+
+        self.objects.retain(Tracked::is_alive);
+        self.threshold = MIN_THRESHOLD.max(self.objects.len() * 2);
+        before - self.objects.len()
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            Tracked::Environment(weak) => weak.upgrade().map(Object::Environment),
+            Tracked::Function(weak) => weak.upgrade().map(Object::Function),
+            Tracked::Class(weak) => weak.upgrade().map(Object::Class),
+            Tracked::Instance(weak) => weak.upgrade().map(Object::Instance),
+            Tracked::List(weak) => weak.upgrade().map(Object::List),
+            Tracked::Map(weak) => weak.upgrade().map(Object::Map),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            Tracked::Environment(weak) => weak.strong_count() > 0,
+            Tracked::Function(weak) => weak.strong_count() > 0,
+            Tracked::Class(weak) => weak.strong_count() > 0,
+            Tracked::Instance(weak) => weak.strong_count() > 0,
+            Tracked::List(weak) => weak.strong_count() > 0,
+            Tracked::Map(weak) => weak.strong_count() > 0,
+        }
+    }
+}
+
+impl Object {
+    fn address(&self) -> *const () {
+        match self {
+            Object::Environment(rc) => Rc::as_ptr(rc) as *const (),
+            Object::Function(rc) => Rc::as_ptr(rc) as *const (),
+            Object::Class(rc) => Rc::as_ptr(rc) as *const (),
+            Object::Instance(rc) => Rc::as_ptr(rc) as *const (),
+            Object::List(rc) => Rc::as_ptr(rc) as *const (),
+            Object::Map(rc) => Rc::as_ptr(rc) as *const (),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::Environment(rc) => Rc::strong_count(rc),
+            Object::Function(rc) => Rc::strong_count(rc),
+            Object::Class(rc) => Rc::strong_count(rc),
+            Object::Instance(rc) => Rc::strong_count(rc),
+            Object::List(rc) => Rc::strong_count(rc),
+            Object::Map(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    /// Addresses of the objects this one holds a strong reference to, one
+    /// per reference, or `None` if it is borrowed.
+    fn children(&self) -> Option<Vec<*const ()>> {
+        let mut children = vec![];
+        match self {
+            Object::Environment(environment) => {
+                let environment = environment.try_borrow_mut().ok()?;
+                if let Some(enclosing) = environment.enclosing() {
+                    children.push(Rc::as_ptr(enclosing) as *const ());
+                }
+                children.extend(environment.values().filter_map(address_of));
+            }
+            Object::Function(function) => {
+                children.push(Rc::as_ptr(&function.closure) as *const ());
+            }
+            Object::Class(class) => {
+                if let Some(superclass) = &class.superclass {
+                    children.push(Rc::as_ptr(superclass) as *const ());
+                }
+                children.extend(
+                    class
+                        .methods
+                        .values()
+                        .map(|method| Rc::as_ptr(method) as *const ()),
+                );
+            }
+            Object::Instance(instance) => {
+                children.push(Rc::as_ptr(&instance.class) as *const ());
+                let fields = instance.fields.try_borrow_mut().ok()?;
+                children.extend(fields.values().filter_map(address_of));
+            }
+            Object::List(list) => {
+                let elements = list.elements.try_borrow_mut().ok()?;
+                children.extend(elements.iter().filter_map(address_of));
+            }
+            Object::Map(map) => {
+                let entries = map.entries.try_borrow_mut().ok()?;
+                children.extend(entries.iter().filter_map(|(_, value)| address_of(value)));
+            }
+        }
+        Some(children)
+    }
+
+    /// Drops the references held by this object, returning them so they can
+    /// be dropped once no object is borrowed anymore. Only environments,
+    /// instances, lists and maps are mutable, so every cycle goes through
+    /// one of them.
+    fn clear(&self) -> Vec<Value> {
+        match self {
+            Object::Environment(environment) => environment.borrow_mut().take_values(),
+            Object::Instance(instance) => instance.fields.take().into_values().collect(),
+            Object::List(list) => list.elements.take(),
+            Object::Map(map) => map.entries.borrow_mut().take_values(),
+            Object::Function(_) | Object::Class(_) => vec![],
+        }
+    }
+}
+
+/// Address of the tracked object `value` refers to, if any.
+fn address_of(value: &Value) -> Option<*const ()> {
+    match value {
+        Value::Function(function) => Some(Rc::as_ptr(function) as *const ()),
+        Value::Class(class) => Some(Rc::as_ptr(class) as *const ()),
+        Value::Instance(instance) => Some(Rc::as_ptr(instance) as *const ()),
+        Value::List(list) => Some(Rc::as_ptr(list) as *const ()),
+        Value::Map(map) => Some(Rc::as_ptr(map) as *const ()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, rc::Rc};
+
+    use crate::{
+        class::{Class, Instance},
+        environment::Environment,
+        interpreter::Value,
+    };
+
+    use super::Heap;
+
+    #[test]
+    fn check_cycles() {
+        let mut heap = Heap::new();
+        let class = heap.class(Class {
+            name: "A".to_string(),
+            superclass: None,
+            methods: HashMap::new(),
+        });
+        let instance = heap.instance(Instance::new(Rc::clone(&class)));
+        instance
+            .fields
+            .borrow_mut()
+            .insert("self".to_string(), Value::Instance(Rc::clone(&instance)));
+        let environment = heap.environment(Environment::new());
+        environment
+            .borrow_mut()
+            .define("instance", Value::Instance(Rc::clone(&instance)));
+        drop(instance);
+
+        assert_eq!(heap.collect(), 0);
+        // The environment is freed right away, the instance only by the
+        // collector.
+        drop(environment);
+        assert_eq!(heap.collect(), 2);
+        assert_eq!(Rc::strong_count(&class), 1);
+    }
+}
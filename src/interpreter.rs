@@ -0,0 +1,2594 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    ops::RangeInclusive,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+
+use miette::SourceSpan;
+
+use crate::{
+    class::{Class, Instance},
+    diag::Diag,
+    engine,
+    environment::Environment,
+    errors::{FoxError, RuntimeError},
+    expr::{
+        BinaryExpr, CallExpr, Expr, GetExpr, Literal, MatchArm, MatchExpr, Pattern, SetExpr,
+        SuperExpr, UnaryExpr,
+    },
+    function::Function,
+    heap::Heap,
+    iter::Iter,
+    lexer::{Keyword, Lexer, Token, TokenType},
+    list::{self, List},
+    map::{Entries, Map},
+    module::{Module, Modules},
+    native::{self, CallError, NativeError, NativeFn, NativeFunction},
+    number::{NumericError, NumericOp},
+    output::{Output, OutputLimits},
+    parser::Parser,
+    range::Range,
+    record::{Mutation, Recorder},
+    repl,
+    resolver::Resolver,
+    source::{LineIndex, PositionEncoding, SourceFile},
+    stdlib,
+    stmt::{ClassStmt, ImportStmt, Stmt, ThrowStmt, TryStmt},
+    vm::Closure,
+};
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Function(Rc<Function>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    List(Rc<List>),
+    Map(Rc<Map>),
+    Range(Range),
+    Module(Rc<Module>),
+    Native(Rc<NativeFunction>),
+    /// A function compiled for the bytecode `Vm`.
+    Closure(Rc<Closure>),
+    Nil,
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+            (Value::Float(lhs), Value::Float(rhs)) => lhs == rhs,
+            (Value::Int(int), Value::Float(float)) | (Value::Float(float), Value::Int(int)) => {
+                *int as f64 == *float
+            }
+            (Value::String(lhs), Value::String(rhs)) => lhs == rhs,
+            (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+            (Value::Function(lhs), Value::Function(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::Class(lhs), Value::Class(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::Instance(lhs), Value::Instance(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::List(lhs), Value::List(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::Map(lhs), Value::Map(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::Range(lhs), Value::Range(rhs)) => lhs == rhs,
+            (Value::Module(lhs), Value::Module(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::Native(lhs), Value::Native(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::Closure(lhs), Value::Closure(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// `nil` and `false` are falsy, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Function(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Range(_) => "range",
+            Value::Module(_) => "module",
+            Value::Native(_) | Value::Closure(_) => "function",
+            Value::Nil => "nil",
+        }
+    }
+
+    /// Makes the instance, list or map this refers to deeply immutable.
+    /// Other values are immutable to begin with.
+    pub(crate) fn freeze(&self) {
+        match self {
+            Value::Instance(instance) => instance.freeze(),
+            Value::List(list) => list.freeze(),
+            Value::Map(map) => map.freeze(),
+            _ => {}
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{int}"),
+            Value::Float(num) => write!(f, "{num}"),
+            Value::String(str) => write!(f, "{str}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Function(fun) => write!(f, "{fun:?}"),
+            Value::Class(class) => write!(f, "{class:?}"),
+            Value::Instance(instance) => write!(f, "{instance:?}"),
+            Value::List(list) => write!(f, "{list}"),
+            Value::Map(map) => write!(f, "{map}"),
+            Value::Range(range) => write!(f, "{range}"),
+            Value::Module(module) => write!(f, "{module:?}"),
+            Value::Native(native) => write!(f, "{native:?}"),
+            Value::Closure(closure) => write!(f, "{closure:?}"),
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(int: i64) -> Self {
+        Value::Int(int)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(num: f64) -> Self {
+        Value::Float(num)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Value::Bool(bool)
+    }
+}
+
+impl From<String> for Value {
+    fn from(str: String) -> Self {
+        Value::String(str)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(str: &str) -> Self {
+        Value::String(str.to_string())
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::String(Some(str)) => Value::String(str.to_string()),
+            Literal::Number(Some(num)) => Value::Float(*num),
+            Literal::Int(int) => Value::Int(*int),
+            Literal::Bool(bool) => Value::Bool(*bool),
+            _ => Value::Nil,
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Self {
+        option.map_or(Value::Nil, Into::into)
+    }
+}
+
+/// Describes a value that couldn't be converted to a Rust type.
+fn expected(expected: &str, found: &Value) -> String {
+    format!("expected {expected}, found {}", found.type_name())
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(_) | Value::Float(_) => Ok(value.as_float().unwrap()),
+            _ => Err(expected("a number", value)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for i64 {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(int) => Ok(*int),
+            _ => Err(expected("an int", value)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(bool) => Ok(*bool),
+            _ => Err(expected("a bool", value)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String(str) => Ok(str.to_string()),
+            _ => Err(expected("a string", value)),
+        }
+    }
+}
+
+/// A handle to a Fox function or class that the host can hold on to, see
+/// `Interpreter::take_callback`.
+#[derive(Debug, Clone)]
+pub struct Callback {
+    name: String,
+    callee: Value,
+}
+
+/// Reasons for abandoning the execution of statements early.
+enum ControlFlow {
+    Return(Value),
+    Break,
+    Continue,
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for ControlFlow {
+    fn from(err: RuntimeError) -> Self {
+        ControlFlow::Error(err)
+    }
+}
+
+/// A call being made, for stack traces, which only work out the line the
+/// call is on when they are asked for.
+#[derive(Clone)]
+struct Frame {
+    callee: Value,
+    /// The script the call is made from and its span there, or `None` if
+    /// the host makes it.
+    call_site: Option<(SourceFile, SourceSpan)>,
+}
+
+/// How deep calls can be nested before raising `runtime::stack_overflow`,
+/// low enough for the native stack of a thread to hold them.
+const FRAMES_MAX: usize = 1024;
+
+/// Fox code run by every interpreter before any script.
+const PRELUDE: &str = "
+    class Error {
+        fn init(kind, message, data) {
+            this.kind = kind;
+            this.message = message;
+            this.data = data;
+        }
+    }
+";
+
+/// Tree-walking interpreter evaluating the statements produced by the
+/// `Parser`.
+pub struct Interpreter {
+    source: SourceFile,
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    heap: Heap,
+    /// The `Error` class of the prelude, which every thrown value must be an
+    /// instance of. Kept separately in case a script shadows the global.
+    error_class: Rc<Class>,
+    /// The value being thrown, while the `runtime::thrown` or
+    /// `runtime::native_failed` error it caused propagates to a `catch`.
+    thrown: Option<Value>,
+    /// Functions being called, outermost first, for stack traces.
+    frames: Vec<Frame>,
+    /// Stack trace of the runtime error propagating to a `catch`, captured
+    /// before the calls it unwinds are popped off `frames`.
+    error_stack: Option<String>,
+    output: Output,
+    /// The `debug` builtin, which pauses the script rather than running a
+    /// function. Kept separately in case a script shadows the global.
+    debug: Rc<NativeFunction>,
+    /// Run when the script calls `debug()`, or raises an uncaught error
+    /// with `debug_on_error` set, which is passed along. The interpreter is
+    /// set up to run code in the scope of the call or error.
+    pub(crate) debugger: fn(&mut Interpreter, Option<&RuntimeError>),
+    /// Whether to pause in the scope of an uncaught runtime error before it
+    /// ends the script.
+    debug_on_error: bool,
+    /// Whether the runtime error propagating has paused the script already.
+    paused_on_error: bool,
+    /// Number of `try` bodies running, which catch any error raised.
+    catching: usize,
+    /// Records the changes to variables and properties for the debugger,
+    /// if enabled.
+    pub(crate) recorder: Option<Recorder>,
+    modules: Modules,
+    /// The `std` module, whose submodules named after a type hold the
+    /// methods of its values, like `std.string` for strings. Kept separately
+    /// in case a script shadows the global.
+    std: Rc<Module>,
+    /// When the scripts being run must have finished, along with how long
+    /// they were given, if limited.
+    deadline: Option<(Instant, Duration)>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        for function in native::builtins() {
+            let name = function.name.to_string();
+            globals
+                .borrow_mut()
+                .define(&name, Value::Native(Rc::new(function)));
+        }
+        let debug = Rc::new(NativeFunction {
+            name: "debug".to_string(),
+            arity: 0,
+            optional: 0,
+            function: NativeFn::Plain(Box::new(|_| Ok(Value::Nil))),
+        });
+        globals
+            .borrow_mut()
+            .define("debug", Value::Native(Rc::clone(&debug)));
+        let std = Rc::new(stdlib::module());
+        globals
+            .borrow_mut()
+            .define("std", Value::Module(Rc::clone(&std)));
+        let mut interpreter = Interpreter {
+            source: SourceFile::default(),
+            environment: Rc::clone(&globals),
+            globals,
+            heap: Heap::new(),
+            // Replaced by the class the prelude declares right below.
+            error_class: Rc::new(Class {
+                name: String::new(),
+                superclass: None,
+                methods: HashMap::new(),
+            }),
+            thrown: None,
+            frames: vec![],
+            error_stack: None,
+            output: Output::default(),
+            debug,
+            debugger: repl::debug,
+            debug_on_error: false,
+            paused_on_error: false,
+            catching: 0,
+            recorder: None,
+            modules: Modules::default(),
+            std,
+            deadline: None,
+        };
+
+        let prelude = SourceFile::new("<prelude>", PRELUDE);
+        let (tokens, _) = Lexer::new(&prelude).scan_all();
+        let stmts = Parser::new(&prelude, tokens)
+            .parse()
+            .expect("prelude must parse");
+        Resolver::new(&prelude)
+            .resolve(&stmts)
+            .expect("prelude must resolve");
+        interpreter
+            .interpret(&prelude, &stmts)
+            .expect("prelude must run");
+        let Some(Value::Class(error_class)) = interpreter.global("Error") else {
+            unreachable!("prelude declares `Error`");
+        };
+        interpreter.error_class = error_class;
+        interpreter
+    }
+
+    /// Executes `stmts`, stopping at the first runtime error. `source` is the
+    /// code the statements were parsed from and is used for diagnostics.
+    pub fn interpret(&mut self, source: &SourceFile, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+        self.eval(source, stmts).map(drop)
+    }
+
+    /// Like `Interpreter::interpret`, but returns the value of the script:
+    /// the one of a top-level `return`, or else of the last statement if it
+    /// is an expression, or else `nil`.
+    pub(crate) fn eval(
+        &mut self,
+        source: &SourceFile,
+        stmts: &[Stmt],
+    ) -> Result<Value, RuntimeError> {
+        self.source = source.clone();
+        let mut value = Value::Nil;
+        for stmt in stmts {
+            let result = match stmt {
+                Stmt::Expression(expr) => self.evaluate(expr),
+                stmt => self.execute(stmt).map(|()| Value::Nil),
+            };
+            match result {
+                Ok(result) => value = result,
+                // A top-level `return` ends the script.
+                Err(ControlFlow::Return(result)) => return Ok(result),
+                Err(ControlFlow::Error(err)) => {
+                    self.pause_on_error(&err);
+                    self.clear_error();
+                    return Err(err);
+                }
+                Err(ControlFlow::Break | ControlFlow::Continue) => {
+                    unreachable!("the resolver rejects `break` and `continue` outside loops")
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    /// Calls the global function or class `name` with `args`, as if the call
+    /// `name(args...)` was made from Fox code. Meant for hosts that load a
+    /// script once and then repeatedly invoke its entry points.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+        let Some(callee) = self.global(name) else {
+            return Err(Self::invalid_call(name, "it is not defined"));
+        };
+        self.call_from_host(name, callee, args)
+    }
+
+    /// Like `Interpreter::call`, but with the bindings in `scope` layered over
+    /// the globals for the duration of the call, e.g. to pass a web handler
+    /// its request. Code run by the call sees them like globals, shadowing
+    /// any real global of the same name, and they are gone once it returns.
+    /// Assigning to a scoped binding only changes the scoped value.
+    pub fn call_with_scope(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        scope: HashMap<String, Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.globals));
+        for (name, value) in scope {
+            environment.define(&name, value);
+        }
+        let environment = self.heap.environment(environment);
+        let globals = std::mem::replace(&mut self.globals, environment);
+        let result = self.call(name, args);
+        self.globals = globals;
+        result
+    }
+
+    /// Limits how much the scripts run from now on can print. Exceeding a
+    /// limit raises a runtime error, which scripts can catch.
+    pub fn set_output_limits(&mut self, limits: OutputLimits) {
+        self.output.set_limits(limits);
+    }
+
+    /// Limits how long the scripts and calls run from now on can take in
+    /// total, or lifts the limit if `None`. Once it is reached, a loop or
+    /// function call raises a runtime error which scripts can't catch.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+    }
+
+    /// Keeps what scripts print from now on rather than writing it to the
+    /// standard output, until taken with `Interpreter::take_output`.
+    pub(crate) fn capture_output(&mut self) {
+        self.output.capture();
+    }
+
+    /// Returns what scripts printed since capturing started or it was last
+    /// taken.
+    pub(crate) fn take_output(&mut self) -> String {
+        self.output.take_captured()
+    }
+
+    /// Sets whether an uncaught runtime error pauses the script at the
+    /// debug prompt, in the scope it was raised in, before it is returned.
+    pub fn set_debug_on_error(&mut self, debug_on_error: bool) {
+        self.debug_on_error = debug_on_error;
+    }
+
+    /// Starts recording the last `limit` changes the scripts run from now on
+    /// make to variables and properties, which the debug prompt can step
+    /// back through, or stops recording if `limit` is `None`.
+    pub fn set_recording(&mut self, limit: Option<usize>) {
+        self.recorder = limit.map(Recorder::new);
+    }
+
+    /// Frees the objects that are no longer reachable but kept alive by
+    /// reference cycles, returning how many were freed. This also happens
+    /// automatically as the script allocates.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.heap.collect()
+    }
+
+    /// Defines a global function `name` taking `arity` arguments that runs
+    /// `function` when called from Fox code. The function returns either
+    /// its result or a `NativeError` describing why it failed, which Fox code
+    /// can catch and is otherwise reported as a runtime error at the call
+    /// site. Plain `String` messages convert to errors of kind `native`.
+    /// Values convert to and from common Rust types using `TryFrom` and
+    /// `From`.
+    ///
+    /// ```ignore
+    /// interpreter.define_native("hypot", 2, |args| {
+    ///     let (x, y) = (f64::try_from(&args[0])?, f64::try_from(&args[1])?);
+    ///     Ok(x.hypot(y))
+    /// });
+    /// ```
+    pub fn define_native<F, R>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<R, NativeError> + 'static,
+        R: Into<Value>,
+    {
+        let native = NativeFunction {
+            name: name.to_string(),
+            arity,
+            optional: 0,
+            function: NativeFn::Plain(Box::new(move |args| function(args).map(Into::into))),
+        };
+        self.globals
+            .borrow_mut()
+            .define(name, Value::Native(Rc::new(native)));
+    }
+
+    /// Returns the value of the global variable `name`, if defined.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get(name)
+    }
+
+    /// Returns every global variable with its value, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let globals = self.globals.borrow();
+        let mut globals = globals
+            .bindings()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect::<Vec<_>>();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
+    /// Turns `value`, usually a function handed out by a script, into a
+    /// `Callback` the host can store and call at any later point using
+    /// `Interpreter::call_callback`.
+    pub fn take_callback(&self, value: Value) -> Result<Callback, RuntimeError> {
+        match Self::callee_name(&value) {
+            Some(name) => Ok(Callback {
+                name,
+                callee: value,
+            }),
+            None => Err(Self::invalid_call(
+                &value.to_string(),
+                &format!("it is a {}, not a function", value.type_name()),
+            )),
+        }
+    }
+
+    /// Calls `callback` with `args`.
+    ///
+    /// Callbacks can't be invoked while the interpreter is running, i.e.
+    /// from within another call, which is enforced by the exclusive borrow of
+    /// the interpreter. The callback itself may call any Fox code, and state
+    /// it changes is visible to later calls.
+    pub fn call_callback(
+        &mut self,
+        callback: &Callback,
+        args: &[Value],
+    ) -> Result<Value, RuntimeError> {
+        self.call_from_host(&callback.name, callback.callee.clone(), args)
+    }
+
+    fn call_from_host(
+        &mut self,
+        name: &str,
+        callee: Value,
+        args: &[Value],
+    ) -> Result<Value, RuntimeError> {
+        let invalid_call = |reason: String| Self::invalid_call(name, &reason);
+        let Some(arity) = Self::arity(&callee) else {
+            return Err(invalid_call(format!(
+                "it is a {}, not a function",
+                callee.type_name()
+            )));
+        };
+        if !arity.contains(&args.len()) {
+            return Err(invalid_call(format!(
+                "expected {} arguments but got {}",
+                Self::expected(&arity, args.len()),
+                args.len()
+            )));
+        }
+        let result = self.call_value(callee, args.to_vec(), None);
+        if result.is_err() {
+            self.clear_error();
+        }
+        result
+    }
+
+    /// Forgets about the error that was propagating, once it is handed to
+    /// the host.
+    fn clear_error(&mut self) {
+        self.thrown = None;
+        self.error_stack = None;
+        self.paused_on_error = false;
+        self.frames.clear();
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), ControlFlow> {
+        match stmt {
+            Stmt::Block(stmts) => {
+                self.in_new_scope(|this| stmts.iter().try_for_each(|stmt| this.execute(stmt)))?;
+            }
+            Stmt::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            Stmt::For(stmt) => self.in_new_scope(|this| {
+                if let Some(initializer) = &stmt.initializer {
+                    this.execute(initializer)?;
+                }
+                while this.condition(stmt.condition.as_ref())? {
+                    if !this.loop_body(&stmt.body)? {
+                        break;
+                    }
+                    if let Some(increment) = &stmt.increment {
+                        this.evaluate(increment)?;
+                    }
+                }
+                Ok(())
+            })?,
+            Stmt::ForIn(stmt) => {
+                let iterable = self.evaluate(&stmt.iterable)?;
+                let iter = Iter::new(&iterable).map_err(|found| {
+                    RuntimeError::not_iterable(&self.source, stmt.iterable_position.span(), found)
+                })?;
+                for value in iter {
+                    // Every iteration gets its own variable, so that closures
+                    // capture the value of their iteration.
+                    let mut proceed = true;
+                    self.in_new_scope(|this| {
+                        this.record(&stmt.name, || stmt.name.lexeme(), &value);
+                        this.environment
+                            .borrow_mut()
+                            .define(&stmt.name.lexeme(), value);
+                        proceed = this.loop_body(&stmt.body)?;
+                        Ok(())
+                    })?;
+                    if !proceed {
+                        break;
+                    }
+                }
+            }
+            Stmt::Guard(stmt) => {
+                if !self.evaluate(&stmt.condition)?.is_truthy() {
+                    self.execute(&stmt.else_branch)?;
+                }
+            }
+            Stmt::If(stmt) => {
+                if self.evaluate(&stmt.condition)?.is_truthy() {
+                    self.execute(&stmt.then_branch)?;
+                } else if let Some(else_branch) = &stmt.else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::Export(stmt) => {
+                self.execute(&stmt.declaration)?;
+                if let Some(module) = &self.modules.current {
+                    module.export(stmt.name().lexeme());
+                }
+            }
+            Stmt::Import(stmt) => {
+                let module = Value::Module(self.import(stmt)?);
+                self.record(&stmt.name, || stmt.name.lexeme(), &module);
+                self.environment
+                    .borrow_mut()
+                    .define(&stmt.name.lexeme(), module);
+            }
+            Stmt::Let(stmt) => {
+                let value = match &stmt.initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+                self.record(&stmt.name, || stmt.name.lexeme(), &value);
+                self.environment
+                    .borrow_mut()
+                    .define(&stmt.name.lexeme(), value);
+            }
+            Stmt::Print(stmt) => {
+                let value = self.evaluate(&stmt.value)?;
+                self.output.print(&value).map_err(|exceeded| {
+                    exceeded.error(&self.source, stmt.keyword.position.span())
+                })?;
+            }
+            Stmt::While(stmt) => {
+                while self.evaluate(&stmt.condition)?.is_truthy() {
+                    if !self.loop_body(&stmt.body)? {
+                        break;
+                    }
+                }
+            }
+            Stmt::Function(stmt) => {
+                let function = Function {
+                    declaration: Rc::clone(stmt),
+                    closure: Rc::clone(&self.environment),
+                    is_initializer: false,
+                    module: self.modules.current.clone(),
+                };
+                let function = self.heap.function(function);
+                self.environment
+                    .borrow_mut()
+                    .define(&stmt.name.lexeme(), Value::Function(function));
+            }
+            Stmt::Return(stmt) => {
+                let value = match &stmt.value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(ControlFlow::Return(value));
+            }
+            Stmt::Class(stmt) => self.class(stmt)?,
+            Stmt::Throw(stmt) => {
+                let value = self.evaluate(&stmt.value)?;
+                return Err(self.throw(stmt, value).into());
+            }
+            Stmt::Try(stmt) => self.try_catch(stmt)?,
+            Stmt::Break(_) => return Err(ControlFlow::Break),
+            Stmt::Continue(_) => return Err(ControlFlow::Continue),
+        }
+        Ok(())
+    }
+
+    /// Runs the body of a loop, returning whether the loop goes on, which it
+    /// doesn't after a `break`.
+    fn loop_body(&mut self, body: &Stmt) -> Result<bool, ControlFlow> {
+        self.check_deadline()?;
+        match self.execute(body) {
+            Ok(()) | Err(ControlFlow::Continue) => Ok(true),
+            Err(ControlFlow::Break) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn check_deadline(&self) -> Result<(), RuntimeError> {
+        match self.deadline {
+            Some((deadline, limit)) if Instant::now() >= deadline => {
+                Err(RuntimeError::timed_out(limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the error that carries `value`, thrown by `stmt`, to the
+    /// nearest `catch`.
+    fn throw(&mut self, stmt: &ThrowStmt, value: Value) -> RuntimeError {
+        let span = stmt.keyword.position.span();
+        let instance = match &value {
+            Value::Instance(instance) if self.is_error(&value) => instance,
+            _ => {
+                return Diag::error("runtime::invalid_throw")
+                    .arg("found", value.type_name())
+                    .msg("Can only throw instances of `Error`, found {found}")
+                    .source(&self.source)
+                    .primary(span, "thrown here")
+                    .help("create one using `Error(kind, message, data)`")
+                    .into()
+            }
+        };
+        let field = |name: &str| instance.fields.borrow().get(name).cloned();
+        let (kind, message) = (field("kind"), field("message"));
+        // A rethrown error keeps the stack of where it was first thrown.
+        if field("stack").is_none() && !instance.frozen.get() {
+            let stack = Value::String(self.stack_trace());
+            instance
+                .fields
+                .borrow_mut()
+                .insert("stack".to_string(), stack);
+        }
+        let err = Diag::error("runtime::thrown")
+            .arg("kind", kind.unwrap_or(Value::Nil))
+            .arg("message", message.unwrap_or(Value::Nil))
+            .msg("Uncaught {kind} error: {message}")
+            .source(&self.source)
+            .primary(span, "thrown here")
+            .help("consider handling it using `try {{ ... }} catch error {{ ... }}`")
+            .into();
+        self.thrown = Some(value);
+        err
+    }
+
+    /// Runs the body of `stmt`, and its handler if the body fails with a
+    /// runtime error. Errors raised by the interpreter itself are caught as
+    /// `Error` instances too, with the kind given by `RuntimeError::kind`.
+    fn try_catch(&mut self, stmt: &TryStmt) -> Result<(), ControlFlow> {
+        self.catching += 1;
+        let result =
+            self.in_new_scope(|this| stmt.body.iter().try_for_each(|stmt| this.execute(stmt)));
+        self.catching -= 1;
+        let err = match result {
+            // Running out of time ends the script, whatever catches errors.
+            Err(ControlFlow::Error(err)) if !err.is_timeout() => err,
+            result => return result,
+        };
+        let stack = self.error_stack.take();
+        let thrown = self.thrown.take();
+        let error = match thrown {
+            Some(thrown)
+                if err.code == "runtime::thrown" || err.code == "runtime::native_failed" =>
+            {
+                thrown
+            }
+            _ => {
+                let stack = stack.unwrap_or_else(|| self.stack_trace());
+                let error = NativeError::new(err.kind(), &err.message, Value::Nil);
+                self.error_value(error, stack)
+            }
+        };
+
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        environment.define(&stmt.name.lexeme(), error);
+        let environment = self.heap.environment(environment);
+        self.in_environment(environment, |this| {
+            stmt.handler.iter().try_for_each(|stmt| this.execute(stmt))
+        })
+    }
+
+    /// Whether `value` is an instance of `Error` or one of its subclasses.
+    fn is_error(&self, value: &Value) -> bool {
+        matches!(value, Value::Instance(instance) if instance.class.is_subclass_of(&self.error_class))
+    }
+
+    /// Creates an instance of `Error` describing `error`.
+    fn error_value(&mut self, error: NativeError, stack: String) -> Value {
+        let instance = self
+            .heap
+            .instance(Instance::new(Rc::clone(&self.error_class)));
+        instance.fields.borrow_mut().extend([
+            ("kind".to_string(), Value::String(error.kind)),
+            ("message".to_string(), Value::String(error.message)),
+            ("data".to_string(), error.data),
+            ("stack".to_string(), Value::String(stack)),
+        ]);
+        Value::Instance(instance)
+    }
+
+    /// The functions being called, innermost first, one per line.
+    pub(crate) fn stack_trace(&self) -> String {
+        let mut index: Option<LineIndex> = None;
+        let mut lines = vec![];
+        for frame in self.frames.iter().rev() {
+            let name = Self::callee_name(&frame.callee).unwrap_or_default();
+            let Some((source, span)) = &frame.call_site else {
+                lines.push(format!("at {name}, called from the host"));
+                continue;
+            };
+            // Consecutive frames are usually in the same script, whose index
+            // is reused.
+            let index = match &mut index {
+                Some(index) if index.indexes(source) => index,
+                index => index.insert(LineIndex::new(source)),
+            };
+            let line = index
+                .line_col(span.offset(), PositionEncoding::Utf8)
+                .map_or(0, |position| position.line + 1);
+            lines.push(format!("at {name}, called from line {line}"));
+        }
+        lines.join("\n")
+    }
+
+    fn class(&mut self, stmt: &ClassStmt) -> Result<(), RuntimeError> {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self.look_up(&superclass.name, &superclass.depth)? {
+                Value::Class(class) => Some(class),
+                value => {
+                    return Err(Diag::error("runtime::invalid_superclass")
+                        .arg("found", value.type_name())
+                        .msg("Superclass must be a class, found {found}")
+                        .source(&self.source)
+                        .primary(superclass.name.position.span(), "inherited from here")
+                        .into())
+                }
+            },
+            None => None,
+        };
+
+        // Methods of a subclass close over an extra scope binding `super`.
+        let closure = match &superclass {
+            Some(superclass) => {
+                let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                environment.define("super", Value::Class(Rc::clone(superclass)));
+                self.heap.environment(environment)
+            }
+            None => Rc::clone(&self.environment),
+        };
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let name = method.name.lexeme();
+                let function = Function {
+                    declaration: Rc::clone(method),
+                    closure: Rc::clone(&closure),
+                    is_initializer: name == "init",
+                    module: self.modules.current.clone(),
+                };
+                (name, self.heap.function(function))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let class = Class {
+            name: stmt.name.lexeme(),
+            superclass,
+            methods,
+        };
+        let class = self.heap.class(class);
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name.lexeme(), Value::Class(class));
+        Ok(())
+    }
+
+    /// Runs `f` in a fresh scope nested in the current one.
+    fn in_new_scope<T, F>(&mut self, f: F) -> Result<T, ControlFlow>
+    where
+        F: FnOnce(&mut Self) -> Result<T, ControlFlow>,
+    {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        let environment = self.heap.environment(environment);
+        self.in_environment(environment, f)
+    }
+
+    /// Runs `f` with `environment` as the current scope, restoring the
+    /// current scope afterwards even if `f` fails.
+    fn in_environment<T, F>(
+        &mut self,
+        environment: Rc<RefCell<Environment>>,
+        f: F,
+    ) -> Result<T, ControlFlow>
+    where
+        F: FnOnce(&mut Self) -> Result<T, ControlFlow>,
+    {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = f(self);
+        if let Err(ControlFlow::Error(err)) = &result {
+            self.pause_on_error(err);
+        }
+        self.environment = previous;
+        result
+    }
+
+    /// Runs `f` with the globals and source of `module`, or of the main
+    /// script if `None`, as those of the code running.
+    fn in_module<T, F>(&mut self, module: Option<&Rc<Module>>, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let same = match (module, &self.modules.current) {
+            (Some(module), Some(current)) => Rc::ptr_eq(module, current),
+            (None, None) => true,
+            _ => false,
+        };
+        if same {
+            return f(self);
+        }
+        if self.modules.current.is_none() {
+            self.modules.script = Some((Rc::clone(&self.globals), self.source.clone()));
+        }
+        let (globals, source) = match module {
+            Some(module) => (Rc::clone(&module.globals), module.source.clone()),
+            None => self
+                .modules
+                .script
+                .clone()
+                .expect("the main script is kept while a module runs"),
+        };
+        let globals = std::mem::replace(&mut self.globals, globals);
+        let source = std::mem::replace(&mut self.source, source);
+        let current = std::mem::replace(&mut self.modules.current, module.cloned());
+        let result = f(self);
+        self.globals = globals;
+        self.source = source;
+        self.modules.current = current;
+        result
+    }
+
+    /// Returns the module `stmt` imports, loading and running it unless it
+    /// was imported before. Its path is relative to the file importing it.
+    fn import(&mut self, stmt: &ImportStmt) -> Result<Rc<Module>, RuntimeError> {
+        let directory = Path::new(self.source.name()).parent();
+        let path = directory.unwrap_or(Path::new("")).join(&stmt.path);
+        let display = path.display().to_string();
+        let io_error = |error| FoxError::Io {
+            path: display.clone(),
+            error,
+        };
+        let canonical =
+            fs::canonicalize(&path).map_err(|error| self.import_failed(stmt, io_error(error)))?;
+        if let Some(module) = self.modules.loaded.get(&canonical) {
+            return Ok(Rc::clone(module));
+        }
+        let loading = &self.modules.loading;
+        if let Some(start) = loading.iter().position(|(path, _)| *path == canonical) {
+            let chain = loading[start..].iter().map(|(_, path)| path.as_str());
+            return Err(Diag::error("runtime::import_cycle")
+                .arg("path", &stmt.path)
+                .arg("chain", chain.chain([display.as_str()]).join(" -> "))
+                .msg("Circular import of `{path}`")
+                .source(&self.source)
+                .primary(stmt.path_position.span(), "imported again here")
+                .help("the modules import each other: {chain}")
+                .into());
+        }
+        let text = fs::read_to_string(&canonical)
+            .map_err(|error| self.import_failed(stmt, io_error(error)))?;
+        let source = SourceFile::new(display.clone(), text);
+        let (stmts, _) = engine::parse(&source).map_err(|error| self.import_failed(stmt, error))?;
+
+        let script_globals = match &self.modules.script {
+            Some((globals, _)) if self.modules.current.is_some() => globals,
+            _ => &self.globals,
+        };
+        let globals = Environment::with_enclosing(Rc::clone(script_globals));
+        let globals = self.heap.environment(globals);
+        let module = Rc::new(Module::new(stmt.name.lexeme(), source, globals));
+        self.modules.loading.push((canonical.clone(), display));
+        let result = self.in_module(Some(&module), |this| {
+            let globals = Rc::clone(&this.globals);
+            this.in_environment(globals, |this| {
+                stmts.iter().try_for_each(|stmt| this.execute(stmt))
+            })
+        });
+        self.modules.loading.pop();
+        match result {
+            // A top-level `return` ends the module.
+            Ok(()) | Err(ControlFlow::Return(_)) => {}
+            Err(ControlFlow::Error(err)) => return Err(err),
+            Err(ControlFlow::Break | ControlFlow::Continue) => {
+                unreachable!("the resolver rejects `break` and `continue` outside loops")
+            }
+        }
+        self.modules.loaded.insert(canonical, Rc::clone(&module));
+        Ok(module)
+    }
+
+    fn import_failed(&self, stmt: &ImportStmt, error: FoxError) -> RuntimeError {
+        Diag::error("runtime::import_failed")
+            .arg("path", &stmt.path)
+            .msg("Couldn't import `{path}`")
+            .source(&self.source)
+            .primary(stmt.path_position.span(), "imported here")
+            .related(error.into_reports())
+            .into()
+    }
+
+    /// Records that the variable or property `target` named by `name` was
+    /// set to `value`, if recording.
+    fn record<F>(&mut self, name: &Token, target: F, value: &Value)
+    where
+        F: FnOnce() -> String,
+    {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        recorder.record(Mutation {
+            location: format!("{}:{}", self.source.name(), name.position.line),
+            target: target(),
+            value: value.to_string(),
+        });
+    }
+
+    /// Pauses the script for the debugger in the current scope if `err`
+    /// won't be caught and `debug_on_error` is set. As the error propagates
+    /// out of every scope it was raised in, only the innermost one pauses.
+    fn pause_on_error(&mut self, err: &RuntimeError) {
+        if self.debug_on_error && self.catching == 0 && !self.paused_on_error {
+            self.paused_on_error = true;
+            self.debug(Some(err));
+        }
+    }
+
+    fn condition(&mut self, condition: Option<&Expr>) -> Result<bool, ControlFlow> {
+        match condition {
+            Some(expr) => Ok(self.evaluate(expr)?.is_truthy()),
+            None => Ok(true),
+        }
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, ControlFlow> {
+        match expr {
+            Expr::Assign(expr) => {
+                let value = self.evaluate(&expr.value)?;
+                let name = expr.name.lexeme();
+                let assigned = match expr.depth.get() {
+                    Some(distance) => {
+                        self.environment
+                            .borrow_mut()
+                            .assign_at(distance, &name, value.clone())
+                    }
+                    None => self.globals.borrow_mut().assign(&name, value.clone()),
+                };
+                if !assigned {
+                    return Err(self.undefined_variable(&expr.name).into());
+                }
+                self.record(&expr.name, || name, &value);
+                Ok(value)
+            }
+            Expr::Binary(expr) => {
+                let lhs = self.evaluate(&expr.lhs)?;
+                let rhs = self.evaluate(&expr.rhs)?;
+                Ok(self.binary(expr, lhs, rhs)?)
+            }
+            Expr::Call(expr) => {
+                let callee = self.evaluate(&expr.callee)?;
+                let arguments = expr
+                    .arguments
+                    .iter()
+                    .map(|argument| self.evaluate(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(self.call_expr(expr, callee, arguments)?)
+            }
+            Expr::Get(expr) => {
+                let object = self.evaluate(&expr.object)?;
+                Ok(self.get(expr, object)?)
+            }
+            Expr::Grouping(expr) => self.evaluate(&expr.expr),
+            Expr::Index(expr) => {
+                let object = self.evaluate(&expr.object)?;
+                let index = self.evaluate(&expr.index)?;
+                list::index(&object, &index)
+                    .map_err(|err| err.error(&self.source, expr.index_position.span()).into())
+            }
+            Expr::IndexSet(expr) => {
+                let object = self.evaluate(&expr.object)?;
+                let index = self.evaluate(&expr.index)?;
+                let value = self.evaluate(&expr.value)?;
+                if let Err(err) = list::set_index(&object, &index, value.clone()) {
+                    return Err(err.error(&self.source, expr.index_position.span()).into());
+                }
+                let target = || format!("{}[{index}]", expr.object);
+                self.record(&expr.bracket, target, &value);
+                Ok(value)
+            }
+            Expr::List(expr) => {
+                let elements = expr
+                    .elements
+                    .iter()
+                    .map(|element| self.evaluate(element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::List(self.heap.list(List::new(elements))))
+            }
+            Expr::Map(expr) => {
+                let mut entries = Entries::default();
+                for entry in &expr.entries {
+                    let key = self.evaluate(&entry.key)?;
+                    let key = list::key(&key)
+                        .map_err(|err| err.error(&self.source, entry.key_position.span()))?;
+                    let value = self.evaluate(&entry.value)?;
+                    entries.insert(key, value);
+                }
+                Ok(Value::Map(self.heap.map(Map::new(entries))))
+            }
+            Expr::Interpolation(expr) => {
+                let mut string = String::new();
+                for part in &expr.parts {
+                    string.push_str(&self.evaluate(part)?.to_string());
+                }
+                Ok(Value::String(string))
+            }
+            Expr::Literal(literal) => Ok(literal.into()),
+            Expr::Set(expr) => {
+                let object = self.evaluate(&expr.object)?;
+                let value = self.evaluate(&expr.value)?;
+                let value = self.set(expr, object, value)?;
+                let target = || format!("{}.{}", expr.object, expr.name.lexeme());
+                self.record(&expr.name, target, &value);
+                Ok(value)
+            }
+            Expr::Logical(expr) => {
+                let lhs = self.evaluate(&expr.lhs)?;
+                let decides = match expr.operator.ty {
+                    TokenType::Keyword(Keyword::Or) => lhs.is_truthy(),
+                    _ => !lhs.is_truthy(),
+                };
+                if decides {
+                    Ok(lhs)
+                } else {
+                    self.evaluate(&expr.rhs)
+                }
+            }
+            Expr::Match(expr) => self.match_arms(expr),
+            Expr::Conditional(expr) => {
+                if self.evaluate(&expr.condition)?.is_truthy() {
+                    self.evaluate(&expr.then_branch)
+                } else {
+                    self.evaluate(&expr.else_branch)
+                }
+            }
+            Expr::Super(expr) => Ok(self.super_method(expr)?),
+            Expr::This(expr) => Ok(self.look_up(&expr.keyword, &expr.depth)?),
+            Expr::Try(expr) => {
+                let value = self.evaluate(&expr.expr)?;
+                if self.is_error(&value) {
+                    return Err(ControlFlow::Return(value));
+                }
+                Ok(value)
+            }
+            Expr::Unary(expr) => {
+                let rhs = self.evaluate(&expr.rhs)?;
+                Ok(self.unary(expr, rhs)?)
+            }
+            Expr::Variable(expr) => Ok(self.look_up(&expr.name, &expr.depth)?),
+        }
+    }
+
+    /// Evaluates the body of the first arm of `expr` matching its subject.
+    fn match_arms(&mut self, expr: &MatchExpr) -> Result<Value, ControlFlow> {
+        let subject = self.evaluate(&expr.subject)?;
+        for arm in &expr.arms {
+            let chosen = match &arm.pattern {
+                Pattern::Literal { value, .. } if subject != Value::from(value) => continue,
+                Pattern::Literal { .. } | Pattern::Wildcard(_) => self.arm(arm)?,
+                Pattern::Binding(name) => self.in_new_scope(|this| {
+                    this.record(name, || name.lexeme(), &subject);
+                    this.environment
+                        .borrow_mut()
+                        .define(&name.lexeme(), subject.clone());
+                    this.arm(arm)
+                })?,
+            };
+            if let Some(value) = chosen {
+                return Ok(value);
+            }
+        }
+        let span = expr.subject_position.span();
+        Err(RuntimeError::no_match(&self.source, span, &subject.to_string()).into())
+    }
+
+    /// Evaluates the body of `arm` whose pattern matched, unless its guard is
+    /// falsy.
+    fn arm(&mut self, arm: &MatchArm) -> Result<Option<Value>, ControlFlow> {
+        if let Some(guard) = &arm.guard {
+            if !self.evaluate(guard)?.is_truthy() {
+                return Ok(None);
+            }
+        }
+        self.evaluate(&arm.body).map(Some)
+    }
+
+    /// Looks up a variable `depth` scopes out as computed by the `Resolver`,
+    /// or in the globals if it is unresolved.
+    fn look_up(&self, name: &Token, depth: &Cell<Option<usize>>) -> Result<Value, RuntimeError> {
+        let value = match depth.get() {
+            Some(distance) => self.environment.borrow().get_at(distance, &name.lexeme()),
+            None => self.globals.borrow().get(&name.lexeme()),
+        };
+        match value {
+            Some(value) => Ok(value),
+            None => Err(self.undefined_variable(name)),
+        }
+    }
+
+    fn unary(&self, expr: &UnaryExpr, rhs: Value) -> Result<Value, RuntimeError> {
+        if let TokenType::Bang = expr.operator.ty {
+            return Ok(Value::Bool(!rhs.is_truthy()));
+        }
+        rhs.negate().map_err(|err| match err {
+            NumericError::NotANumber => RuntimeError::invalid_operand(
+                &self.source,
+                expr.operator.position.span(),
+                &expr.operator.lexeme(),
+                rhs.type_name(),
+            ),
+            NumericError::Overflow | NumericError::DivisionByZero => self.overflow(&expr.operator),
+        })
+    }
+
+    fn binary(&self, expr: &BinaryExpr, lhs: Value, rhs: Value) -> Result<Value, RuntimeError> {
+        let op = match (&expr.operator.ty, &lhs, &rhs) {
+            (TokenType::EqualEq, lhs, rhs) => return Ok(Value::Bool(lhs == rhs)),
+            (TokenType::BangEq, lhs, rhs) => return Ok(Value::Bool(lhs != rhs)),
+            (TokenType::Plus, Value::String(lhs), Value::String(rhs)) => {
+                return Ok(Value::String(format!("{lhs}{rhs}")))
+            }
+            (TokenType::Plus, ..) => NumericOp::Add,
+            (TokenType::Minus, ..) => NumericOp::Subtract,
+            (TokenType::Star, ..) => NumericOp::Multiply,
+            (TokenType::Slash, ..) => NumericOp::Divide,
+            (TokenType::Percent, ..) => NumericOp::Remainder,
+            (TokenType::StarStar, ..) => NumericOp::Power,
+            (TokenType::Ampersand, ..) => NumericOp::BitAnd,
+            (TokenType::Pipe, ..) => NumericOp::BitOr,
+            (TokenType::Caret, ..) => NumericOp::BitXor,
+            (TokenType::LessLess, ..) => NumericOp::ShiftLeft,
+            (TokenType::GreaterGreater, ..) => NumericOp::ShiftRight,
+            (TokenType::Greater, ..) => NumericOp::Greater,
+            (TokenType::GreaterEq, ..) => NumericOp::GreaterEqual,
+            (TokenType::Less, ..) => NumericOp::Less,
+            (TokenType::LessEq, ..) => NumericOp::LessEqual,
+            (TokenType::DotDot, ..) => NumericOp::Range,
+            (TokenType::DotDotEq, ..) => NumericOp::RangeInclusive,
+            _ => unreachable!(),
+        };
+        lhs.numeric(op, &rhs).map_err(|err| match err {
+            NumericError::NotANumber => {
+                let expected = match op {
+                    NumericOp::Add => "two numbers or two strings",
+                    op if op.takes_ints() => "ints",
+                    _ => "numbers",
+                };
+                RuntimeError::invalid_operands(
+                    &self.source,
+                    expr.operator.position.span(),
+                    &expr.operator.lexeme(),
+                    expected,
+                    &format!("{} and {}", lhs.type_name(), rhs.type_name()),
+                )
+            }
+            NumericError::Overflow => self.overflow(&expr.operator),
+            NumericError::DivisionByZero => RuntimeError::division_by_zero(
+                &self.source,
+                expr.operator.position.span(),
+                &expr.operator.lexeme(),
+            ),
+        })
+    }
+
+    fn overflow(&self, operator: &Token) -> RuntimeError {
+        let span = operator.position.span();
+        RuntimeError::integer_overflow(&self.source, span, &operator.lexeme())
+    }
+
+    fn call_expr(
+        &mut self,
+        expr: &CallExpr,
+        callee: Value,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let Some(arity) = Self::arity(&callee) else {
+            let span = expr.paren.position.span();
+            return Err(RuntimeError::not_callable(
+                &self.source,
+                span,
+                callee.type_name(),
+            ));
+        };
+        if !arity.contains(&arguments.len()) {
+            return Err(RuntimeError::arity_mismatch(
+                &self.source,
+                expr.paren.position.span(),
+                &Self::callee_name(&callee).unwrap_or_default(),
+                Self::expected(&arity, arguments.len()),
+                arguments.len(),
+            ));
+        }
+        self.call_value(callee, arguments, Some(expr.paren.position.span()))
+    }
+
+    /// The numbers of arguments `callee` can take, or `None` if it can't be
+    /// called.
+    fn arity(callee: &Value) -> Option<RangeInclusive<usize>> {
+        let arity = match callee {
+            Value::Function(function) => function.arity(),
+            Value::Class(class) => class.arity(),
+            Value::Native(native) => return Some(native.arity - native.optional..=native.arity),
+            _ => return None,
+        };
+        Some(arity..=arity)
+    }
+
+    /// The number of arguments to report as expected by a callee taking
+    /// `arity` when called with `found`, the nearest one it takes.
+    fn expected(arity: &RangeInclusive<usize>, found: usize) -> usize {
+        found.clamp(*arity.start(), *arity.end())
+    }
+
+    /// Calls `callee` with `arguments` on behalf of a native function, like
+    /// the comparator `sort` is given. Errors raised by the call propagate
+    /// as they are, so scripts catch the errors their own functions throw.
+    pub(crate) fn call_back(
+        &mut self,
+        callee: &Value,
+        arguments: Vec<Value>,
+    ) -> Result<Value, CallError> {
+        match Self::arity(callee) {
+            Some(arity) if arity.contains(&arguments.len()) => {
+                Ok(self.call_value(callee.clone(), arguments, None)?)
+            }
+            Some(_) => Err(format!(
+                "expected a function taking {} arguments, found `{}`",
+                arguments.len(),
+                Self::callee_name(callee).unwrap_or_default()
+            )
+            .into()),
+            None => Err(format!("expected a function, found {}", callee.type_name()).into()),
+        }
+    }
+
+    /// The name `callee` was declared with, or `None` if it can't be called.
+    fn callee_name(callee: &Value) -> Option<String> {
+        match callee {
+            Value::Function(function) => Some(function.name()),
+            Value::Class(class) => Some(class.name.to_string()),
+            Value::Native(native) => Some(native.name.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Calls `callee`, which must be callable with as many arguments as
+    /// there are in `arguments`. `span` is that of the call in Fox code, if
+    /// it is made from there.
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        span: Option<SourceSpan>,
+    ) -> Result<Value, RuntimeError> {
+        if self.frames.len() == FRAMES_MAX {
+            // Calls the host makes are never that deep, but natives can call
+            // back from anywhere.
+            let span = span.unwrap_or_else(|| (0, 0).into());
+            return Err(RuntimeError::stack_overflow(&self.source, span, FRAMES_MAX));
+        }
+        self.frames.push(Frame {
+            callee: callee.clone(),
+            call_site: span.map(|span| (self.source.clone(), span)),
+        });
+        let result = self.call_frame(callee, arguments, span);
+        if result.is_err() && self.error_stack.is_none() {
+            self.error_stack = Some(self.stack_trace());
+        }
+        self.frames.pop();
+        result
+    }
+
+    fn call_frame(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        span: Option<SourceSpan>,
+    ) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::Function(function) => self.call_function(&function, arguments),
+            Value::Class(class) => {
+                let instance = self.heap.instance(Instance::new(Rc::clone(&class)));
+                if let Some(init) = class.find_method("init") {
+                    let init = init.bind(Rc::clone(&instance), &mut self.heap);
+                    self.call_function(&init, arguments)?;
+                }
+                Ok(Value::Instance(instance))
+            }
+            Value::Native(native) if Rc::ptr_eq(&native, &self.debug) => {
+                self.debug(None);
+                Ok(Value::Nil)
+            }
+            Value::Native(native) => native.call(self, &arguments).map_err(|error| {
+                let error = match error {
+                    CallError::Native(error) => error,
+                    CallError::Fox(err) => return err,
+                };
+                let diag = Diag::error("runtime::native_failed")
+                    .arg("name", &native.name)
+                    .arg("message", &error.message)
+                    .msg("`{name}` failed: {message}");
+                let err = match span {
+                    Some(span) => diag.source(&self.source).primary(span, "called here"),
+                    None => diag.without_source(),
+                }
+                .into();
+                let stack = self.stack_trace();
+                self.thrown = Some(self.error_value(error, stack));
+                err
+            }),
+            _ => unreachable!("callee must be callable"),
+        }
+    }
+
+    /// Pauses the script for `debugger`, because of `error` if given. Code
+    /// it runs sees the variables in scope like globals, so it can read and
+    /// assign them, while the variables it declares are dropped once the
+    /// script resumes. Errors it raises don't pause again.
+    fn debug(&mut self, error: Option<&RuntimeError>) {
+        let scope = Environment::with_enclosing(Rc::clone(&self.environment));
+        let scope = self.heap.environment(scope);
+        let globals = std::mem::replace(&mut self.globals, Rc::clone(&scope));
+        let environment = std::mem::replace(&mut self.environment, scope);
+        let source = self.source.clone();
+        let frames = self.frames.clone();
+        let thrown = self.thrown.take();
+        let error_stack = self.error_stack.take();
+        let paused_on_error = self.paused_on_error;
+        let debug_on_error = std::mem::replace(&mut self.debug_on_error, false);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.pause();
+        }
+        (self.debugger)(self, error);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.resume();
+        }
+        self.globals = globals;
+        self.environment = environment;
+        self.source = source;
+        self.frames = frames;
+        self.thrown = thrown;
+        self.error_stack = error_stack;
+        self.paused_on_error = paused_on_error;
+        self.debug_on_error = debug_on_error;
+    }
+
+    /// The variables in scope, innermost first, without the outermost
+    /// globals like classes and functions.
+    pub(crate) fn scope(&self) -> Vec<(String, Value)> {
+        let mut variables: Vec<(String, Value)> = vec![];
+        let mut environment = Rc::clone(&self.environment);
+        loop {
+            let Some(enclosing) = environment.borrow().enclosing().cloned() else {
+                break;
+            };
+            let mut bindings = environment
+                .borrow()
+                .bindings()
+                .filter(|(name, _)| !variables.iter().any(|(seen, _)| seen == *name))
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect::<Vec<_>>();
+            bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+            variables.extend(bindings);
+            environment = enclosing;
+        }
+        variables
+    }
+
+    fn call_function(
+        &mut self,
+        function: &Function,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        self.check_deadline()?;
+        let mut environment = Environment::with_enclosing(Rc::clone(&function.closure));
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            self.record(param, || param.lexeme(), &argument);
+            environment.define(&param.lexeme(), argument);
+        }
+        let environment = self.heap.environment(environment);
+        let result = self.in_module(function.module.as_ref(), |this| {
+            this.in_environment(environment, |this| {
+                function
+                    .declaration
+                    .body
+                    .iter()
+                    .try_for_each(|stmt| this.execute(stmt))
+            })
+        });
+        let value = match result {
+            Ok(()) => Value::Nil,
+            Err(ControlFlow::Return(value)) => value,
+            Err(ControlFlow::Error(err)) => return Err(err),
+            Err(ControlFlow::Break | ControlFlow::Continue) => {
+                unreachable!("the resolver rejects `break` and `continue` outside loops")
+            }
+        };
+        if function.is_initializer {
+            return Ok(function
+                .closure
+                .borrow()
+                .get_at(0, "this")
+                .unwrap_or(Value::Nil));
+        }
+        Ok(value)
+    }
+
+    fn get(&mut self, expr: &GetExpr, object: Value) -> Result<Value, RuntimeError> {
+        if let Value::Module(module) = &object {
+            let name = expr.name.lexeme();
+            return module.get(&name).ok_or_else(|| {
+                Diag::error("runtime::not_exported")
+                    .arg("name", name)
+                    .arg("module", &module.name)
+                    .msg("Module `{module}` doesn't export `{name}`")
+                    .source(&self.source)
+                    .primary(expr.name.position.span(), "not exported")
+                    .help("declare it with `export` in the module to use it elsewhere")
+                    .into()
+            });
+        }
+        let Value::Instance(instance) = object else {
+            return self.primitive_method(&expr.name, object);
+        };
+        let name = expr.name.lexeme();
+        if let Some(value) = instance.fields.borrow().get(&name) {
+            return Ok(value.clone());
+        }
+        match instance.class.find_method(&name) {
+            Some(method) => {
+                let method = method.bind(Rc::clone(&instance), &mut self.heap);
+                Ok(Value::Function(self.heap.function(method)))
+            }
+            None => Err(self.undefined_property(&expr.name, &instance.class.name)),
+        }
+    }
+
+    /// The method `name` of the value `object` that isn't an instance, which
+    /// is the function of the same name in the module of `std` named after
+    /// its type, bound to `object`.
+    fn primitive_method(&self, name: &Token, object: Value) -> Result<Value, RuntimeError> {
+        let found = object.type_name();
+        let Some(Value::Module(methods)) = self.std.get(found) else {
+            return Err(self.not_an_instance(name, &object));
+        };
+        match methods.get(&name.lexeme()) {
+            Some(Value::Native(method)) if method.arity > 0 => {
+                Ok(Value::Native(Rc::new(method.bind(object))))
+            }
+            _ => Err(Diag::error("runtime::undefined_method")
+                .arg("name", name.lexeme())
+                .arg("found", found)
+                .msg("Undefined method `{name}` of {found} values")
+                .source(&self.source)
+                .primary(name.position.span(), "not a method of {found} values")
+                .help("the methods of {found} values are the functions of `std.{found}`")
+                .into()),
+        }
+    }
+
+    fn set(&self, expr: &SetExpr, object: Value, value: Value) -> Result<Value, RuntimeError> {
+        let Value::Instance(instance) = object else {
+            return Err(self.not_an_instance(&expr.name, &object));
+        };
+        if instance.frozen.get() {
+            return Err(Diag::error("runtime::frozen_instance")
+                .arg("name", expr.name.lexeme())
+                .arg("class", &instance.class.name)
+                .msg("Can't set property `{name}` of a frozen {class} instance")
+                .source(&self.source)
+                .primary(expr.name.position.span(), "set here")
+                .help("frozen instances can't be changed, consider copying it instead")
+                .into());
+        }
+        instance
+            .fields
+            .borrow_mut()
+            .insert(expr.name.lexeme(), value.clone());
+        Ok(value)
+    }
+
+    fn super_method(&mut self, expr: &SuperExpr) -> Result<Value, RuntimeError> {
+        // `this` is always bound in the scope right inside the one binding
+        // `super`, see `Interpreter::class` and `Function::bind`.
+        let distance = expr.depth.get().expect("`super` is always resolved");
+        let environment = self.environment.borrow();
+        let Some(Value::Class(superclass)) = environment.get_at(distance, "super") else {
+            unreachable!("`super` is always bound to a class");
+        };
+        let Some(Value::Instance(instance)) = environment.get_at(distance - 1, "this") else {
+            unreachable!("`this` is always bound to an instance");
+        };
+        drop(environment);
+        match superclass.find_method(&expr.method.lexeme()) {
+            Some(method) => {
+                let method = method.bind(instance, &mut self.heap);
+                Ok(Value::Function(self.heap.function(method)))
+            }
+            None => Err(self.undefined_property(&expr.method, &superclass.name)),
+        }
+    }
+
+    fn undefined_property(&self, name: &Token, class: &str) -> RuntimeError {
+        Diag::error("runtime::undefined_property")
+            .arg("name", name.lexeme())
+            .arg("class", class)
+            .msg("Undefined property `{name}`")
+            .source(&self.source)
+            .primary(
+                name.position.span(),
+                "not a field or method of this {class} instance",
+            )
+            .into()
+    }
+
+    fn not_an_instance(&self, name: &Token, object: &Value) -> RuntimeError {
+        Diag::error("runtime::not_an_instance")
+            .arg("found", object.type_name())
+            .msg("Only instances have properties, found {found}")
+            .source(&self.source)
+            .primary(name.position.span(), "property accessed here")
+            .into()
+    }
+
+    fn undefined_variable(&self, name: &Token) -> RuntimeError {
+        let span = name.position.span();
+        RuntimeError::undefined_variable(&self.source, span, &name.lexeme())
+    }
+
+    /// The error of the host calling `name`, which can't be for `reason`.
+    fn invalid_call(name: &str, reason: &str) -> RuntimeError {
+        Diag::error("runtime::invalid_call")
+            .arg("name", name)
+            .arg("reason", reason)
+            .msg("Cannot call `{name}`: {reason}")
+            .without_source()
+            .into()
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        errors::RuntimeError, lexer::Lexer, native::NativeError, parser::Parser,
+        resolver::Resolver, source::SourceFile, stmt::Stmt,
+    };
+
+    use super::{Interpreter, Value};
+
+    /// Runs `source`, whose last statement must be an expression statement,
+    /// and returns the value of that expression.
+    fn eval(source: &str) -> Result<Value, RuntimeError> {
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        assert!(
+            matches!(stmts.last(), Some(Stmt::Expression(_))),
+            "last statement must be an expression"
+        );
+        Interpreter::new().eval(source, &stmts)
+    }
+
+    /// Asserts that `result` failed with the diagnostic `code` and returns the
+    /// error for further checks.
+    fn failed<T: std::fmt::Debug>(result: Result<T, RuntimeError>, code: &str) -> RuntimeError {
+        let error = result.unwrap_err();
+        assert_eq!(error.code, code, "{error}");
+        error
+    }
+
+    #[test]
+    fn check_expressions() {
+        assert_eq!(eval("(1 + 2) * 3 - 4 / 2;").unwrap(), Value::Float(7.0));
+        assert_eq!(eval("-(1 + 2);").unwrap(), Value::Int(-3));
+        assert_eq!(
+            eval("\"fox\" + \"lang\";").unwrap(),
+            Value::String("foxlang".to_string())
+        );
+        assert_eq!(eval("1 < 2 == !false;").unwrap(), Value::Bool(true));
+        assert_eq!(eval("nil == false;").unwrap(), Value::Bool(false));
+        assert_eq!(eval("!nil;").unwrap(), Value::Bool(true));
+        assert_eq!(
+            eval("let n = 0; let a = n > 0 ? \"pos\" : n < 0 ? \"neg\" : \"zero\"; a;").unwrap(),
+            Value::String("zero".to_string())
+        );
+        assert_eq!(eval("true ? 1 : missing;").unwrap(), Value::Int(1));
+        assert_eq!(eval("nil or 0 or missing;").unwrap(), Value::Int(0));
+        assert_eq!(
+            eval("1 and false and missing;").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            eval("\"fox\" and nil or \"default\";").unwrap(),
+            Value::String("default".to_string())
+        );
+        assert_eq!(
+            eval(r#"let n = 2; "${n} + ${n} = ${n + n}, ${"${nil}!"}";"#).unwrap(),
+            Value::String("2 + 2 = 4, nil!".to_string())
+        );
+    }
+
+    #[test]
+    fn check_numbers() {
+        assert!(matches!(
+            eval("let n = 0; for let i = 0; i < 3; i = i + 1 { n = n + i; } n;"),
+            Ok(Value::Int(3))
+        ));
+        assert!(matches!(eval("7 / 2;"), Ok(Value::Float(n)) if n == 3.5));
+        assert!(matches!(eval("2 * 1.5;"), Ok(Value::Float(n)) if n == 3.0));
+        assert_eq!(eval("1 == 1.0;").unwrap(), Value::Bool(true));
+        assert_eq!(eval("17 % 5 + 2 ** 3 ** 2;").unwrap(), Value::Int(514));
+        assert_eq!(eval("(6 & 3 | 8) ^ 1 << 4 >> 2;").unwrap(), Value::Int(14));
+        assert!(matches!(eval("7.5 % 2;"), Ok(Value::Float(n)) if n == 1.5));
+        failed(eval("1 % 0;"), "runtime::division_by_zero");
+        let error = failed(eval("1.0 | 2;"), "runtime::invalid_operands");
+        assert_eq!(error.value("expected"), Some("ints"));
+        failed(
+            eval("-0x7fff_ffff_ffff_ffff - 2;"),
+            "runtime::integer_overflow",
+        );
+    }
+
+    #[test]
+    fn check_statements() {
+        assert_eq!(eval("let a = 1; a = a + 1; a;").unwrap(), Value::Int(2));
+        assert_eq!(
+            eval("let a = 0; while a < 5 { a = a + 1; } a;").unwrap(),
+            Value::Int(5)
+        );
+        assert_eq!(
+            eval("let sum = 0; for let i = 1; i <= 4; i = i + 1 { sum = sum + i; } sum;").unwrap(),
+            Value::Int(10)
+        );
+        assert_eq!(
+            eval("let a = 10; a -= 1; a *= 2; a /= 3; a %= 4; a += 0.5; a;").unwrap(),
+            Value::Float(2.5)
+        );
+        assert_eq!(
+            eval("let a; if a { a = 1; } else if !a { a = 2; } a;").unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            eval("let sum = 0; for let i = 0; i < 10; i += 1 { if i % 2 == 0 { continue; } if i > 6 { break; } sum += i; } sum;").unwrap(),
+            Value::Int(9)
+        );
+        assert_eq!(
+            eval("let a = 0; while true { a += 1; { if a < 3 { continue; } } break; } a;").unwrap(),
+            Value::Int(3)
+        );
+    }
+
+    #[test]
+    fn check_for_in() {
+        assert_eq!(
+            eval("let s = \"\"; for x in [1, \"a\", nil] { s += \"${x},\"; } s;").unwrap(),
+            Value::String("1,a,nil,".to_string())
+        );
+        assert_eq!(
+            eval("let s = \"\"; for k in {\"b\": 1, \"a\": 2} { s += k; } for c in \"fox\" { s += c; } s;").unwrap(),
+            Value::String("bafox".to_string())
+        );
+        assert_eq!(
+            eval("let sum = 0; for x in [1, 2, 3, 4, 5] { if x == 2 { continue; } if x == 5 { break; } sum += x; } sum;").unwrap(),
+            Value::Int(8)
+        );
+        assert_eq!(
+            eval("let fs = {}; for k in [\"a\", \"b\"] { fn f() { return k; } fs[k] = f; } fs[\"a\"]();").unwrap(),
+            Value::String("a".to_string())
+        );
+        failed(eval("for x in [1] { } x;"), "runtime::undefined_variable");
+        let error = eval("for x in 1 + 2 { } nil;").unwrap_err();
+        assert_eq!(error.code, "runtime::not_iterable");
+        assert_eq!(error.value("found"), Some("int"));
+        assert_eq!(error.span(), Some((9, 5).into()));
+    }
+
+    #[test]
+    fn check_match() {
+        let describe = "fn describe(n) { return match n { 0 => \"zero\", -1 => \"minus one\", x if x > 9 => \"big ${x}\", _ => \"other\" }; }";
+        assert_eq!(
+            eval(&format!(
+                "{describe} describe(0) + describe(-1.0) + describe(10) + describe(5);"
+            ))
+            .unwrap(),
+            Value::String("zerominus onebig 10other".to_string())
+        );
+        assert_eq!(
+            eval("let x = 1; let y = match 2 { x => x * 10 }; x + y;").unwrap(),
+            Value::Int(21)
+        );
+        let error = eval("match \"fox\" { nil => 1, true => 2 };").unwrap_err();
+        assert_eq!(error.code, "runtime::no_match");
+        assert_eq!(error.value("value"), Some("fox"));
+        assert_eq!(error.span(), Some((6, 5).into()));
+        assert_eq!(error.kind(), "match");
+    }
+
+    #[test]
+    fn check_ranges() {
+        assert_eq!(
+            eval("let sum = 0; for i in 1..4 { sum += i; } for i in 10..=10 { sum += i; } for i in 3..1 { sum += 100; } sum;").unwrap(),
+            Value::Int(16)
+        );
+        assert_eq!(
+            eval("let xs = [1, 2, 3, 4]; let n = 2; \"${xs[1..n + 1]} ${xs[0..=0]} ${xs[4..4]} ${0..n}\";").unwrap(),
+            Value::String("[2, 3] [1] [] 0..2".to_string())
+        );
+        assert_eq!(
+            eval("\"héllo\"[1..=3];").unwrap(),
+            Value::String("éll".to_string())
+        );
+        assert_eq!(eval("1..2 == 1..2;").unwrap(), Value::Bool(true));
+        let error = eval("[1, 2][1..3];").unwrap_err();
+        assert_eq!(error.code, "runtime::range_out_of_bounds");
+        assert_eq!(error.value("range"), Some("1..3"));
+        assert_eq!(error.value("len"), Some("2"));
+        let error = failed(eval("1..2.5;"), "runtime::invalid_operands");
+        assert_eq!(error.value("expected"), Some("ints"));
+    }
+
+    #[test]
+    fn check_scopes() {
+        assert_eq!(
+            eval("let a = 1; { let a = 2; a = a + 1; } a;").unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            eval("let a = 1; { let b = 2; a = a + b; } a;").unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            eval("let a = 0; for let i = 0; i < 2; i = i + 1 { let a = i; } a;").unwrap(),
+            Value::Int(0)
+        );
+        failed(eval("{ let b = 1; } b;"), "runtime::undefined_variable");
+        failed(
+            eval("for let i = 0; i < 2; i = i + 1 { } i;"),
+            "runtime::undefined_variable",
+        );
+    }
+
+    #[test]
+    fn check_functions() {
+        assert_eq!(
+            eval("fn add(a, b) { return a + b; } add(1, 2);").unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(eval("fn f() {} f();").unwrap(), Value::Nil);
+        assert_eq!(
+            eval("fn fib(n) { if n < 2 { return n; } return fib(n - 1) + fib(n - 2); } fib(10);")
+                .unwrap(),
+            Value::Int(55)
+        );
+        assert_eq!(
+            eval("fn f() { while true { return 1; } } f();").unwrap(),
+            Value::Int(1)
+        );
+        let sign = "fn sign(n) { guard n != 0 else { return 0; } guard n > 0 else { return -1; } return 1; }";
+        assert_eq!(
+            eval(&format!("{sign} sign(0) + sign(-5) * 10 + sign(3) * 100;")).unwrap(),
+            Value::Int(90)
+        );
+    }
+
+    #[test]
+    fn check_closures() {
+        let counter = "
+            fn make_counter() {
+                let count = 0;
+                fn increment() {
+                    count = count + 1;
+                    return count;
+                }
+                return increment;
+            }
+            let counter = make_counter();
+            counter();
+            counter();
+        ";
+        assert_eq!(eval(counter).unwrap(), Value::Int(2));
+        assert_eq!(
+            eval("let a = 1; fn f() { return a; } { let a = 2; f(); } f();").unwrap(),
+            Value::Int(1)
+        );
+    }
+
+    #[test]
+    fn check_classes() {
+        let point = "
+            class Point {
+                fn init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+
+                fn sum() {
+                    return this.x + this.y;
+                }
+            }
+        ";
+        assert_eq!(
+            eval(&format!("{point} Point(1, 2).sum();")).unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            eval(&format!(
+                "{point} let p = Point(1, 2); p.x = 5; let f = p.sum; f();"
+            ))
+            .unwrap(),
+            Value::Int(7)
+        );
+        assert_eq!(
+            eval(&format!("{point} let p = Point(1, 2); p.y *= 10; p.sum();")).unwrap(),
+            Value::Int(21)
+        );
+        assert_eq!(
+            eval(&format!("{point} let p = Point(1, 2); p.init(3, 4) == p;")).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval("class Fox {} let f = Fox(); f.name = \"fox\"; f.name;").unwrap(),
+            Value::String("fox".to_string())
+        );
+    }
+
+    #[test]
+    fn check_freeze() {
+        let config = "
+            class Config {}
+            let config = Config();
+            config.db = Config();
+            config.db.host = \"localhost\";
+            freeze(config);
+        ";
+        assert_eq!(
+            eval(&format!("{config} config.db.host;")).unwrap(),
+            Value::String("localhost".to_string())
+        );
+        failed(
+            eval(&format!("{config} config.db = nil;")),
+            "runtime::frozen_instance",
+        );
+        failed(
+            eval(&format!("{config} config.db.host = nil;")),
+            "runtime::frozen_instance",
+        );
+        assert_eq!(eval("freeze(1);").unwrap(), Value::Int(1));
+        let error = failed(
+            eval("let xs = [[1]]; freeze(xs); xs[0][0] = 2;"),
+            "runtime::frozen_collection",
+        );
+        assert_eq!(error.value("found"), Some("list"));
+    }
+
+    #[test]
+    fn check_maps() {
+        assert_eq!(
+            eval(
+                "let m = {\"b\": 1, 2: [true], nil: {}}; m[\"b\"] = 3; m[\"a\"] = \"x\"; \"${m}\";"
+            )
+            .unwrap(),
+            Value::String("{\"b\": 3, 2: [true], nil: {}, \"a\": \"x\"}".to_string())
+        );
+        assert_eq!(
+            eval("let m = {\"a\": 1, \"b\": 2}; m[\"a\"] += 10; \"${keys(m)} ${values(m)}\";")
+                .unwrap(),
+            Value::String("[\"a\", \"b\"] [11, 2]".to_string())
+        );
+        assert_eq!(
+            eval("let m = {1: nil}; has(m, 1) and !has(m, \"1\");").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval("let m = {}; m[\"self\"] = m; \"${m}\";").unwrap(),
+            Value::String("{\"self\": {...}}".to_string())
+        );
+
+        let error = failed(
+            eval("let m = {\"a\": 1}; m[\"b\"];"),
+            "runtime::missing_key",
+        );
+        assert_eq!(error.value("key"), Some("\"b\""));
+        let error = failed(eval("let m = {1.5: 1}; m;"), "runtime::invalid_key");
+        assert_eq!(error.value("found"), Some("float"));
+        assert_eq!(error.span().map(|span| span.offset()), Some(9));
+        let error = failed(
+            eval("let m = freeze({\"a\": 1}); m[\"b\"] = 2;"),
+            "runtime::frozen_collection",
+        );
+        assert_eq!(error.value("found"), Some("map"));
+        failed(eval("keys([1]);"), "runtime::native_failed");
+    }
+
+    #[test]
+    fn check_lists() {
+        assert_eq!(
+            eval("let xs = [1, \"fox\", [nil],]; \"${xs} ${[]}\";").unwrap(),
+            Value::String("[1, \"fox\", [nil]] []".to_string())
+        );
+        assert_eq!(
+            eval("let xs = [1, 2, 3]; xs[0] = xs[1] + xs[2]; xs[1] += 10; \"${xs}\";").unwrap(),
+            Value::String("[5, 12, 3]".to_string())
+        );
+        assert_eq!(
+            eval("let xs = [[1, 2], [3]]; let ys = xs[0]; ys[1] = 4; xs[0][1];").unwrap(),
+            Value::Int(4)
+        );
+        assert_eq!(
+            eval("let xs = [1]; xs[0] = xs; \"${xs}\";").unwrap(),
+            Value::String("[[...]]".to_string())
+        );
+        assert_eq!(eval("[1] == [1];").unwrap(), Value::Bool(false));
+
+        let error = eval("let xs = [1, 2]; let i = 1; xs[i + 1];").unwrap_err();
+        assert_eq!(error.code, "runtime::index_out_of_bounds");
+        assert_eq!(error.value("index"), Some("2"));
+        assert_eq!(error.value("len"), Some("2"));
+        assert_eq!(error.span(), Some((31, 5).into()));
+        let error = failed(eval("[1][-1];"), "runtime::index_out_of_bounds");
+        assert_eq!(error.value("index"), Some("-1"));
+        let error = failed(eval("[1][0.0];"), "runtime::invalid_index");
+        assert_eq!(error.value("found"), Some("float"));
+        let error = failed(eval("let s = \"fox\"; s[0] = 1;"), "runtime::not_indexable");
+        assert_eq!(error.value("found"), Some("string"));
+    }
+
+    #[test]
+    fn check_primitive_methods() {
+        assert_eq!(
+            eval("let s = \"Fox, Lang\"; \"${s.upper()} ${s.len()} ${s.slice(5, 9)}\";").unwrap(),
+            Value::String("FOX, LANG 9 Lang".to_string())
+        );
+        assert_eq!(
+            eval("\"${\"a,b,,c\".split(\",\")} ${\"ab\".split(\"\")}\";").unwrap(),
+            Value::String("[\"a\", \"b\", \"\", \"c\"] [\"a\", \"b\"]".to_string())
+        );
+        assert_eq!(
+            eval("let contains = \"fox\".contains; contains(\"o\");").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval("let std = nil; [1, 2].join(\"-\");").unwrap(),
+            Value::String("1-2".to_string())
+        );
+
+        let error = failed(eval("\"fox\".reverse();"), "runtime::undefined_method");
+        assert_eq!(error.value("name"), Some("reverse"));
+        assert_eq!(error.value("found"), Some("string"));
+        failed(eval("\"fox\".slice(2, 5);"), "runtime::native_failed");
+        failed(eval("\"fox\".upper(1);"), "runtime::arity_mismatch");
+        let error = failed(eval("(1).len();"), "runtime::not_an_instance");
+        assert_eq!(error.value("found"), Some("int"));
+    }
+
+    #[test]
+    fn check_list_methods() {
+        assert_eq!(
+            eval(
+                "let xs = [3, 1, 2]; xs.push(4); let last = xs.pop(); xs.push(5);
+                fn double(x) { return x * 2; }
+                fn odd(x) { return x % 2 == 1; }
+                fn add(sum, x) { return sum + x; }
+                \"${xs} ${last} ${xs.map(double)} ${xs.filter(odd)} ${xs.reduce(add, 0)}\";"
+            )
+            .unwrap(),
+            Value::String("[3, 1, 2, 5] 4 [6, 2, 4, 10] [3, 1, 5] 11".to_string())
+        );
+        assert_eq!(
+            eval(
+                "let xs = [3, 1.5, -2]; xs.sort(); let words = [\"b\", \"a\"]; words.sort();
+                let calls = 0;
+                fn longest_first(a, b) { calls += 1; return std.string.len(b) - std.string.len(a); }
+                let ys = [\"ab\", \"c\", \"def\", \"gh\"]; ys.sort(longest_first);
+                \"${xs} ${words} ${ys} ${calls > 0}\";"
+            )
+            .unwrap(),
+            Value::String(
+                "[-2, 1.5, 3] [\"a\", \"b\"] [\"def\", \"ab\", \"gh\", \"c\"] true".to_string()
+            )
+        );
+        assert_eq!(
+            eval(
+                "fn fail(a, b) { throw Error(\"compare\", \"no order\", nil); }
+                let kind; try { [2, 1].sort(fail); } catch e { kind = e.kind; } kind;"
+            )
+            .unwrap(),
+            Value::String("compare".to_string())
+        );
+
+        let error = failed(eval("[1, \"a\"].sort();"), "runtime::native_failed");
+        assert!(error.value("message").unwrap().starts_with("can't compare"));
+        failed(
+            eval("fn f(a) { return 0; } [2, 1].sort(f);"),
+            "runtime::native_failed",
+        );
+        failed(eval("[].pop();"), "runtime::native_failed");
+        failed(eval("freeze([1]).push(2);"), "runtime::native_failed");
+        let error = failed(eval("[1].sort(nil, nil);"), "runtime::arity_mismatch");
+        assert_eq!(error.value("expected"), Some("1"));
+        assert_eq!(error.value("found"), Some("2"));
+    }
+
+    #[test]
+    fn check_diff() {
+        let point = "class Point { fn init(x, y) { this.x = x; this.y = y; } }";
+        assert_eq!(
+            eval(&format!("{point} diff(Point(1, 2), Point(1, 3));")).unwrap(),
+            Value::String(".y: 2 -> 3".to_string())
+        );
+        assert_eq!(
+            eval(&format!("{point} diff(Point(1, 2), Point(1, 2));")).unwrap(),
+            Value::Nil
+        );
+    }
+
+    #[test]
+    fn check_inheritance() {
+        let source = "
+            class Animal {
+                fn init(name) { this.name = name; }
+                fn speak() { return this.name + \" makes a sound\"; }
+                fn kind() { return \"animal\"; }
+            }
+            class Fox < Animal {
+                fn speak() { return super.speak() + \" and it's a \" + this.kind(); }
+                fn kind() { return \"fox\"; }
+            }
+            Fox(\"Foxy\").speak();
+        ";
+        assert_eq!(
+            eval(source).unwrap(),
+            Value::String("Foxy makes a sound and it's a fox".to_string())
+        );
+    }
+
+    #[test]
+    fn check_exceptions() {
+        assert_eq!(
+            eval("let a; try { throw Error(\"io\", \"gone\", 404); a = 1; } catch e { a = e.kind + e.message + \"?\"; } a;").unwrap(),
+            Value::String("iogone?".to_string())
+        );
+        assert_eq!(
+            eval("let a; try { throw Error(\"io\", \"gone\", 404); } catch e { a = e.data; } a;")
+                .unwrap(),
+            Value::Int(404)
+        );
+        assert_eq!(
+            eval("class NotFound < Error {} let a; try { throw NotFound(\"nf\", nil, nil); } catch e { a = e.kind; } a;").unwrap(),
+            Value::String("nf".to_string())
+        );
+        assert_eq!(
+            eval("let a; try { a = 1 + nil; } catch e { a = e.kind; } a;").unwrap(),
+            Value::String("type".to_string())
+        );
+        assert_eq!(
+            eval("let a = 0; try { try { throw Error(1, 2, 3); } catch e { a = a + 1; throw e; } } catch e { a = a + e.data; } a;").unwrap(),
+            Value::Int(4)
+        );
+        assert_eq!(
+            eval("fn f() { try { return 1; } catch e { return 2; } } f();").unwrap(),
+            Value::Int(1)
+        );
+        let error = failed(
+            eval("throw Error(\"io\", \"gone\", nil); nil;"),
+            "runtime::thrown",
+        );
+        assert_eq!(error.value("kind"), Some("io"));
+        assert_eq!(error.value("message"), Some("gone"));
+        failed(eval("throw \"gone\"; nil;"), "runtime::invalid_throw");
+    }
+
+    #[test]
+    fn check_error_propagation() {
+        let source = "
+            fn parse(s) {
+                if s == \"\" {
+                    return Error(\"parse\", \"empty input\", s);
+                }
+                return s + \"!\";
+            }
+            fn load(s) {
+                let parsed = try parse(s);
+                return \"loaded \" + parsed;
+            }
+        ";
+        assert_eq!(
+            eval(&format!("{source} load(\"a\");")).unwrap(),
+            Value::String("loaded a!".to_string())
+        );
+        assert_eq!(
+            eval(&format!("{source} load(\"\").kind;")).unwrap(),
+            Value::String("parse".to_string())
+        );
+        // Propagated errors are values, not caught unless thrown.
+        assert_eq!(
+            eval(&format!(
+                "{source} let a = 1; try {{ load(\"\"); }} catch e {{ a = 2; }} a;"
+            ))
+            .unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            eval(&format!(
+                "{source} let a; try {{ throw load(\"\"); }} catch e {{ a = e.message; }} a;"
+            ))
+            .unwrap(),
+            Value::String("empty input".to_string())
+        );
+    }
+
+    #[test]
+    fn check_stack_traces() {
+        let source = "
+            fn inner() {
+                throw Error(\"io\", \"gone\", nil);
+            }
+            fn outer() {
+                inner();
+            }
+            fn undefined() {
+                return missing;
+            }
+            let thrown;
+            let failed;
+            try { outer(); } catch e { thrown = e.stack; }
+            try { undefined(); } catch e { failed = e.stack; }
+        ";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source, &stmts).unwrap();
+
+        assert_eq!(
+            interpreter.global("thrown"),
+            Some(Value::from(
+                "at inner, called from line 6\nat outer, called from line 13"
+            ))
+        );
+        assert_eq!(
+            interpreter.global("failed"),
+            Some(Value::from("at undefined, called from line 14"))
+        );
+    }
+
+    #[test]
+    fn check_native_errors() {
+        let source = "
+            let status;
+            try { fetch(\"/missing\"); } catch e { status = e.data; }
+            let kind;
+            try { fetch(1); } catch e { kind = e.kind; }
+        ";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("fetch", 1, |args| {
+            let path = String::try_from(&args[0])?;
+            Err::<Value, _>(NativeError::new(
+                "http",
+                &format!("{path} not found"),
+                404.0,
+            ))
+        });
+        interpreter.interpret(source, &stmts).unwrap();
+
+        assert_eq!(interpreter.global("status"), Some(Value::Float(404.0)));
+        assert_eq!(interpreter.global("kind"), Some(Value::from("native")));
+    }
+
+    #[test]
+    fn check_natives() {
+        let source = "
+            let a = hypot(3, 4);
+            let b = greet(\"fox\");
+            let c = greet(nil);
+        ";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("hypot", 2, |args| {
+            let (x, y) = (f64::try_from(&args[0])?, f64::try_from(&args[1])?);
+            Ok(x.hypot(y))
+        });
+        interpreter.define_native("greet", 1, |args| match &args[0] {
+            Value::Nil => Ok(None),
+            name => Ok(Some(format!("hi {}", String::try_from(name)?))),
+        });
+        interpreter.interpret(source, &stmts).unwrap();
+
+        assert_eq!(interpreter.global("a"), Some(Value::Float(5.0)));
+        assert_eq!(interpreter.global("b"), Some(Value::from("hi fox")));
+        assert_eq!(interpreter.global("c"), Some(Value::Nil));
+        assert_eq!(
+            interpreter
+                .call("hypot", &[6.0.into(), 8.0.into()])
+                .unwrap(),
+            Value::Float(10.0)
+        );
+
+        let source = "hypot(1, true);";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        let error = failed(
+            interpreter.interpret(source, &stmts),
+            "runtime::native_failed",
+        );
+        assert_eq!(
+            error.value("message"),
+            Some("expected a number, found bool")
+        );
+    }
+
+    #[test]
+    fn check_host_calls() {
+        let source = "
+            let calls = 0;
+            fn on_event(name) {
+                calls = calls + 1;
+                return name + \" handled\";
+            }
+            class Point { fn init(x) { this.x = x; } }
+            let limit = 10;
+        ";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source, &stmts).unwrap();
+
+        let event = Value::String("click".to_string());
+        assert_eq!(
+            interpreter.call("on_event", &[event.clone()]).unwrap(),
+            Value::String("click handled".to_string())
+        );
+        interpreter.call("on_event", &[event]).unwrap();
+        assert_eq!(
+            interpreter.globals.borrow().get("calls"),
+            Some(Value::Int(2))
+        );
+        assert!(matches!(
+            interpreter.call("Point", &[Value::Float(1.0)]),
+            Ok(Value::Instance(_))
+        ));
+
+        failed(interpreter.call("missing", &[]), "runtime::invalid_call");
+        failed(interpreter.call("limit", &[]), "runtime::invalid_call");
+        failed(interpreter.call("on_event", &[]), "runtime::invalid_call");
+    }
+
+    #[test]
+    fn check_scoped_calls() {
+        let source = "
+            let user = \"nobody\";
+            let handled = 0;
+            fn handle() {
+                handled = handled + 1;
+                user = user + \"!\";
+                return user;
+            }
+        ";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source, &stmts).unwrap();
+
+        let scope = HashMap::from([("user".to_string(), Value::String("fox".to_string()))]);
+        assert_eq!(
+            interpreter.call_with_scope("handle", &[], scope).unwrap(),
+            Value::String("fox!".to_string())
+        );
+        assert_eq!(
+            interpreter.global("user"),
+            Some(Value::String("nobody".to_string()))
+        );
+        assert_eq!(interpreter.global("handled"), Some(Value::Int(1)));
+        assert_eq!(
+            interpreter.call("handle", &[]).unwrap(),
+            Value::String("nobody!".to_string())
+        );
+    }
+
+    #[test]
+    fn check_garbage_collection() {
+        let source = "
+            class Node {
+                fn init() { this.next = this; }
+                fn get() { return 1; }
+            }
+            fn make() {
+                fn recurse() { return recurse; }
+                let node = Node();
+                node.method = node.get;
+                let xs = [node];
+                xs[0] = xs;
+                return recurse;
+            }
+            make();
+            let kept = make();
+        ";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source, &stmts).unwrap();
+
+        assert!(interpreter.collect_garbage() > 0);
+        assert_eq!(interpreter.collect_garbage(), 0);
+        let kept = interpreter.global("kept").unwrap();
+        let callback = interpreter.take_callback(kept.clone()).unwrap();
+        assert_eq!(interpreter.call_callback(&callback, &[]).unwrap(), kept);
+    }
+
+    #[test]
+    fn check_callbacks() {
+        let source = "
+            let clicks = 0;
+            fn subscribe() {
+                fn on_click(times) {
+                    clicks = clicks + times;
+                    return clicks;
+                }
+                return on_click;
+            }
+        ";
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(source, &stmts).unwrap();
+
+        let handler = interpreter.call("subscribe", &[]).unwrap();
+        let callback = interpreter.take_callback(handler).unwrap();
+        interpreter
+            .call_callback(&callback, &[Value::Float(2.0)])
+            .unwrap();
+        assert_eq!(
+            interpreter
+                .call_callback(&callback, &[Value::Float(3.0)])
+                .unwrap(),
+            Value::Float(5.0)
+        );
+        assert_eq!(interpreter.global("clicks"), Some(Value::Float(5.0)));
+
+        failed(
+            interpreter.call_callback(&callback, &[]),
+            "runtime::invalid_call",
+        );
+        failed(
+            interpreter.take_callback(Value::Nil),
+            "runtime::invalid_call",
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        failed(eval("-\"fox\";"), "runtime::invalid_operand");
+        failed(eval("1 + \"fox\";"), "runtime::invalid_operands");
+        failed(eval("a;"), "runtime::undefined_variable");
+        failed(eval("a = 1; nil;"), "runtime::undefined_variable");
+        failed(eval("\"fox\"();"), "runtime::not_callable");
+        failed(eval("fn f(a) {} f(1, 2);"), "runtime::arity_mismatch");
+        failed(
+            eval("class A { fn init(a) {} } A();"),
+            "runtime::arity_mismatch",
+        );
+        failed(eval("let a = 1; a.b;"), "runtime::not_an_instance");
+        failed(eval("class A {} A().b;"), "runtime::undefined_property");
+        failed(
+            eval("let A = 1; class B < A {} nil;"),
+            "runtime::invalid_superclass",
+        );
+        // Debug builds need a larger stack than the default one of tests to
+        // nest calls up to the limit.
+        let overflow = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(|| eval("fn f() { f(); } f();").map(drop))
+            .unwrap()
+            .join()
+            .unwrap();
+        let error = failed(overflow, "runtime::stack_overflow");
+        assert_eq!(error.value("limit"), Some("1024"));
+        assert_eq!(error.span().map(|span| span.offset()), Some(11));
+    }
+}
@@ -0,0 +1,55 @@
+use std::{ops::RangeInclusive, rc::Rc, vec};
+
+use crate::{interpreter::Value, list::List};
+
+/// Steps through the values a `for-in` loop runs its body with.
+pub(crate) enum Iter {
+    /// The elements of a list, read as the loop goes so that elements
+    /// appended by the body are visited too.
+    List { list: Rc<List>, next: usize },
+    /// Values collected when the loop started, like the keys of a map.
+    Values(vec::IntoIter<Value>),
+    /// The ints of a range.
+    Range(RangeInclusive<i64>),
+}
+
+impl Iter {
+    /// Iterates over the elements of a list, the keys of a map, in the order
+    /// they were inserted, the characters of a string or the ints of a range.
+    /// Fails with the type of `value` if it isn't iterable.
+    pub(crate) fn new(value: &Value) -> Result<Iter, &'static str> {
+        match value {
+            Value::List(list) => Ok(Iter::List {
+                list: Rc::clone(list),
+                next: 0,
+            }),
+            Value::Map(map) => {
+                let entries = map.entries.borrow();
+                let keys = entries.iter().map(|(key, _)| key.clone().into());
+                Ok(Iter::Values(keys.collect::<Vec<_>>().into_iter()))
+            }
+            Value::String(string) => {
+                let chars = string.chars().map(|ch| Value::String(ch.to_string()));
+                Ok(Iter::Values(chars.collect::<Vec<_>>().into_iter()))
+            }
+            Value::Range(range) => Ok(Iter::Range(range.ints())),
+            value => Err(value.type_name()),
+        }
+    }
+}
+
+impl Iterator for Iter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            Iter::List { list, next } => {
+                let value = list.elements.borrow().get(*next).cloned()?;
+                *next += 1;
+                Some(value)
+            }
+            Iter::Values(values) => values.next(),
+            Iter::Range(ints) => ints.next().map(Value::Int),
+        }
+    }
+}
@@ -1,13 +1,20 @@
 use itertools::{Itertools, MultiPeek};
-use miette::NamedSource;
+use miette::SourceSpan;
+use serde::Serialize;
 use std::{
+    collections::VecDeque,
     fmt,
-    str::{Chars, FromStr},
+    io::{self, Read},
+    str::{self, Chars, FromStr},
 };
 
-use crate::errors::SyntaxError;
+use crate::{
+    diag::Diag,
+    errors::{FoxError, SyntaxError},
+    source::SourceFile,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Token {
     pub ty: TokenType,
     pub position: Position,
@@ -20,13 +27,30 @@ impl Token {
             TokenType::RightParen => ")".to_string(),
             TokenType::LeftBrace => "{".to_string(),
             TokenType::RightBrace => "}".to_string(),
+            TokenType::LeftBracket => "[".to_string(),
+            TokenType::RightBracket => "]".to_string(),
             TokenType::Comma => ",".to_string(),
             TokenType::Semicolon => ";".to_string(),
+            TokenType::Question => "?".to_string(),
+            TokenType::Colon => ":".to_string(),
             TokenType::Dot => ".".to_string(),
+            TokenType::DotDot => "..".to_string(),
+            TokenType::DotDotEq => "..=".to_string(),
+            TokenType::FatArrow => "=>".to_string(),
             TokenType::Minus => "-".to_string(),
             TokenType::Plus => "+".to_string(),
             TokenType::Slash => "/".to_string(),
             TokenType::Star => "*".to_string(),
+            TokenType::Percent => "%".to_string(),
+            TokenType::Ampersand => "&".to_string(),
+            TokenType::Pipe => "|".to_string(),
+            TokenType::Caret => "^".to_string(),
+            TokenType::StarStar => "**".to_string(),
+            TokenType::PlusEq => "+=".to_string(),
+            TokenType::MinusEq => "-=".to_string(),
+            TokenType::StarEq => "*=".to_string(),
+            TokenType::SlashEq => "/=".to_string(),
+            TokenType::PercentEq => "%=".to_string(),
             TokenType::Bang => "!".to_string(),
             TokenType::BangEq => "!=".to_string(),
             TokenType::Equal => "=".to_string(),
@@ -35,8 +59,12 @@ impl Token {
             TokenType::GreaterEq => ">=".to_string(),
             TokenType::Less => "<".to_string(),
             TokenType::LessEq => "<=".to_string(),
+            TokenType::GreaterGreater => ">>".to_string(),
+            TokenType::LessLess => "<<".to_string(),
             TokenType::Identifier(ident) => ident.to_string(),
             TokenType::String(lit) => lit.to_string(),
+            TokenType::Interpolation(lit) | TokenType::InterpolationEnd(lit) => lit.to_string(),
+            TokenType::Int(int) => int.to_string(),
             TokenType::Number(num) => num.to_string(),
             TokenType::Keyword(kw) => kw.lexeme().to_owned(),
             TokenType::Comment => "<comment>".to_string(),
@@ -45,38 +73,88 @@ impl Token {
     }
 }
 
-#[derive(Debug)]
+/// Where a token is in the source. `start` and `end` are byte offsets, so
+/// they can be used to slice the source and as diagnostic spans, while
+/// `line` and `column` count from 1 and `column` counts characters.
+#[derive(Debug, Clone, Serialize)]
 pub struct Position {
     pub line: usize,
+    pub column: usize,
     pub start: usize,
     pub end: usize,
 }
 
-#[derive(Debug, PartialEq)]
+impl Position {
+    pub fn span(&self) -> SourceSpan {
+        (self.start, self.end - self.start).into()
+    }
+
+    /// The position from the start of the first of the two positions to the
+    /// end of the last one.
+    pub fn join(&self, other: &Position) -> Position {
+        let first = if self.start <= other.start {
+            self
+        } else {
+            other
+        };
+        Position {
+            end: self.end.max(other.end),
+            ..first.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum TokenType {
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Semicolon,
+    Question,
+    Colon,
     Dot,
+    DotDot,
+    DotDotEq,
     Minus,
     Plus,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
 
+    StarStar,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    PercentEq,
     Bang,
     BangEq,
     Equal,
     EqualEq,
+    FatArrow,
     Greater,
     GreaterEq,
     Less,
     LessEq,
+    GreaterGreater,
+    LessLess,
 
     Identifier(String),
     String(String),
+    /// The part of an interpolated string before a `${`, either from its
+    /// opening `"` or from the `}` of the previous interpolation.
+    Interpolation(String),
+    /// The part of an interpolated string from the `}` of its last
+    /// interpolation to its closing `"`.
+    InterpolationEnd(String),
+    Int(i64),
     Number(f64),
 
     Keyword(Keyword),
@@ -84,7 +162,7 @@ pub enum TokenType {
     Eof,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Keyword {
     Let,
     Fn,
@@ -99,9 +177,19 @@ pub enum Keyword {
     True,
     False,
     For,
+    In,
+    Match,
+    Import,
+    Export,
     While,
+    Guard,
     Nil,
     Print,
+    Throw,
+    Try,
+    Catch,
+    Break,
+    Continue,
 }
 
 impl Keyword {
@@ -120,9 +208,19 @@ impl Keyword {
             Keyword::True => "true",
             Keyword::False => "false",
             Keyword::For => "for",
+            Keyword::In => "in",
+            Keyword::Match => "match",
+            Keyword::Import => "import",
+            Keyword::Export => "export",
             Keyword::While => "while",
+            Keyword::Guard => "guard",
             Keyword::Nil => "nil",
             Keyword::Print => "print",
+            Keyword::Throw => "throw",
+            Keyword::Try => "try",
+            Keyword::Catch => "catch",
+            Keyword::Break => "break",
+            Keyword::Continue => "continue",
         }
     }
 }
@@ -145,49 +243,126 @@ impl FromStr for Keyword {
             "true" => Ok(Keyword::True),
             "false" => Ok(Keyword::False),
             "for" => Ok(Keyword::For),
+            "in" => Ok(Keyword::In),
+            "match" => Ok(Keyword::Match),
+            "import" => Ok(Keyword::Import),
+            "export" => Ok(Keyword::Export),
             "while" => Ok(Keyword::While),
+            "guard" => Ok(Keyword::Guard),
             "nil" => Ok(Keyword::Nil),
             "print" => Ok(Keyword::Print),
+            "throw" => Ok(Keyword::Throw),
+            "try" => Ok(Keyword::Try),
+            "catch" => Ok(Keyword::Catch),
+            "break" => Ok(Keyword::Break),
+            "continue" => Ok(Keyword::Continue),
             _ => Err(fmt::Error),
         }
     }
 }
 
 pub struct Lexer<'a> {
-    source: &'a str,
+    source: &'a SourceFile,
     iter: MultiPeek<Chars<'a>>,
+    /// Byte offset of the next character.
     current: usize,
     line: usize,
+    column: usize,
+    /// The number of `{` left to close in each `${...}` being scanned, the
+    /// innermost last.
+    interpolations: Vec<usize>,
     at_eof: bool,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(source: &'a str) -> Self {
+    pub fn new(source: &'a SourceFile) -> Self {
         Lexer {
             source,
-            iter: source.chars().multipeek(),
+            iter: source.text().chars().multipeek(),
             current: 0,
             line: 1,
+            column: 1,
+            interpolations: vec![],
             at_eof: false,
         }
     }
 
+    /// Scans the whole source, collecting every valid token and every error
+    /// instead of stopping at the first one. Comments are skipped.
+    pub fn scan_all(self) -> (Vec<Token>, Vec<SyntaxError>) {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        for item in self {
+            match item {
+                Ok(t) => tokens.push(t),
+                Err(e) => errors.push(e),
+            }
+        }
+        (tokens, errors)
+    }
+
     pub fn scan_token(&mut self) -> Option<Result<Token, SyntaxError>> {
         self.advance_while(|ch| ch.is_whitespace());
         let start = self.current;
+        let (line, column) = (self.line, self.column);
         let ch = self.advance();
         let token = ch.map(|ch| {
             let ty = match ch {
                 '(' => TokenType::LeftParen,
                 ')' => TokenType::RightParen,
-                '{' => TokenType::LeftBrace,
-                '}' => TokenType::RightBrace,
+                '[' => TokenType::LeftBracket,
+                ']' => TokenType::RightBracket,
+                '{' => {
+                    if let Some(braces) = self.interpolations.last_mut() {
+                        *braces += 1;
+                    }
+                    TokenType::LeftBrace
+                }
+                '}' => match self.interpolations.last_mut() {
+                    Some(0) => {
+                        self.interpolations.pop();
+                        match self.string(start) {
+                            Ok(TokenType::String(string)) => TokenType::InterpolationEnd(string),
+                            Ok(ty) => ty,
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    Some(braces) => {
+                        *braces -= 1;
+                        TokenType::RightBrace
+                    }
+                    None => TokenType::RightBrace,
+                },
                 ',' => TokenType::Comma,
                 ';' => TokenType::Semicolon,
+                '?' => TokenType::Question,
+                ':' => TokenType::Colon,
+                '.' if self.advance_if('.') => {
+                    if self.advance_if('=') {
+                        TokenType::DotDotEq
+                    } else {
+                        TokenType::DotDot
+                    }
+                }
                 '.' => TokenType::Dot,
+                '-' if self.advance_if('=') => TokenType::MinusEq,
                 '-' => TokenType::Minus,
+                '+' if self.advance_if('=') => TokenType::PlusEq,
                 '+' => TokenType::Plus,
-                '*' => TokenType::Star,
+                '*' => {
+                    if self.advance_if('*') {
+                        TokenType::StarStar
+                    } else if self.advance_if('=') {
+                        TokenType::StarEq
+                    } else {
+                        TokenType::Star
+                    }
+                }
+                '%' if self.advance_if('=') => TokenType::PercentEq,
+                '%' => TokenType::Percent,
+                '&' => TokenType::Ampersand,
+                '|' => TokenType::Pipe,
+                '^' => TokenType::Caret,
                 '/' => {
                     let next = self.iter.peek();
                     if let Some('/') = next {
@@ -202,6 +377,9 @@ impl<'a> Lexer<'a> {
                             Ok(ty) => ty,
                             Err(err) => return Err(err),
                         }
+                    } else if let Some('=') = next {
+                        self.advance();
+                        TokenType::SlashEq
                     } else {
                         TokenType::Slash
                     }
@@ -215,25 +393,28 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 '=' => {
-                    if let Some('=') = self.iter.peek() {
-                        self.advance();
+                    if self.advance_if('=') {
                         TokenType::EqualEq
+                    } else if self.advance_if('>') {
+                        TokenType::FatArrow
                     } else {
                         TokenType::Equal
                     }
                 }
                 '>' => {
-                    if let Some('=') = self.iter.peek() {
-                        self.advance();
+                    if self.advance_if('=') {
                         TokenType::GreaterEq
+                    } else if self.advance_if('>') {
+                        TokenType::GreaterGreater
                     } else {
                         TokenType::Greater
                     }
                 }
                 '<' => {
-                    if let Some('=') = self.iter.peek() {
-                        self.advance();
+                    if self.advance_if('=') {
                         TokenType::LessEq
+                    } else if self.advance_if('<') {
+                        TokenType::LessLess
                     } else {
                         TokenType::Less
                     }
@@ -242,22 +423,31 @@ impl<'a> Lexer<'a> {
                     Ok(ty) => ty,
                     Err(err) => return Err(err),
                 },
-                ch if ch.is_numeric() => self.number(start),
-                ch if ch.is_alphabetic() => self.identifier(start),
+                'r' if self.advance_if('"') => match self.raw_string(start) {
+                    Ok(ty) => ty,
+                    Err(err) => return Err(err),
+                },
+                ch if ch.is_ascii_digit() => match self.number(ch, start) {
+                    Ok(ty) => ty,
+                    Err(err) => return Err(err),
+                },
+                ch if ch.is_alphabetic() || ch == '_' => self.identifier(start),
                 ch => {
-                    return Err(SyntaxError::UnexpectedCharacter {
-                        src: NamedSource::new("", self.source.to_string()),
-                        span: (start, 1).into(),
-                        char: ch,
-                    })
+                    return Err(Diag::error("syntax::unexpected_character")
+                        .arg("char", ch)
+                        .msg("Unexpected character `{char}` found")
+                        .source(self.source)
+                        .primary((start, ch.len_utf8()).into(), "this one right here")
+                        .into())
                 }
             };
 
             self.iter.reset_peek();
             let position = Position {
+                line,
+                column,
                 start,
                 end: self.current,
-                line: self.line,
             };
             Ok(Token { ty, position })
         });
@@ -270,6 +460,7 @@ impl<'a> Lexer<'a> {
                     ty: TokenType::Eof,
                     position: Position {
                         line: self.line,
+                        column: self.column,
                         start: self.current,
                         end: self.current,
                     },
@@ -280,64 +471,200 @@ impl<'a> Lexer<'a> {
     }
 
     fn advance(&mut self) -> Option<char> {
-        self.iter.next().map(|ch| {
-            self.current += 1;
+        self.iter.next().inspect(|&ch| {
+            self.current += ch.len_utf8();
             if '\n' == ch {
                 self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
             }
-            ch
         })
     }
 
-    fn advance_while<F>(&mut self, predicate: F) -> usize
+    fn advance_if(&mut self, expected: char) -> bool {
+        let matches = self.iter.peek() == Some(&expected);
+        self.iter.reset_peek();
+        if matches {
+            self.advance();
+        }
+        matches
+    }
+
+    fn advance_while<F>(&mut self, predicate: F)
     where
         F: Fn(&char) -> bool,
     {
-        let mut count = 0usize;
         while let Some(ch) = self.iter.peek() {
             if !predicate(ch) {
                 break;
             }
-            count += 1;
             self.advance();
         }
         self.iter.reset_peek();
-        count
     }
 
+    /// Scans a string literal up to its closing quote, or up to the `${` of
+    /// an interpolation, even if an escape in it is invalid, so that lexing
+    /// resumes after the string.
     fn string(&mut self, start: usize) -> Result<TokenType, SyntaxError> {
-        let len = self.advance_while(|ch| ch != &'"');
-        if self.advance().is_none() {
-            return Err(SyntaxError::UnterminatedString {
-                src: NamedSource::new("", self.source.to_string()),
-                leading_quote: (start, 1).into(),
-            });
+        let mut literal = String::new();
+        let mut invalid_escape = None;
+        let ty = loop {
+            match self.advance() {
+                Some('"') => break TokenType::String(literal),
+                Some('$') if self.advance_if('{') => {
+                    self.interpolations.push(0);
+                    break TokenType::Interpolation(literal);
+                }
+                Some('\\') => {
+                    let escape_start = self.current - 1;
+                    match self.escape() {
+                        Some(ch) => literal.push(ch),
+                        None => {
+                            let len = self.current - escape_start;
+                            invalid_escape.get_or_insert((escape_start, len));
+                        }
+                    }
+                }
+                Some(ch) => literal.push(ch),
+                None => return Err(self.unterminated_string((start, 1).into())),
+            }
+        };
+        match invalid_escape {
+            Some(span) => Err(Diag::error("syntax::invalid_escape_sequence")
+                .msg("Invalid escape sequence in string")
+                .source(self.source)
+                .primary(span.into(), "this escape")
+                .help("valid escapes are `\\n`, `\\t`, `\\r`, `\\\"`, `\\\\`, `\\$`, `\\0` and `\\u{{XXXX}}`")
+                .into()),
+            None => Ok(ty),
         }
-        let start = start + 1;
-        let end = start + len;
-        Ok(TokenType::String(self.source[start..end].to_string()))
-    }
-
-    fn number(&mut self, start: usize) -> TokenType {
-        let mut len = self.advance_while(|ch| ch.is_numeric());
-        if let Some(&'.') = self.iter.peek() {
-            let is_frac = self.iter.peek().map_or(false, |ch| ch.is_numeric());
-            if is_frac {
-                self.advance();
-                len += 1;
-                len += self.advance_while(|ch| ch.is_numeric());
+    }
+
+    /// Scans the rest of an escape sequence after its `\\`, returning the
+    /// character it stands for, or `None` if it is invalid. A malformed
+    /// `\\u{...}` never consumes the closing quote of the string.
+    fn escape(&mut self) -> Option<char> {
+        match self.advance()? {
+            '"' => Some('"'),
+            '$' => Some('$'),
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '\\' => Some('\\'),
+            '0' => Some('\0'),
+            'u' => {
+                if !self.advance_if('{') {
+                    return None;
+                }
+                let mut digits = String::new();
+                while let Some(&ch) = self.iter.peek() {
+                    if !ch.is_ascii_hexdigit() || digits.len() == 6 {
+                        break;
+                    }
+                    digits.push(ch);
+                    self.advance();
+                }
+                self.iter.reset_peek();
+                if digits.is_empty() || !self.advance_if('}') {
+                    return None;
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
             }
+            _ => None,
         }
+    }
+
+    /// Scans a raw string, `r"..."`, after its opening quote. Its contents
+    /// are kept as written, without escapes or interpolations.
+    fn raw_string(&mut self, start: usize) -> Result<TokenType, SyntaxError> {
+        self.advance_while(|ch| ch != &'"');
+        if self.advance().is_none() {
+            return Err(self.unterminated_string((start, 2).into()));
+        }
+        // Without the `r"` and the closing quote.
+        let literal = &self.source.text()[start + 2..self.current - 1];
+        Ok(TokenType::String(literal.to_string()))
+    }
+
+    /// Scans a number starting with the digit `first`: an int, like `1_000`
+    /// or in hexadecimal, octal or binary, like `0xFF`, or a float, which
+    /// has a fractional part or an exponent, like `1.5e-3`. Ints must fit in
+    /// an `i64`. Underscores can separate digits. Letters right after a
+    /// number are scanned along with it, so that `12ab` is one malformed
+    /// number.
+    fn number(&mut self, first: char, start: usize) -> Result<TokenType, SyntaxError> {
+        let radix = match (first, self.iter.peek()) {
+            ('0', Some('x' | 'X')) => Some(16),
+            ('0', Some('o' | 'O')) => Some(8),
+            ('0', Some('b' | 'B')) => Some(2),
+            _ => None,
+        };
         self.iter.reset_peek();
-        let end = start + len;
-        let literal = &self.source[start..=end];
-        TokenType::Number(literal.parse::<f64>().unwrap())
+
+        let number = if let Some(radix) = radix {
+            self.advance();
+            let digits_start = self.current;
+            self.advance_while(|ch| ch.is_ascii_alphanumeric() || ch == &'_');
+            let digits = &self.source.text()[digits_start..self.current];
+            if is_separated(digits, |ch| ch.is_digit(radix)) {
+                i64::from_str_radix(&digits.replace('_', ""), radix)
+                    .ok()
+                    .map(TokenType::Int)
+            } else {
+                None
+            }
+        } else {
+            self.advance_while(|ch| ch.is_ascii_digit() || ch == &'_');
+            if let Some(&'.') = self.iter.peek() {
+                let is_frac = self.iter.peek().is_some_and(|ch| ch.is_ascii_digit());
+                if is_frac {
+                    self.advance();
+                    self.advance_while(|ch| ch.is_ascii_digit() || ch == &'_');
+                }
+            }
+            self.iter.reset_peek();
+            if self.advance_if('e') || self.advance_if('E') {
+                let _ = self.advance_if('+') || self.advance_if('-');
+            }
+            self.advance_while(|ch| ch.is_ascii_alphanumeric() || ch == &'_');
+            let literal = &self.source.text()[start..self.current];
+            let digits = literal.replace('_', "");
+            if !is_separated(literal, |ch| ch.is_ascii_digit()) {
+                None
+            } else if digits.contains(['.', 'e', 'E']) {
+                digits.parse::<f64>().ok().map(TokenType::Number)
+            } else {
+                digits.parse::<i64>().ok().map(TokenType::Int)
+            }
+        };
+        number.ok_or_else(|| {
+            Diag::error("syntax::malformed_number")
+                .msg("Malformed number")
+                .source(self.source)
+                .primary((start, self.current - start).into(), "this number")
+                .help("numbers look like `42`, `1_000`, `1.5e-3`, `0xFF`, `0o755` or `0b1010`")
+                .into()
+        })
+    }
+
+    /// The error of a string whose opening quote, or `r"`, is at
+    /// `leading_quote`.
+    fn unterminated_string(&self, leading_quote: SourceSpan) -> SyntaxError {
+        Diag::error("syntax::unterminated_string")
+            .msg("Missing trailing `\"` to terminate the string")
+            .source(self.source)
+            .primary(leading_quote, "opening `\"` found here")
+            .help("consider adding a `\"` after the string literal")
+            .into()
     }
 
     fn identifier(&mut self, start: usize) -> TokenType {
-        let len = self.advance_while(|ch| ch.is_alphanumeric() || ch == &'_');
-        let end = start + len;
-        let literal = &self.source[start..=end];
+        self.advance_while(|ch| ch.is_alphanumeric() || ch == &'_');
+        let literal = &self.source.text()[start..self.current];
         if let Ok(kw) = Keyword::from_str(literal) {
             TokenType::Keyword(kw)
         } else {
@@ -364,10 +691,12 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
         if count > 0 {
-            Err(SyntaxError::UnterminatedBlockComment {
-                src: NamedSource::new("", self.source.to_string()),
-                comment_start: (start, 2).into(),
-            })
+            Err(Diag::error("syntax::unterminated_block_comment")
+                .msg("Missing trailing `*/` to terminate the block comment")
+                .source(self.source)
+                .primary((start, 2).into(), "start of the block comment")
+                .help("consider adding `*/` at the end of the block comment")
+                .into())
         } else {
             Ok(TokenType::Comment)
         }
@@ -388,3 +717,435 @@ impl<'a> Iterator for Lexer<'a> {
         None
     }
 }
+
+/// Whether every `_` in `digits` is between two characters that are
+/// `digit`s.
+fn is_separated(digits: &str, digit: impl Fn(char) -> bool) -> bool {
+    let chars = digits.chars().collect::<Vec<_>>();
+    chars.iter().enumerate().all(|(i, &ch)| {
+        ch != '_' || i > 0 && digit(chars[i - 1]) && chars.get(i + 1).is_some_and(|&ch| digit(ch))
+    })
+}
+
+/// The number of bytes read at a time by a `ChunkedLexer` by default.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Lexes a source read from a stream a chunk at a time, so that huge or
+/// streamed sources never have to be held in one `String`. Only the part
+/// of the source that hasn't been turned into tokens yet is kept around,
+/// and a token split across chunks is lexed again once the rest of it has
+/// been read.
+///
+/// Token positions are relative to the whole source, like those of
+/// `Lexer`. Syntax errors only hold the part of the source buffered when
+/// they were found, and their spans are relative to that part.
+pub struct ChunkedLexer<R> {
+    reader: R,
+    name: String,
+    chunk_size: usize,
+    /// Bytes read but not decoded yet, i.e. a character split across chunks.
+    bytes: Vec<u8>,
+    /// Source decoded but not turned into tokens yet.
+    window: String,
+    /// The number of bytes and lines before the window, and the column the
+    /// window starts at.
+    offset: usize,
+    line: usize,
+    column: usize,
+    /// The interpolations open at the start of the window.
+    interpolations: Vec<usize>,
+    at_eof: bool,
+    pending: VecDeque<Result<Token, FoxError>>,
+}
+
+impl<R: Read> ChunkedLexer<R> {
+    pub fn new(reader: R) -> Self {
+        ChunkedLexer {
+            reader,
+            name: "<stream>".to_string(),
+            chunk_size: CHUNK_SIZE,
+            bytes: vec![],
+            window: String::new(),
+            offset: 0,
+            line: 1,
+            column: 1,
+            interpolations: vec![],
+            at_eof: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Sets the name diagnostics refer to the source by.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Reads the next chunk and decodes as much of it as possible into the
+    /// window. Returns `false` once the stream is exhausted.
+    fn read_chunk(&mut self) -> io::Result<bool> {
+        let len = self.bytes.len();
+        self.bytes.resize(len + self.chunk_size, 0);
+        let read = loop {
+            match self.reader.read(&mut self.bytes[len..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        self.bytes.truncate(len + *read.as_ref().unwrap_or(&0));
+        let read = read?;
+
+        let valid = match str::from_utf8(&self.bytes) {
+            Ok(decoded) => decoded.len(),
+            Err(e) if e.error_len().is_none() && read > 0 => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let decoded = str::from_utf8(&self.bytes[..valid]).unwrap();
+        self.window.push_str(decoded);
+        self.bytes.drain(..valid);
+        Ok(read > 0)
+    }
+
+    /// Lexes the window, queueing the tokens that can't change by reading
+    /// more and dropping their source from the window. Everything is queued
+    /// once the stream is exhausted.
+    fn lex_window(&mut self) {
+        let len = self.window.len();
+        let window = SourceFile::new(&self.name, self.window.as_str());
+        let mut lexer = Lexer::new(&window);
+        lexer.interpolations = self.interpolations.clone();
+        let mut end = 0;
+        while let Some(item) = lexer.scan_token() {
+            // A token is final once the characters the lexer looks ahead at
+            // to decide where it ends, at most two, have been read.
+            let item_end = match &item {
+                Ok(token) if token.ty == TokenType::Eof => None,
+                Ok(token) => Some(token.position.end),
+                Err(err) if err.code == "syntax::unexpected_character" => {
+                    err.span().map(|span| span.offset() + span.len())
+                }
+                Err(err)
+                    if err.code == "syntax::invalid_escape_sequence"
+                        || err.code == "syntax::malformed_number" =>
+                {
+                    Some(lexer.current)
+                }
+                // Unterminated strings and comments may end in a later chunk.
+                Err(_) => None,
+            };
+            if !self.at_eof && item_end.is_none_or(|item_end| item_end + 2 > len) {
+                break;
+            }
+            end = item_end.unwrap_or(len);
+            self.interpolations.clone_from(&lexer.interpolations);
+            match item {
+                Ok(token) if token.ty == TokenType::Comment => {}
+                Ok(mut token) => {
+                    if token.position.line == 1 {
+                        token.position.column += self.column - 1;
+                    }
+                    token.position.start += self.offset;
+                    token.position.end += self.offset;
+                    token.position.line += self.line - 1;
+                    self.pending.push_back(Ok(token));
+                }
+                Err(e) => self.pending.push_back(Err(FoxError::Syntax(vec![e]))),
+            }
+        }
+
+        let lexed = self.window.drain(..end).collect::<String>();
+        self.offset += lexed.len();
+        self.line += lexed.matches('\n').count();
+        match lexed.rfind('\n') {
+            Some(i) => self.column = lexed[i + 1..].chars().count() + 1,
+            None => self.column += lexed.chars().count(),
+        }
+    }
+}
+
+impl<R: Read> Iterator for ChunkedLexer<R> {
+    type Item = Result<Token, FoxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+            if self.at_eof {
+                return None;
+            }
+            match self.read_chunk() {
+                Ok(read) => self.at_eof = !read,
+                Err(error) => {
+                    self.at_eof = true;
+                    return Some(Err(FoxError::Io {
+                        path: self.name.clone(),
+                        error,
+                    }));
+                }
+            }
+            self.lex_window();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{errors::SyntaxError, source::SourceFile};
+
+    use super::{ChunkedLexer, Lexer, Position, Token, TokenType};
+
+    #[test]
+    fn check_scan_all() {
+        let source = SourceFile::new("test.fox", "let a = \"fox; // comment\n@ 1 # 2");
+        let (tokens, errors) = Lexer::new(&source).scan_all();
+        assert_eq!(codes(&errors), ["syntax::unterminated_string"]);
+        assert!(matches!(tokens.last(), Some(t) if t.ty == TokenType::Eof));
+
+        let source = SourceFile::new("test.fox", "a @ b # c");
+        let (tokens, errors) = Lexer::new(&source).scan_all();
+        assert_eq!(tokens.len(), 4);
+        let chars = errors.iter().map(|err| err.value("char").unwrap());
+        assert_eq!(chars.collect::<Vec<_>>(), ["@", "#"]);
+        assert_eq!(codes(&errors)[0], "syntax::unexpected_character");
+    }
+
+    fn codes(errors: &[SyntaxError]) -> Vec<&str> {
+        errors.iter().map(|err| err.code).collect()
+    }
+
+    /// The text of `source` pointed at by the primary label of `err`.
+    fn spanned<'a>(source: &'a str, err: &SyntaxError) -> &'a str {
+        let span = err.span().unwrap();
+        &source[span.offset()..span.offset() + span.len()]
+    }
+
+    #[test]
+    fn check_escapes() {
+        let lex = |text: &str| {
+            Lexer::new(&SourceFile::new("test.fox", text))
+                .next()
+                .unwrap()
+        };
+        let string = |text: &str| match lex(text) {
+            Ok(Token {
+                ty: TokenType::String(string),
+                ..
+            }) => string,
+            other => panic!("expected a string, found {other:?}"),
+        };
+        assert_eq!(string(r#""a\tb\nc\r\0""#), "a\tb\nc\r\0");
+        assert_eq!(string(r#""say \"fox\" \\o/""#), "say \"fox\" \\o/");
+        assert_eq!(string(r#""\u{1F98A} \u{e9}""#), "🦊 é");
+
+        for (text, escape) in [
+            (r#""a \q b""#, r"\q"),
+            (r#""\u{110000}""#, r"\u{110000}"),
+            (r#""\u{}""#, r"\u{"),
+            (r#""\u12""#, r"\u"),
+        ] {
+            let err = lex(text).unwrap_err();
+            assert_eq!(err.code, "syntax::invalid_escape_sequence", "{text}");
+            assert_eq!(spanned(text, &err), escape, "{text}");
+        }
+        let (tokens, errors) = Lexer::new(&SourceFile::new("test.fox", r#""\x" + 1"#)).scan_all();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            lex(r#""fox\""#).unwrap_err().code,
+            "syntax::unterminated_string"
+        );
+    }
+
+    #[test]
+    fn check_interpolation() {
+        let source = SourceFile::new("test.fox", r#""a${ {} }b${ "c${d}" }\${e}" }"#);
+        let (tokens, errors) = Lexer::new(&source).scan_all();
+        assert!(errors.is_empty());
+        let types = tokens.into_iter().map(|t| t.ty).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                TokenType::Interpolation("a".to_string()),
+                TokenType::LeftBrace,
+                TokenType::RightBrace,
+                TokenType::Interpolation("b".to_string()),
+                TokenType::Interpolation("c".to_string()),
+                TokenType::Identifier("d".to_string()),
+                TokenType::InterpolationEnd("".to_string()),
+                TokenType::InterpolationEnd("${e}".to_string()),
+                TokenType::RightBrace,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn check_raw_strings() {
+        let text = "r\"C:\\fox\\${den}\" \"two\nlines\" rest\nr\"three\n\n\" end r";
+        let (tokens, errors) = Lexer::new(&SourceFile::new("test.fox", text)).scan_all();
+        assert!(errors.is_empty());
+        let tokens = tokens
+            .into_iter()
+            .map(|t| (t.ty, t.position.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            [
+                (TokenType::String(r"C:\fox\${den}".to_string()), 1),
+                (TokenType::String("two\nlines".to_string()), 1),
+                (TokenType::Identifier("rest".to_string()), 2),
+                (TokenType::String("three\n\n".to_string()), 3),
+                (TokenType::Identifier("end".to_string()), 5),
+                (TokenType::Identifier("r".to_string()), 5),
+                (TokenType::Eof, 5),
+            ]
+        );
+        let (_, errors) = Lexer::new(&SourceFile::new("test.fox", "r\"fox")).scan_all();
+        assert_eq!(codes(&errors), ["syntax::unterminated_string"]);
+    }
+
+    #[test]
+    fn check_numbers() {
+        let lex = |text: &str| {
+            Lexer::new(&SourceFile::new("test.fox", text))
+                .next()
+                .unwrap()
+        };
+        for (text, int) in [
+            ("42", 42),
+            ("1_000_000", 1_000_000),
+            ("0xFF", 255),
+            ("0Xdead_beef", 3735928559),
+            ("0o755", 493),
+            ("0b1010", 10),
+        ] {
+            assert!(
+                matches!(lex(text), Ok(Token { ty: TokenType::Int(n), .. }) if n == int),
+                "{text}"
+            );
+        }
+        for (text, number) in [
+            ("12.5", 12.5),
+            ("1.5e-3", 1.5e-3),
+            ("2E+2", 200.0),
+            ("1_0.2_5e1_0", 10.25e10),
+        ] {
+            assert!(
+                matches!(lex(text), Ok(Token { ty: TokenType::Number(n), .. }) if n == number),
+                "{text}"
+            );
+        }
+        for text in [
+            "1_",
+            "1__0",
+            "1_.5",
+            "1e",
+            "1e+",
+            "12ab",
+            "0x",
+            "0xFG",
+            "0b102",
+            "0o_7",
+            "0x1_0000_0000_0000_0000",
+        ] {
+            let err = lex(text).unwrap_err();
+            assert_eq!(err.code, "syntax::malformed_number", "{text}");
+            assert_eq!(spanned(text, &err), text);
+        }
+        let (tokens, errors) = Lexer::new(&SourceFile::new("test.fox", "1.foo 0xZ + 2")).scan_all();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &tokens.iter().map(|t| &t.ty).collect::<Vec<_>>()[..],
+            [
+                TokenType::Int(1),
+                TokenType::Dot,
+                TokenType::Identifier(_),
+                TokenType::Plus,
+                TokenType::Int(2),
+                TokenType::Eof
+            ]
+        ));
+    }
+
+    #[test]
+    fn check_positions() {
+        let text = "// ¿qué?\nlet zorro = \"狐\" + año; ☃";
+        let source = SourceFile::new("test.fox", text);
+        let (tokens, errors) = Lexer::new(&source).scan_all();
+        let positions = tokens
+            .iter()
+            .filter(|t| t.ty != TokenType::Comment)
+            .map(|t| {
+                (
+                    &text[t.position.start..t.position.end],
+                    t.position.line,
+                    t.position.column,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            [
+                ("let", 2, 1),
+                ("zorro", 2, 5),
+                ("=", 2, 11),
+                ("\"狐\"", 2, 13),
+                ("+", 2, 17),
+                ("año", 2, 19),
+                (";", 2, 22),
+                ("", 2, 25),
+            ]
+        );
+        assert_eq!(codes(&errors), ["syntax::unexpected_character"]);
+        assert_eq!(spanned(text, &errors[0]), "☃");
+    }
+
+    #[test]
+    fn check_chunked() {
+        let source =
+            "class Fox < Animal {\n  fn speak() { print \"what does the fox say?\"; }\n}\n\
+            /* ¿qué /* dice */ el zorro? */ let ratio = 1_2.5e0 >= 0x3 != !false; // done\n\
+            fox.speak(r\"\\d+\", \"renard\", \"лиса\", \"狐 ${ratio + { }}\"); // 🦊";
+        let expected = Lexer::new(&SourceFile::new("test.fox", source))
+            .map(|t| t.unwrap())
+            .map(|t| {
+                let Position {
+                    line,
+                    column,
+                    start,
+                    end,
+                } = t.position;
+                (t.ty, line, column, start, end)
+            })
+            .collect::<Vec<_>>();
+        for chunk_size in [1, 2, 3, 7, 64, 4096] {
+            let tokens = ChunkedLexer::new(source.as_bytes())
+                .with_chunk_size(chunk_size)
+                .map(|t| t.unwrap())
+                .map(|t| {
+                    let Position {
+                        line,
+                        column,
+                        start,
+                        end,
+                    } = t.position;
+                    (t.ty, line, column, start, end)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(tokens, expected, "chunks of {chunk_size} bytes");
+        }
+
+        let errors = ChunkedLexer::new("a @ b \"c".as_bytes())
+            .with_chunk_size(2)
+            .filter_map(Result::err)
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 2);
+        assert!(ChunkedLexer::new(&[b'a', 0xff][..]).any(|t| t.is_err()));
+    }
+}
@@ -1,45 +1,255 @@
 #![feature(if_let_guard)]
 
+mod about;
+mod check;
+mod chunk;
+mod class;
+mod compiler;
+mod diag;
+mod diff;
+mod dump;
+mod engine;
+mod environment;
 mod errors;
-mod lexer;
 mod expr;
+mod function;
+mod heap;
+mod interpreter;
+mod iter;
+mod lexer;
+mod list;
+mod locale;
+mod map;
+mod module;
+mod native;
+mod number;
+mod output;
+mod parser;
+mod range;
+mod record;
+mod repl;
+mod resolver;
+mod snapshot;
+mod source;
+mod stdlib;
+mod stmt;
+mod suppress;
+mod testing;
+mod vm;
 
-use miette::Report;
 use std::{
-    fs,
-    io::Result,
-    io::{stdin, stdout, Write},
-    path::PathBuf,
+    fmt, fs,
+    path::{Path, PathBuf},
     process::exit,
+    time::Duration,
+};
+
+use miette::Report;
+
+use compiler::Compiler;
+use dump::{print_ast, print_tokens};
+use locale::Catalog;
+use testing::TestOptions;
+use vm::Vm;
+
+pub use diag::{Diag, DiagLabel, DiagLevel};
+pub use diff::{diff, Difference, DifferenceKind};
+pub use dump::{AstFormat, TokenFormat};
+pub use engine::Fox;
+pub use errors::{
+    CompileError, FoxError, ParseError, ResolveError, ResolveWarning, RuntimeError, SyntaxError,
 };
+pub use interpreter::{Callback, Interpreter, Value};
+pub use lexer::{ChunkedLexer, Keyword, Lexer, Position, Token, TokenType};
+pub use native::NativeError;
+pub use output::OutputLimits;
+pub use source::{LineCol, LineIndex, PositionEncoding, SourceFile, Span};
+
+/// The engine executing scripts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Backend {
+    /// Walks the syntax tree directly. Supports the whole language.
+    #[default]
+    Tree,
+    /// Compiles to bytecode run on a stack VM, which is faster but doesn't
+    /// support classes, modules, exceptions, `try` expressions or native
+    /// functions yet.
+    Vm,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = fmt::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(Backend::Tree),
+            "vm" => Ok(Backend::Vm),
+            _ => Err(fmt::Error),
+        }
+    }
+}
 
-use lexer::Lexer;
+/// Runs the script at `path`, printing at most what `limits` allow and
+/// stopping it once it has run for `timeout`, if given. With
+/// `debug_on_error`, an uncaught runtime error pauses the script at the
+/// debug prompt, which can step back through the last `record` changes to
+/// variables if given. Only the tree backend has the debug prompt.
+pub fn run_file(
+    path: PathBuf,
+    backend: Backend,
+    limits: OutputLimits,
+    timeout: Option<Duration>,
+    debug_on_error: bool,
+    record: Option<usize>,
+) {
+    let source = read_file(&path);
+    if run(source, backend, limits, timeout, debug_on_error, record).is_err() {
+        exit(64);
+    }
+}
 
-pub fn run_file(path: PathBuf) {
-    let source = fs::read_to_string(path).unwrap();
-    if let Err(_) = run(source) {
+/// Prints the tokens of the script at `path` in `format`.
+pub fn lex_file(path: PathBuf, format: TokenFormat) {
+    let source = read_file(&path);
+    if print_tokens(&source, format).is_err() {
         exit(64);
     }
 }
 
-pub fn run_prompt() {
-    loop {
-        print!("> ");
-        let _ = stdout().flush();
-        let mut line = String::new();
-        let _ = stdin().read_line(&mut line);
-        let _ = run(line);
+/// Prints the syntax tree of the script at `path` in `format`.
+pub fn parse_file(path: PathBuf, format: AstFormat) {
+    let source = read_file(&path);
+    if print_ast(&source, format).is_err() {
+        exit(64);
     }
 }
 
-fn run(source: String) -> Result<()> {
-    let lexer = Lexer::new(&source);
-    for i in lexer {
-        if let Err(e) = i {
-            eprintln!("{:?}", Report::new(e));
-        } else if let Ok(t) = i {
-            println!("{t:?}");
+pub fn run_prompt(backend: Backend) {
+    repl::run(backend);
+}
+
+/// Prints the version of fox, and with `verbose` how it was built.
+pub fn print_version(verbose: bool) {
+    print!("{}", about::version(verbose));
+}
+
+/// Prints where fox keeps its files, see `fox env`.
+pub fn print_env() {
+    print!("{}", about::env());
+}
+
+/// Checks the scripts found in `args` for errors without running them, see
+/// `check::check`.
+pub fn run_check(args: &[String], no_ignore: bool) {
+    if check::check(args, no_ignore).is_err() {
+        exit(64);
+    }
+}
+
+/// Runs the tests found in `args`, see `testing::test`, using `threads`
+/// threads or one per available core, and only the ones whose name contains
+/// `filter` if given. A test taking longer than `timeout` fails. Snapshots
+/// are recorded rather than compared if the `FOX_BLESS` environment
+/// variable is set to anything but `0`, and their differences are colored
+/// if `color` is set.
+pub fn run_tests(
+    args: &[String],
+    threads: Option<usize>,
+    filter: Option<String>,
+    timeout: Duration,
+    color: bool,
+) {
+    let bless =
+        std::env::var_os("FOX_BLESS").is_some_and(|bless| !bless.is_empty() && bless != "0");
+    let options = TestOptions {
+        threads,
+        filter,
+        timeout,
+        bless,
+        color,
+    };
+    if testing::test(args, &options).is_err() {
+        exit(64);
+    }
+}
+
+/// Translates diagnostics into `locale`, or the locale of the environment if
+/// `None`, when a catalog for it is found, see `Catalog::load`. English
+/// diagnostics are kept otherwise.
+pub fn set_locale(locale: Option<String>) {
+    let Some(locale) = locale.or_else(locale::system_locale) else {
+        return;
+    };
+    match Catalog::load(&locale) {
+        Ok(Some(catalog)) => locale::install(catalog),
+        Ok(None) => {}
+        Err(err) => report_error(err),
+    }
+}
+
+/// Reads the script at `path`, exiting if it can't be read.
+fn read_file(path: &Path) -> SourceFile {
+    match fs::read_to_string(path) {
+        Ok(text) => SourceFile::new(path.display().to_string(), text),
+        Err(error) => {
+            report_error(FoxError::Io {
+                path: path.display().to_string(),
+                error,
+            });
+            exit(66);
         }
     }
+}
+
+/// Lexes, parses and evaluates `source`, reporting every error to stderr.
+/// Returns `Err` if any error occurred.
+fn run(
+    source: SourceFile,
+    backend: Backend,
+    limits: OutputLimits,
+    timeout: Option<Duration>,
+    debug_on_error: bool,
+    record: Option<usize>,
+) -> Result<(), ()> {
+    let result = match backend {
+        Backend::Tree => {
+            let mut fox = Fox::new();
+            fox.interpreter().set_output_limits(limits);
+            fox.interpreter().set_timeout(timeout);
+            fox.interpreter().set_debug_on_error(debug_on_error);
+            fox.interpreter().set_recording(record);
+            let result = fox.eval_source(&source);
+            report_warnings(fox.take_warnings());
+            result.map(drop)
+        }
+        Backend::Vm => run_vm(&source, limits, timeout),
+    };
+    result.map_err(report_error)
+}
+
+fn run_vm(
+    source: &SourceFile,
+    limits: OutputLimits,
+    timeout: Option<Duration>,
+) -> Result<(), FoxError> {
+    let (stmts, warnings) = engine::parse(source)?;
+    report_warnings(warnings);
+    let mut vm = Vm::new();
+    let script = Compiler::new(source, vm.global_slots()).compile(&stmts)?;
+    vm.set_output_limits(limits);
+    vm.set_timeout(timeout);
+    vm.interpret(source, script)?;
     Ok(())
 }
+
+fn report_error(err: FoxError) {
+    for report in err.into_reports() {
+        eprintln!("{report:?}");
+    }
+}
+
+fn report_warnings(warnings: Vec<ResolveWarning>) {
+    for warning in warnings {
+        eprintln!("{:?}", Report::new(warning));
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+};
+
+use miette::SourceSpan;
+
+use crate::{
+    diag::Diag, errors::RuntimeError, interpreter::Value, map::Key, range::Range,
+    source::SourceFile,
+};
+
+pub struct List {
+    pub elements: RefCell<Vec<Value>>,
+    /// Whether the elements can no longer be set, see `List::freeze`.
+    pub frozen: Cell<bool>,
+}
+
+impl List {
+    pub fn new(elements: Vec<Value>) -> Self {
+        List {
+            elements: RefCell::new(elements),
+            frozen: Cell::new(false),
+        }
+    }
+
+    /// Makes this list and every list, map and instance reachable through
+    /// its elements immutable.
+    pub fn freeze(&self) {
+        if self.frozen.replace(true) {
+            return;
+        }
+        for value in self.elements.borrow().iter() {
+            value.freeze();
+        }
+    }
+
+    /// Writes the list like its literal, with `[...]` and `{...}` in place
+    /// of the lists and maps it is nested in, so that cyclic lists can be
+    /// printed.
+    pub(crate) fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        parents: &mut Vec<*const ()>,
+    ) -> fmt::Result {
+        let address = self as *const List as *const ();
+        if parents.contains(&address) {
+            return write!(f, "[...]");
+        }
+        parents.push(address);
+        write!(f, "[")?;
+        for (i, element) in self.elements.borrow().iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write_element(element, f, parents)?;
+        }
+        parents.pop();
+        write!(f, "]")
+    }
+}
+
+/// Writes an element of a list or map nested in `parents`, quoting strings.
+pub(crate) fn write_element(
+    value: &Value,
+    f: &mut fmt::Formatter<'_>,
+    parents: &mut Vec<*const ()>,
+) -> fmt::Result {
+    match value {
+        Value::List(list) => list.write(f, parents),
+        Value::Map(map) => map.write(f, parents),
+        Value::String(string) => write!(f, "{string:?}"),
+        value => write!(f, "{value}"),
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &mut vec![])
+    }
+}
+
+impl fmt::Debug for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+/// Why indexing a value failed, see `index` and `set_index`.
+#[derive(Debug)]
+pub(crate) enum IndexError {
+    /// The value indexed isn't a list or map, and has this type.
+    NotIndexable(&'static str),
+    /// The index of a list isn't an int, and has this type.
+    InvalidIndex(&'static str),
+    /// The key of a map can't be hashed, and has this type.
+    InvalidKey(&'static str),
+    OutOfBounds {
+        index: i64,
+        len: usize,
+    },
+    /// The range a list or string is sliced with goes past its length.
+    RangeOutOfBounds {
+        range: Range,
+        len: usize,
+    },
+    MissingKey(Key),
+    /// The list or map set is frozen, and has this type.
+    Frozen(&'static str),
+}
+
+impl IndexError {
+    /// The error of indexing with the index at `span`.
+    pub(crate) fn error(self, source: &SourceFile, span: SourceSpan) -> RuntimeError {
+        let diag = match self {
+            IndexError::NotIndexable(found) => Diag::error("runtime::not_indexable")
+                .arg("found", found)
+                .msg("Can only index lists and maps, found {found}")
+                .source(source)
+                .primary(span, "indexed here"),
+            IndexError::InvalidIndex(found) => Diag::error("runtime::invalid_index")
+                .arg("found", found)
+                .msg("List index must be an int, found {found}")
+                .source(source)
+                .primary(span, "found {found} here"),
+            IndexError::InvalidKey(found) => Diag::error("runtime::invalid_key")
+                .arg("found", found)
+                .msg("Map keys must be ints, strings, bools or nil, found {found}")
+                .source(source)
+                .primary(span, "found {found} here"),
+            IndexError::OutOfBounds { index, len } => Diag::error("runtime::index_out_of_bounds")
+                .arg("index", index)
+                .arg("len", len)
+                .msg("Index {index} is out of bounds for a list of length {len}")
+                .source(source)
+                .primary(span, "evaluates to {index}")
+                .help("indices start at 0 and go up to the length of the list minus one"),
+            IndexError::RangeOutOfBounds { range, len } => {
+                Diag::error("runtime::range_out_of_bounds")
+                    .arg("range", range)
+                    .arg("len", len)
+                    .msg("Range {range} is out of bounds for a length of {len}")
+                    .source(source)
+                    .primary(span, "slices out of bounds here")
+                    .help("slices start and end anywhere from 0 up to the length")
+            }
+            IndexError::MissingKey(key) => Diag::error("runtime::missing_key")
+                .arg("key", key)
+                .msg("Key {key} is not in the map")
+                .source(source)
+                .primary(span, "not found")
+                .help("consider checking for it first using `has(map, key)`"),
+            IndexError::Frozen(found) => Diag::error("runtime::frozen_collection")
+                .arg("found", found)
+                .msg("Can't set an element of a frozen {found}")
+                .source(source)
+                .primary(span, "set here")
+                .help("frozen {found}s can't be changed, consider copying it instead"),
+        };
+        diag.into()
+    }
+}
+
+/// The element of the list or the value of the map `object` at `index`, or
+/// the slice of the list or string `object` a range `index` covers.
+pub(crate) fn index(object: &Value, index: &Value) -> Result<Value, IndexError> {
+    if let Value::Range(range) = index {
+        return slice(object, range);
+    }
+    if let Value::Map(map) = object {
+        let key = key(index)?;
+        let entries = map.entries.borrow();
+        return match entries.get(&key) {
+            Some(value) => Ok(value.clone()),
+            None => Err(IndexError::MissingKey(key)),
+        };
+    }
+    let (list, i) = element(object, index)?;
+    let value = list.elements.borrow()[i].clone();
+    Ok(value)
+}
+
+/// Sets the element of the list or the value of the map `object` at `index`
+/// to `value`. Maps get a new entry if `index` isn't a key yet.
+pub(crate) fn set_index(object: &Value, index: &Value, value: Value) -> Result<(), IndexError> {
+    if let Value::Map(map) = object {
+        let key = key(index)?;
+        if map.frozen.get() {
+            return Err(IndexError::Frozen("map"));
+        }
+        map.entries.borrow_mut().insert(key, value);
+        return Ok(());
+    }
+    let (list, i) = element(object, index)?;
+    if list.frozen.get() {
+        return Err(IndexError::Frozen("list"));
+    }
+    list.elements.borrow_mut()[i] = value;
+    Ok(())
+}
+
+/// The part of the list or string `object` that `range` covers, as a new
+/// list or string. Strings are sliced by characters.
+fn slice(object: &Value, range: &Range) -> Result<Value, IndexError> {
+    let len = match object {
+        Value::List(list) => list.elements.borrow().len(),
+        Value::String(string) => string.chars().count(),
+        object => return Err(IndexError::NotIndexable(object.type_name())),
+    };
+    let Some(positions) = range.slice(len) else {
+        return Err(IndexError::RangeOutOfBounds { range: *range, len });
+    };
+    Ok(match object {
+        Value::List(list) => {
+            let elements = list.elements.borrow()[positions].to_vec();
+            Value::List(Rc::new(List::new(elements)))
+        }
+        Value::String(string) => {
+            let chars = string.chars().skip(positions.start);
+            Value::String(chars.take(positions.len()).collect())
+        }
+        _ => unreachable!(),
+    })
+}
+
+/// `value` as the key of a map.
+pub(crate) fn key(value: &Value) -> Result<Key, IndexError> {
+    Key::new(value).ok_or_else(|| IndexError::InvalidKey(value.type_name()))
+}
+
+/// The list `object` is and the position in it `index` refers to.
+fn element<'a>(object: &'a Value, index: &Value) -> Result<(&'a List, usize), IndexError> {
+    let Value::List(list) = object else {
+        return Err(IndexError::NotIndexable(object.type_name()));
+    };
+    let Value::Int(index) = *index else {
+        return Err(IndexError::InvalidIndex(index.type_name()));
+    };
+    let len = list.elements.borrow().len();
+    match usize::try_from(index) {
+        Ok(i) if i < len => Ok((list, i)),
+        _ => Err(IndexError::OutOfBounds { index, len }),
+    }
+}
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, env, fs, path::PathBuf, sync::OnceLock};
+
+use crate::{diag, errors::FoxError};
+
+/// The catalog diagnostics are translated with, if any was installed.
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Translations of diagnostic messages, labels and help, read from a subset
+/// of the gettext PO format:
+///
+/// ```text
+/// # A comment.
+/// msgid "runtime::error"
+/// msgstr "Erreur d'exécution"
+///
+/// msgid "runtime::undefined_variable"
+/// msgstr "Variable `{name}` non définie"
+///
+/// msgid "runtime::undefined_variable.primary"
+/// msgstr "pas définie"
+/// ```
+///
+/// Texts are keyed by the code of their diagnostic: the message by the code
+/// itself, and its labels and help by the code followed by `.primary`,
+/// `.secondary` or `.help`. The prefix of every diagnostic of a phase is
+/// keyed like `runtime::error` or `resolve::warning`. Translations name
+/// the same args as the English texts, see `Diag`.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    texts: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Parses a catalog read from `path`, which is only used in errors.
+    pub fn parse(path: &str, text: &str) -> Result<Catalog, FoxError> {
+        let mut catalog = Catalog::default();
+        let mut msgid = None;
+        for (i, line) in text.lines().enumerate() {
+            let invalid = |reason: &str| FoxError::InvalidCatalog {
+                path: path.to_string(),
+                line: i + 1,
+                reason: reason.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, string) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid("expected `msgid` or `msgstr` followed by a string"))?;
+            let string = unquote(string.trim()).ok_or_else(|| invalid("malformed string"))?;
+            match (keyword, msgid.take()) {
+                ("msgid", None) => msgid = Some(string),
+                ("msgstr", Some(id)) => {
+                    catalog.texts.insert(id, string);
+                }
+                ("msgid", Some(_)) => return Err(invalid("expected `msgstr` after `msgid`")),
+                ("msgstr", None) => return Err(invalid("expected `msgid` before `msgstr`")),
+                _ => return Err(invalid("expected `msgid` or `msgstr`")),
+            }
+        }
+        if msgid.is_some() {
+            return Err(FoxError::InvalidCatalog {
+                path: path.to_string(),
+                line: text.lines().count(),
+                reason: "expected `msgstr` after `msgid`".to_string(),
+            });
+        }
+        Ok(catalog)
+    }
+
+    /// Loads the catalog for `locale`, like `fr` or `pt_BR.UTF-8`, from
+    /// `fox/locales/<locale>.po` in the config directory, falling back to the
+    /// catalog of the language alone. English needs no catalog, and other
+    /// locales without one are shown in English too.
+    pub fn load(locale: &str) -> Result<Option<Catalog>, FoxError> {
+        let locale = locale.split(['.', '@']).next().unwrap_or_default();
+        let language = locale.split(['_', '-']).next().unwrap_or_default();
+        if language.is_empty() || language == "en" || language == "C" || language == "POSIX" {
+            return Ok(None);
+        }
+        let Some(dir) = catalog_dir() else {
+            return Ok(None);
+        };
+        for name in [locale, language] {
+            let path = dir.join(format!("{name}.po"));
+            match fs::read_to_string(&path) {
+                Ok(text) => return Catalog::parse(&path.display().to_string(), &text).map(Some),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(error) => {
+                    return Err(FoxError::Io {
+                        path: path.display().to_string(),
+                        error,
+                    })
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The translation keyed `key` filled with `args`, if the catalog has
+    /// one and it only names args given.
+    pub fn translate(&self, key: &str, args: &[(&str, String)]) -> Option<String> {
+        diag::fill(self.texts.get(key)?, args)
+    }
+}
+
+/// The locale asked for by the environment, like `LANG=fr_FR.UTF-8`.
+pub fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|locale| !locale.is_empty())
+}
+
+/// Translates the diagnostics built from now on with `catalog`. Only
+/// the first catalog installed is used.
+pub fn install(catalog: Catalog) {
+    let _ = CATALOG.set(catalog);
+}
+
+/// The catalog installed with `install`, if any.
+pub(crate) fn catalog() -> Option<&'static Catalog> {
+    CATALOG.get()
+}
+
+/// Parses a double quoted string with `\"`, `\\`, `\n` and `\t` escapes.
+fn unquote(string: &str) -> Option<String> {
+    let inner = string.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => unquoted.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                ch @ ('"' | '\\') => ch,
+                _ => return None,
+            }),
+            '"' => return None,
+            ch => unquoted.push(ch),
+        }
+    }
+    Some(unquoted)
+}
+
+/// Where catalogs are looked up, see `Catalog::load`.
+pub(crate) fn catalog_dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("fox").join("locales"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::errors::FoxError;
+
+    use super::Catalog;
+
+    #[test]
+    fn check_translate() {
+        let catalog = Catalog::parse(
+            "fr.po",
+            r#"
+            # Runtime errors.
+            msgid "runtime::error"
+            msgstr "Erreur d'exécution"
+
+            msgid "runtime::index_out_of_bounds"
+            msgstr "Liste de longueur {len} indexée par {index}"
+
+            msgid "runtime::undefined_variable.primary"
+            msgstr "pas \"définie\""
+
+            msgid "runtime::stack_overflow.help"
+            msgstr "plus de {limit} appels imbriqués, {depth} au total"
+            "#,
+        )
+        .unwrap();
+        let args = [("index", "3".to_string()), ("len", "2".to_string())];
+        assert_eq!(
+            catalog.translate("runtime::error", &[]).unwrap(),
+            "Erreur d'exécution"
+        );
+        assert_eq!(
+            catalog
+                .translate("runtime::index_out_of_bounds", &args)
+                .unwrap(),
+            "Liste de longueur 2 indexée par 3"
+        );
+        assert_eq!(
+            catalog
+                .translate("runtime::undefined_variable.primary", &[])
+                .unwrap(),
+            "pas \"définie\""
+        );
+        assert_eq!(catalog.translate("runtime::undefined_variable", &[]), None);
+        let args = [("limit", "1024".to_string())];
+        assert_eq!(
+            catalog.translate("runtime::stack_overflow.help", &args),
+            None
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        assert!(matches!(
+            Catalog::parse("fr.po", "msgid \"a\"\nmsgid \"b\""),
+            Err(FoxError::InvalidCatalog { line: 2, .. })
+        ));
+        assert!(matches!(
+            Catalog::parse("fr.po", "msgstr \"a\""),
+            Err(FoxError::InvalidCatalog { line: 1, .. })
+        ));
+        assert!(matches!(
+            Catalog::parse("fr.po", "\nmsgid \"a\nmsgstr \"b\""),
+            Err(FoxError::InvalidCatalog { line: 2, .. })
+        ));
+        assert!(matches!(
+            Catalog::parse("fr.po", "msgid \"a\""),
+            Err(FoxError::InvalidCatalog { line: 1, .. })
+        ));
+        assert!(Catalog::load("en_US.UTF-8").unwrap().is_none());
+    }
+}
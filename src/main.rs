@@ -1,14 +1,162 @@
-use std::path::PathBuf;
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use fox::{AstFormat, Backend, OutputLimits, TokenFormat};
+use miette::MietteHandlerOpts;
+
+#[derive(Parser)]
+#[command(
+    name = "fox",
+    version,
+    disable_version_flag = true,
+    about = "The Fox programming language"
+)]
+struct Cli {
+    /// Print the version
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// With `--version`, also print the commit, target and features fox was
+    /// built with
+    #[arg(long, requires = "version")]
+    verbose: bool,
+
+    /// The engine executing scripts: `tree` or `vm`
+    #[arg(long, global = true, default_value = "tree")]
+    backend: Backend,
+
+    /// Print diagnostics without colors
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// The language of diagnostics, like `fr` or `pt_BR`, defaulting to the
+    /// one of the environment
+    #[arg(long, global = true)]
+    locale: Option<String>,
+
+    /// What to do, starting the interactive prompt if omitted
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a script
+    Run {
+        path: PathBuf,
+        /// The most bytes the script can print in total
+        #[arg(long)]
+        max_output: Option<usize>,
+        /// The most bytes a single `print` can write
+        #[arg(long)]
+        max_print: Option<usize>,
+        /// How many seconds the script can run before it is stopped
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+        /// Pause at the debug prompt where an uncaught error is raised
+        /// (tree backend only)
+        #[arg(long)]
+        debug_on_error: bool,
+        /// Record the last CHANGES changes to variables, 100000 by default,
+        /// for the debug prompt to step back through (tree backend only)
+        #[arg(
+            long,
+            value_name = "CHANGES",
+            num_args = 0..=1,
+            default_missing_value = "100000"
+        )]
+        record: Option<usize>,
+    },
+    /// Start the interactive prompt
+    Repl,
+    /// Print the tokens of a script
+    Lex {
+        path: PathBuf,
+        /// How to print the tokens: `human` or `json`
+        #[arg(long, default_value = "human")]
+        format: TokenFormat,
+    },
+    /// Print the syntax tree of a script
+    #[command(alias = "parse")]
+    Ast {
+        path: PathBuf,
+        /// How to print the tree: `sexpr`, `json` or `dot`
+        #[arg(long, default_value = "sexpr")]
+        format: AstFormat,
+    },
+    /// Check scripts, or the `.fox` files in directories, without running them
+    Check {
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Check the files matched by `.gitignore` and `.foxignore` too
+        #[arg(long)]
+        no_ignore: bool,
+    },
+    /// Run the tests in scripts, or in the `*_test.fox` files in directories
+    Test {
+        #[arg(default_value = ".")]
+        paths: Vec<String>,
+        /// How many test files run at once, one per core by default
+        #[arg(long, value_name = "N")]
+        test_threads: Option<usize>,
+        /// Only run the tests whose name, like `math_test.fox::test_add`,
+        /// contains NAME
+        #[arg(long, value_name = "NAME")]
+        filter: Option<String>,
+        /// How many seconds a single test can take
+        #[arg(long, value_name = "SECS", default_value = "60")]
+        timeout: u64,
+    },
+    /// Print where fox keeps its files, like the prompt history
+    Env,
+}
 
 fn main() {
-    let mut args = std::env::args();
-    if args.len() > 2 {
-        eprintln!("Usage: fox [script]")
-    } else if args.len() == 2 {
-        if let Some(path) = args.nth(1) {
-            fox::run_file(PathBuf::from(path));
+    let cli = Cli::parse();
+    if cli.version {
+        fox::print_version(cli.verbose);
+        return;
+    }
+    if cli.no_color {
+        let _ = miette::set_hook(Box::new(|_| {
+            Box::new(MietteHandlerOpts::new().color(false).build())
+        }));
+    }
+    fox::set_locale(cli.locale);
+    match cli.command.unwrap_or(Command::Repl) {
+        Command::Run {
+            path,
+            max_output,
+            max_print,
+            timeout,
+            debug_on_error,
+            record,
+        } => {
+            let limits = OutputLimits {
+                max_output,
+                max_print,
+            };
+            let timeout = timeout.map(Duration::from_secs);
+            fox::run_file(path, cli.backend, limits, timeout, debug_on_error, record)
+        }
+        Command::Repl => fox::run_prompt(cli.backend),
+        Command::Lex { path, format } => fox::lex_file(path, format),
+        Command::Ast { path, format } => fox::parse_file(path, format),
+        Command::Check { paths, no_ignore } => fox::run_check(&paths, no_ignore),
+        Command::Test {
+            paths,
+            test_threads,
+            filter,
+            timeout,
+        } => {
+            let timeout = Duration::from_secs(timeout);
+            let color = !cli.no_color && io::stdout().is_terminal();
+            fox::run_tests(&paths, test_threads, filter, timeout, color)
         }
-    } else {
-        fox::run_prompt();
+        Command::Env => fox::print_env(),
     }
 }
@@ -0,0 +1,160 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+};
+
+use crate::{interpreter::Value, list};
+
+/// A value a `Map` can be keyed by. Floats are left out, as they can't be
+/// hashed, and so are objects, which are compared by identity.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Int(i64),
+    String(String),
+    Bool(bool),
+    Nil,
+}
+
+impl Key {
+    pub fn new(value: &Value) -> Option<Key> {
+        match value {
+            Value::Int(int) => Some(Key::Int(*int)),
+            Value::String(string) => Some(Key::String(string.clone())),
+            Value::Bool(bool) => Some(Key::Bool(*bool)),
+            Value::Nil => Some(Key::Nil),
+            _ => None,
+        }
+    }
+}
+
+impl From<Key> for Value {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Int(int) => Value::Int(int),
+            Key::String(string) => Value::String(string),
+            Key::Bool(bool) => Value::Bool(bool),
+            Key::Nil => Value::Nil,
+        }
+    }
+}
+
+/// Writes the key like its literal, with strings quoted.
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Int(int) => write!(f, "{int}"),
+            Key::String(string) => write!(f, "{string:?}"),
+            Key::Bool(bool) => write!(f, "{bool}"),
+            Key::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+/// The entries of a `Map`, in the order their keys were first inserted.
+#[derive(Debug, Default)]
+pub struct Entries {
+    entries: Vec<(Key, Value)>,
+    indices: HashMap<Key, usize>,
+}
+
+impl Entries {
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        let i = *self.indices.get(key)?;
+        Some(&self.entries[i].1)
+    }
+
+    /// Sets the value of `key`, which keeps its place if it was set already.
+    pub fn insert(&mut self, key: Key, value: Value) {
+        match self.indices.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every entry, returning the values.
+    pub fn take_values(&mut self) -> Vec<Value> {
+        self.indices.clear();
+        self.entries.drain(..).map(|(_, value)| value).collect()
+    }
+}
+
+pub struct Map {
+    pub entries: RefCell<Entries>,
+    /// Whether the entries can no longer be set, see `Map::freeze`.
+    pub frozen: Cell<bool>,
+}
+
+impl Map {
+    pub fn new(entries: Entries) -> Self {
+        Map {
+            entries: RefCell::new(entries),
+            frozen: Cell::new(false),
+        }
+    }
+
+    /// Makes this map and every map, list and instance reachable through
+    /// its values immutable.
+    pub fn freeze(&self) {
+        if self.frozen.replace(true) {
+            return;
+        }
+        for (_, value) in self.entries.borrow().iter() {
+            value.freeze();
+        }
+    }
+
+    /// Writes the map like its literal, see `list::write_element`.
+    pub(crate) fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        parents: &mut Vec<*const ()>,
+    ) -> fmt::Result {
+        let address = self as *const Map as *const ();
+        if parents.contains(&address) {
+            return write!(f, "{{...}}");
+        }
+        parents.push(address);
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.entries.borrow().iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{key}: ")?;
+            list::write_element(value, f, parents)?;
+        }
+        parents.pop();
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &mut vec![])
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
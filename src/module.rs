@@ -0,0 +1,64 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+    rc::Rc,
+};
+
+use crate::{environment::Environment, interpreter::Value, source::SourceFile};
+
+/// A file loaded by `import`. Its top-level declarations live in globals of
+/// its own rather than in those of the script importing it, which only sees
+/// the ones declared with `export`.
+pub struct Module {
+    pub name: String,
+    pub source: SourceFile,
+    pub globals: Rc<RefCell<Environment>>,
+    exports: RefCell<HashSet<String>>,
+}
+
+impl Module {
+    pub fn new(name: String, source: SourceFile, globals: Rc<RefCell<Environment>>) -> Self {
+        Module {
+            name,
+            source,
+            globals,
+            exports: RefCell::default(),
+        }
+    }
+
+    pub(crate) fn export(&self, name: String) {
+        self.exports.borrow_mut().insert(name);
+    }
+
+    /// The current value of the exported variable `name`, or `None` if it
+    /// isn't exported.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        if !self.exports.borrow().contains(name) {
+            return None;
+        }
+        self.globals.borrow().get(name)
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+/// The modules an `Interpreter` has loaded.
+#[derive(Default)]
+pub(crate) struct Modules {
+    /// The modules loaded so far by canonical path, so that each runs once.
+    pub loaded: HashMap<PathBuf, Rc<Module>>,
+    /// The modules being loaded, outermost first, by canonical path along
+    /// with the path they were imported by, to report import cycles.
+    pub loading: Vec<(PathBuf, String)>,
+    /// The module whose code is running, or `None` for the main script.
+    pub current: Option<Rc<Module>>,
+    /// The globals and source of the main script, kept while the code of a
+    /// module runs.
+    pub script: Option<(Rc<RefCell<Environment>>, SourceFile)>,
+}
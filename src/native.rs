@@ -0,0 +1,216 @@
+use std::{fmt, rc::Rc};
+
+use crate::{
+    diff::diff,
+    errors::RuntimeError,
+    interpreter::{Interpreter, Value},
+    list::List,
+    map::{Key, Map},
+};
+
+pub type PlainFn = Box<dyn Fn(&[Value]) -> Result<Value, NativeError>>;
+
+pub type HigherOrderFn = Box<dyn Fn(&mut Interpreter, &[Value]) -> Result<Value, CallError>>;
+
+/// What a native function runs when called.
+pub enum NativeFn {
+    Plain(PlainFn),
+    /// Calls back into Fox code, like `sort` calls its comparator, using
+    /// `Interpreter::call_back`.
+    HigherOrder(HigherOrderFn),
+}
+
+/// Why a native function calling back into Fox code failed.
+#[derive(Debug)]
+pub enum CallError {
+    /// The native function itself failed.
+    Native(NativeError),
+    /// The Fox code it called raised an error, which propagates as is.
+    Fox(RuntimeError),
+}
+
+impl From<NativeError> for CallError {
+    fn from(error: NativeError) -> Self {
+        CallError::Native(error)
+    }
+}
+
+impl From<String> for CallError {
+    fn from(message: String) -> Self {
+        CallError::Native(message.into())
+    }
+}
+
+impl From<RuntimeError> for CallError {
+    fn from(err: RuntimeError) -> Self {
+        CallError::Fox(err)
+    }
+}
+
+/// Why a native function failed. Fox code catches it as an `Error` with the
+/// same kind, message and data, so hosts can pass along machine-readable
+/// details like an HTTP status code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NativeError {
+    pub kind: String,
+    pub message: String,
+    pub data: Value,
+}
+
+impl NativeError {
+    pub fn new(kind: &str, message: &str, data: impl Into<Value>) -> Self {
+        NativeError {
+            kind: kind.to_string(),
+            message: message.to_string(),
+            data: data.into(),
+        }
+    }
+}
+
+/// Plain messages, like those of failed `Value` conversions, are errors of
+/// kind `native` without data.
+impl From<String> for NativeError {
+    fn from(message: String) -> Self {
+        NativeError {
+            kind: "native".to_string(),
+            message,
+            data: Value::Nil,
+        }
+    }
+}
+
+/// A function implemented in Rust that Fox code can call like any other.
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    /// How many of the last `arity` arguments can be left out.
+    pub optional: usize,
+    pub function: NativeFn,
+}
+
+impl NativeFunction {
+    /// The method of `receiver` this function is, which is called with the
+    /// receiver as first argument followed by the arguments of the call.
+    pub(crate) fn bind(self: &Rc<Self>, receiver: Value) -> NativeFunction {
+        let native = Rc::clone(self);
+        NativeFunction {
+            name: self.name.clone(),
+            arity: self.arity - 1,
+            optional: self.optional,
+            function: NativeFn::HigherOrder(Box::new(move |interpreter, args| {
+                let mut arguments = Vec::with_capacity(args.len() + 1);
+                arguments.push(receiver.clone());
+                arguments.extend_from_slice(args);
+                native.call(interpreter, &arguments)
+            })),
+        }
+    }
+
+    /// Calls the function with `args`, using `interpreter` to run the Fox
+    /// code it calls back, if any.
+    pub(crate) fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        args: &[Value],
+    ) -> Result<Value, CallError> {
+        match &self.function {
+            NativeFn::Plain(function) => Ok(function(args)?),
+            NativeFn::HigherOrder(function) => function(interpreter, args),
+        }
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// The native functions defined as globals in every interpreter.
+pub fn builtins() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction {
+            name: "diff".to_string(),
+            arity: 2,
+            optional: 0,
+            function: NativeFn::Plain(Box::new(|args| Ok(describe_diff(&args[0], &args[1])))),
+        },
+        NativeFunction {
+            name: "freeze".to_string(),
+            arity: 1,
+            optional: 0,
+            function: NativeFn::Plain(Box::new(|args| Ok(freeze(&args[0])))),
+        },
+        NativeFunction {
+            name: "keys".to_string(),
+            arity: 1,
+            optional: 0,
+            function: NativeFn::Plain(Box::new(|args| {
+                let entries = map(&args[0])?.entries.borrow();
+                let keys = entries.iter().map(|(key, _)| key.clone().into());
+                Ok(new_list(keys.collect()))
+            })),
+        },
+        NativeFunction {
+            name: "values".to_string(),
+            arity: 1,
+            optional: 0,
+            function: NativeFn::Plain(Box::new(|args| {
+                let entries = map(&args[0])?.entries.borrow();
+                let values = entries.iter().map(|(_, value)| value.clone());
+                Ok(new_list(values.collect()))
+            })),
+        },
+        NativeFunction {
+            name: "has".to_string(),
+            arity: 2,
+            optional: 0,
+            function: NativeFn::Plain(Box::new(|args| {
+                let key = Key::new(&args[1]).ok_or_else(|| {
+                    format!(
+                        "expected an int, string, bool or nil key, found {}",
+                        args[1].type_name()
+                    )
+                })?;
+                Ok(Value::Bool(
+                    map(&args[0])?.entries.borrow().contains_key(&key),
+                ))
+            })),
+        },
+    ]
+}
+
+/// The map `value` is, or an error naming what it is instead.
+fn map(value: &Value) -> Result<&Map, NativeError> {
+    match value {
+        Value::Map(map) => Ok(map),
+        value => Err(format!("expected a map, found {}", value.type_name()).into()),
+    }
+}
+
+/// A list of `elements`. Lists made by natives aren't tracked by the heap,
+/// which only means that cycles going through them are never collected.
+pub(crate) fn new_list(elements: Vec<Value>) -> Value {
+    Value::List(Rc::new(List::new(elements)))
+}
+
+/// Describes the differences between `a` and `b`, one per line, or returns
+/// `nil` if they are equal.
+fn describe_diff(a: &Value, b: &Value) -> Value {
+    let differences = diff(a, b);
+    if differences.is_empty() {
+        return Value::Nil;
+    }
+    let lines = differences
+        .iter()
+        .map(|difference| difference.to_string())
+        .collect::<Vec<_>>();
+    Value::String(lines.join("\n"))
+}
+
+/// Makes `value` deeply immutable and returns it. Only instances, lists and
+/// maps are mutable to begin with, so other values are returned as is.
+fn freeze(value: &Value) -> Value {
+    value.freeze();
+    value.clone()
+}
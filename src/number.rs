@@ -0,0 +1,215 @@
+use crate::{interpreter::Value, range::Range};
+
+/// An operator on numbers, applied the same way by both backends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum NumericOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    /// `..`, making a `Range` of two ints.
+    Range,
+    /// `..=`, making a `Range` of two ints that includes its end.
+    RangeInclusive,
+}
+
+/// Why applying a `NumericOp` failed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum NumericError {
+    /// An operand isn't a number.
+    NotANumber,
+    /// The result of an operation on ints doesn't fit in an int, or a
+    /// shift is by a negative amount or by 64 bits or more.
+    Overflow,
+    /// The remainder of an int divided by zero.
+    DivisionByZero,
+}
+
+impl NumericOp {
+    /// Whether the operator only applies to ints.
+    pub(crate) fn takes_ints(self) -> bool {
+        matches!(
+            self,
+            NumericOp::BitAnd
+                | NumericOp::BitOr
+                | NumericOp::BitXor
+                | NumericOp::ShiftLeft
+                | NumericOp::ShiftRight
+                | NumericOp::Range
+                | NumericOp::RangeInclusive
+        )
+    }
+}
+
+impl Value {
+    /// The value of an int or a float as a float, or `None` for other values.
+    pub(crate) fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Int(int) => Some(*int as f64),
+            Value::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+
+    /// Applies `op` to this value and `rhs`. Arithmetic on two ints gives
+    /// an int, except for division and negative powers, while a float operand
+    /// makes the other one a float too. Bitwise and range operators only take
+    /// ints.
+    /// Ints and floats compare by their value.
+    pub(crate) fn numeric(&self, op: NumericOp, rhs: &Value) -> Result<Value, NumericError> {
+        match (self, rhs) {
+            (Value::Int(_), Value::Int(_)) if op == NumericOp::Divide => {}
+            (Value::Int(_), Value::Int(rhs)) if op == NumericOp::Power && *rhs < 0 => {}
+            (Value::Int(lhs), Value::Int(rhs)) => return int(op, *lhs, *rhs),
+            _ if op.takes_ints() => return Err(NumericError::NotANumber),
+            _ => {}
+        }
+        let (Some(lhs), Some(rhs)) = (self.as_float(), rhs.as_float()) else {
+            return Err(NumericError::NotANumber);
+        };
+        Ok(match op {
+            NumericOp::Add => Value::Float(lhs + rhs),
+            NumericOp::Subtract => Value::Float(lhs - rhs),
+            NumericOp::Multiply => Value::Float(lhs * rhs),
+            NumericOp::Divide => Value::Float(lhs / rhs),
+            NumericOp::Remainder => Value::Float(lhs % rhs),
+            NumericOp::Power => Value::Float(lhs.powf(rhs)),
+            NumericOp::Greater => Value::Bool(lhs > rhs),
+            NumericOp::GreaterEqual => Value::Bool(lhs >= rhs),
+            NumericOp::Less => Value::Bool(lhs < rhs),
+            NumericOp::LessEqual => Value::Bool(lhs <= rhs),
+            _ => unreachable!(),
+        })
+    }
+
+    /// The number with the opposite sign.
+    pub(crate) fn negate(&self) -> Result<Value, NumericError> {
+        match self {
+            Value::Int(int) => int
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or(NumericError::Overflow),
+            Value::Float(float) => Ok(Value::Float(-float)),
+            _ => Err(NumericError::NotANumber),
+        }
+    }
+}
+
+/// Applies `op` to two ints, when the result is an int.
+fn int(op: NumericOp, lhs: i64, rhs: i64) -> Result<Value, NumericError> {
+    let result = match op {
+        NumericOp::Add => lhs.checked_add(rhs),
+        NumericOp::Subtract => lhs.checked_sub(rhs),
+        NumericOp::Multiply => lhs.checked_mul(rhs),
+        NumericOp::Remainder if rhs == 0 => return Err(NumericError::DivisionByZero),
+        NumericOp::Remainder => lhs.checked_rem(rhs),
+        NumericOp::Power => u32::try_from(rhs).ok().and_then(|exp| lhs.checked_pow(exp)),
+        NumericOp::BitAnd => Some(lhs & rhs),
+        NumericOp::BitOr => Some(lhs | rhs),
+        NumericOp::BitXor => Some(lhs ^ rhs),
+        NumericOp::ShiftLeft => u32::try_from(rhs).ok().and_then(|n| lhs.checked_shl(n)),
+        NumericOp::ShiftRight => u32::try_from(rhs).ok().and_then(|n| lhs.checked_shr(n)),
+        NumericOp::Greater => return Ok(Value::Bool(lhs > rhs)),
+        NumericOp::GreaterEqual => return Ok(Value::Bool(lhs >= rhs)),
+        NumericOp::Less => return Ok(Value::Bool(lhs < rhs)),
+        NumericOp::LessEqual => return Ok(Value::Bool(lhs <= rhs)),
+        NumericOp::Range | NumericOp::RangeInclusive => {
+            return Ok(Value::Range(Range {
+                start: lhs,
+                end: rhs,
+                inclusive: op == NumericOp::RangeInclusive,
+            }))
+        }
+        NumericOp::Divide => unreachable!(),
+    };
+    result.map(Value::Int).ok_or(NumericError::Overflow)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::Value;
+
+    use super::{NumericError, NumericOp};
+
+    #[test]
+    fn check_promotion() {
+        let numeric = |lhs: Value, op, rhs: Value| lhs.numeric(op, &rhs);
+        assert!(matches!(
+            numeric(Value::Int(7), NumericOp::Add, Value::Int(2)),
+            Ok(Value::Int(9))
+        ));
+        assert!(matches!(
+            numeric(Value::Int(7), NumericOp::Divide, Value::Int(2)),
+            Ok(Value::Float(n)) if n == 3.5
+        ));
+        assert!(matches!(
+            numeric(Value::Int(7), NumericOp::Multiply, Value::Float(0.5)),
+            Ok(Value::Float(n)) if n == 3.5
+        ));
+        assert!(matches!(
+            numeric(Value::Float(2.5), NumericOp::Less, Value::Int(3)),
+            Ok(Value::Bool(true))
+        ));
+        assert!(matches!(
+            numeric(
+                Value::Float(f64::NAN),
+                NumericOp::GreaterEqual,
+                Value::Int(3)
+            ),
+            Ok(Value::Bool(false))
+        ));
+        assert_eq!(
+            numeric(Value::Int(i64::MAX), NumericOp::Add, Value::Int(1)),
+            Err(NumericError::Overflow)
+        );
+        assert_eq!(
+            numeric(Value::Int(1), NumericOp::Subtract, Value::Nil),
+            Err(NumericError::NotANumber)
+        );
+        assert!(matches!(
+            numeric(Value::Int(-7), NumericOp::Remainder, Value::Int(3)),
+            Ok(Value::Int(-1))
+        ));
+        assert_eq!(
+            numeric(Value::Int(7), NumericOp::Remainder, Value::Int(0)),
+            Err(NumericError::DivisionByZero)
+        );
+        assert!(matches!(
+            numeric(Value::Int(2), NumericOp::Power, Value::Int(10)),
+            Ok(Value::Int(1024))
+        ));
+        assert!(matches!(
+            numeric(Value::Int(2), NumericOp::Power, Value::Int(-1)),
+            Ok(Value::Float(n)) if n == 0.5
+        ));
+        assert_eq!(
+            numeric(Value::Int(2), NumericOp::Power, Value::Int(64)),
+            Err(NumericError::Overflow)
+        );
+        assert!(matches!(
+            numeric(Value::Int(6), NumericOp::BitXor, Value::Int(3)),
+            Ok(Value::Int(5))
+        ));
+        assert_eq!(
+            numeric(Value::Int(1), NumericOp::ShiftLeft, Value::Int(64)),
+            Err(NumericError::Overflow)
+        );
+        assert_eq!(
+            numeric(Value::Float(1.0), NumericOp::BitOr, Value::Int(2)),
+            Err(NumericError::NotANumber)
+        );
+        assert_eq!(Value::Int(i64::MIN).negate(), Err(NumericError::Overflow));
+        assert_eq!(Value::Int(1), Value::Float(1.0));
+    }
+}
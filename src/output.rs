@@ -0,0 +1,113 @@
+use miette::SourceSpan;
+
+use crate::{diag::Diag, errors::RuntimeError, interpreter::Value, source::SourceFile};
+
+/// Limits on how much a script can print, guarding the host against scripts
+/// that print gigabytes, e.g. in CI or when embedded. Sizes are in bytes,
+/// including the newline `print` ends with, and `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OutputLimits {
+    /// The most a script can print in total.
+    pub max_output: Option<usize>,
+    /// The most a single `print` can write.
+    pub max_print: Option<usize>,
+}
+
+/// A limit of `OutputLimits` that a `print` would exceed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Exceeded {
+    Print(usize),
+    Output(usize),
+}
+
+impl Exceeded {
+    /// The error of the `print` at `span` exceeding the limit.
+    pub(crate) fn error(self, source: &SourceFile, span: SourceSpan) -> RuntimeError {
+        let (limit, what) = match self {
+            Exceeded::Print(limit) => (limit, "at once"),
+            Exceeded::Output(limit) => (limit, "in total"),
+        };
+        Diag::error("runtime::output_limit_exceeded")
+            .arg("limit", limit)
+            .arg("what", what)
+            .msg("Printing more than the {limit} bytes allowed {what}")
+            .source(source)
+            .primary(span, "printed here")
+            .help("the host running the script limits how much it can print")
+            .into()
+    }
+}
+
+/// The standard output `print` writes to, keeping track of how much was
+/// written.
+#[derive(Debug, Default)]
+pub(crate) struct Output {
+    limits: OutputLimits,
+    written: usize,
+    /// What was printed since capturing started, if it did, rather than
+    /// writing it to the standard output.
+    captured: Option<String>,
+}
+
+impl Output {
+    pub(crate) fn set_limits(&mut self, limits: OutputLimits) {
+        self.limits = limits;
+    }
+
+    /// Prints `value` on a line of its own, unless that would exceed one of
+    /// the limits, in which case nothing is printed.
+    pub(crate) fn print(&mut self, value: &Value) -> Result<(), Exceeded> {
+        let line = format!("{value}\n");
+        if let Some(max) = self.limits.max_print.filter(|&max| line.len() > max) {
+            return Err(Exceeded::Print(max));
+        }
+        let written = self.written + line.len();
+        if let Some(max) = self.limits.max_output.filter(|&max| written > max) {
+            return Err(Exceeded::Output(max));
+        }
+        self.written = written;
+        match &mut self.captured {
+            Some(captured) => captured.push_str(&line),
+            None => print!("{line}"),
+        }
+        Ok(())
+    }
+
+    /// Keeps what is printed from now on rather than writing it, until taken
+    /// with `Output::take_captured`.
+    pub(crate) fn capture(&mut self) {
+        self.captured.get_or_insert_with(String::new);
+    }
+
+    /// Returns what was printed since capturing started or it was last taken.
+    pub(crate) fn take_captured(&mut self) -> String {
+        self.captured
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::Value;
+
+    use super::{Exceeded, Output, OutputLimits};
+
+    #[test]
+    fn check_limits() {
+        let mut output = Output::default();
+        output.set_limits(OutputLimits {
+            max_output: Some(10),
+            max_print: Some(6),
+        });
+        assert_eq!(output.print(&Value::String("fox".to_string())), Ok(()));
+        assert_eq!(
+            output.print(&Value::String("foxes!".to_string())),
+            Err(Exceeded::Print(6))
+        );
+        assert_eq!(output.print(&Value::Float(12.5)), Ok(()));
+        assert_eq!(output.print(&Value::Nil), Err(Exceeded::Output(10)));
+        assert_eq!(output.print(&Value::Bool(true)), Err(Exceeded::Output(10)));
+    }
+}
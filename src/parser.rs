@@ -0,0 +1,1268 @@
+use std::{path::Path, rc::Rc, str::FromStr};
+
+use crate::{
+    diag::Diag,
+    errors::ParseError,
+    expr::{
+        AssignExpr, BinaryExpr, CallExpr, ConditionalExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+        IndexSetExpr, InterpolationExpr, ListExpr, Literal, LogicalExpr, MapEntry, MapExpr,
+        MatchArm, MatchExpr, Pattern, SetExpr, SuperExpr, ThisExpr, TryExpr, UnaryExpr,
+        VariableExpr,
+    },
+    lexer::{Keyword, Position, Token, TokenType},
+    source::{SourceFile, Span},
+    stmt::{
+        ClassStmt, ExportStmt, ForInStmt, ForStmt, FunctionStmt, GuardStmt, IfStmt, ImportStmt,
+        LetStmt, PrintStmt, ReturnStmt, Stmt, ThrowStmt, TryStmt, WhileStmt,
+    },
+};
+
+/// Recursive descent parser turning the tokens produced by the `Lexer` into
+/// a program, i.e. a list of `Stmt`s.
+///
+/// Expression precedence, from lowest to highest: assignment, conditional,
+/// logical or, logical and, equality, comparison, range, bitwise or,
+/// bitwise xor, bitwise and, shift, term, factor, unary, power, call and
+/// primary. Assignment, conditionals and powers are right associative, all
+/// other binary operators are left associative.
+///
+/// On a syntax error the parser records it, skips ahead to the next
+/// statement boundary and carries on, so every error in a file gets reported
+/// at once.
+pub struct Parser<'a> {
+    source: &'a SourceFile,
+    tokens: Vec<Token>,
+    current: usize,
+    /// Number of `{ ... }` blocks currently being parsed.
+    blocks: usize,
+    errors: Vec<ParseError>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a SourceFile, mut tokens: Vec<Token>) -> Self {
+        if !matches!(
+            tokens.last(),
+            Some(Token {
+                ty: TokenType::Eof,
+                ..
+            })
+        ) {
+            let end = tokens.last().map_or(0, |t| t.position.end);
+            let line = tokens.last().map_or(1, |t| t.position.line);
+            let column = tokens.last().map_or(1, |t| {
+                let text = &source.text()[t.position.start..t.position.end];
+                t.position.column + text.chars().count()
+            });
+            tokens.push(Token {
+                ty: TokenType::Eof,
+                position: Position {
+                    line,
+                    column,
+                    start: end,
+                    end,
+                },
+            });
+        }
+        Parser {
+            source,
+            tokens,
+            current: 0,
+            blocks: 0,
+            errors: vec![],
+        }
+    }
+
+    /// Parses the whole program, returning every error found.
+    pub fn parse(mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut stmts = vec![];
+        while !self.is_at_end() {
+            stmts.extend(self.recovering_declaration());
+        }
+        if self.errors.is_empty() {
+            Ok(stmts)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// Parses a declaration, or records the error and synchronizes if it is
+    /// malformed.
+    fn recovering_declaration(&mut self) -> Option<Stmt> {
+        match self.declaration() {
+            Ok(stmt) => Some(stmt),
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    /// Discards tokens until the start of what is likely the next statement:
+    /// right after a `;`, or before a keyword starting a statement or the `}`
+    /// closing the enclosing block.
+    fn synchronize(&mut self) {
+        if self.blocks > 0 && self.check(&TokenType::RightBrace) {
+            return;
+        }
+        while !self.is_at_end() {
+            if self.advance().ty == TokenType::Semicolon {
+                return;
+            }
+            match self.peek().ty {
+                TokenType::Keyword(
+                    Keyword::Class
+                    | Keyword::Fn
+                    | Keyword::Let
+                    | Keyword::Import
+                    | Keyword::Export
+                    | Keyword::For
+                    | Keyword::Guard
+                    | Keyword::If
+                    | Keyword::While
+                    | Keyword::Print
+                    | Keyword::Return
+                    | Keyword::Break
+                    | Keyword::Continue
+                    | Keyword::Throw
+                    | Keyword::Try,
+                ) => return,
+                TokenType::RightBrace if self.blocks > 0 => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn declaration(&mut self) -> Result<Stmt, ParseError> {
+        match self.peek().ty {
+            TokenType::Keyword(Keyword::Class) => {
+                self.advance();
+                self.class_declaration()
+            }
+            TokenType::Keyword(Keyword::Fn) => {
+                self.advance();
+                Ok(Stmt::Function(Rc::new(self.function("function")?)))
+            }
+            TokenType::Keyword(Keyword::Let) => {
+                self.advance();
+                self.let_declaration()
+            }
+            TokenType::Keyword(Keyword::Export) => {
+                let keyword = self.advance();
+                let TokenType::Keyword(Keyword::Class | Keyword::Fn | Keyword::Let) =
+                    self.peek().ty
+                else {
+                    return Err(self.unexpected("`class`, `fn` or `let` after `export`"));
+                };
+                let declaration = Box::new(self.declaration()?);
+                Ok(Stmt::Export(ExportStmt {
+                    keyword,
+                    declaration,
+                }))
+            }
+            _ => self.statement(),
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.identifier("class name")?;
+        let superclass = if self.match_any(&[TokenType::Less]).is_some() {
+            let name = self.identifier("superclass name")?;
+            Some(VariableExpr {
+                name,
+                depth: Default::default(),
+            })
+        } else {
+            None
+        };
+        self.consume(TokenType::LeftBrace, "`{` before class body")?;
+        let mut methods = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            self.consume(TokenType::Keyword(Keyword::Fn), "`fn` or `}` in class body")?;
+            methods.push(Rc::new(self.function("method")?));
+        }
+        self.consume(TokenType::RightBrace, "`}` after class body")?;
+        Ok(Stmt::Class(ClassStmt {
+            name,
+            superclass,
+            methods,
+        }))
+    }
+
+    fn function(&mut self, kind: &str) -> Result<FunctionStmt, ParseError> {
+        let name = self.identifier(&format!("{kind} name"))?;
+        let paren = self.consume(TokenType::LeftParen, &format!("`(` after {kind} name"))?;
+        let mut params = vec![];
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                params.push(self.identifier("parameter name")?);
+                if self.match_any(&[TokenType::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        self.close(&paren, "`)` after parameters")?;
+        self.consume(TokenType::LeftBrace, &format!("`{{` before {kind} body"))?;
+        let body = self.block()?;
+        Ok(FunctionStmt { name, params, body })
+    }
+
+    fn let_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.identifier("variable name")?;
+        let initializer = if self.match_any(&[TokenType::Equal]).is_some() {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, "`;` after variable declaration")?;
+        Ok(Stmt::Let(LetStmt { name, initializer }))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        match self.peek().ty {
+            TokenType::Keyword(Keyword::For) => {
+                self.advance();
+                self.for_statement()
+            }
+            TokenType::Keyword(Keyword::Guard) => {
+                let keyword = self.advance();
+                let condition = self.expression()?;
+                self.consume(
+                    TokenType::Keyword(Keyword::Else),
+                    "`else` after guard condition",
+                )?;
+                let else_branch = Box::new(self.block_statement("else body")?);
+                Ok(Stmt::Guard(GuardStmt {
+                    keyword,
+                    condition,
+                    else_branch,
+                }))
+            }
+            TokenType::Keyword(Keyword::If) => {
+                self.advance();
+                self.if_statement()
+            }
+            TokenType::Keyword(Keyword::Import) => {
+                let keyword = self.advance();
+                self.import_statement(keyword)
+            }
+            TokenType::Keyword(Keyword::Print) => {
+                let keyword = self.advance();
+                let value = self.expression()?;
+                self.consume(TokenType::Semicolon, "`;` after value")?;
+                Ok(Stmt::Print(PrintStmt { keyword, value }))
+            }
+            TokenType::Keyword(Keyword::Return) => {
+                let keyword = self.advance();
+                self.return_statement(keyword)
+            }
+            TokenType::Keyword(Keyword::Break) => {
+                let keyword = self.advance();
+                self.consume(TokenType::Semicolon, "`;` after `break`")?;
+                Ok(Stmt::Break(keyword))
+            }
+            TokenType::Keyword(Keyword::Continue) => {
+                let keyword = self.advance();
+                self.consume(TokenType::Semicolon, "`;` after `continue`")?;
+                Ok(Stmt::Continue(keyword))
+            }
+            TokenType::Keyword(Keyword::Throw) => {
+                let keyword = self.advance();
+                let value = self.expression()?;
+                self.consume(TokenType::Semicolon, "`;` after thrown value")?;
+                Ok(Stmt::Throw(ThrowStmt { keyword, value }))
+            }
+            // Otherwise it starts a `try` expression.
+            TokenType::Keyword(Keyword::Try)
+                if self.tokens[self.current + 1].ty == TokenType::LeftBrace =>
+            {
+                self.advance();
+                self.try_statement()
+            }
+            TokenType::Keyword(Keyword::While) => {
+                self.advance();
+                self.while_statement()
+            }
+            TokenType::LeftBrace if !self.starts_map() => {
+                self.advance();
+                Ok(Stmt::Block(self.block()?))
+            }
+            _ => self.expression_statement(),
+        }
+    }
+
+    /// Parses the C-style `for init; condition; increment { ... }` loop,
+    /// whose three clauses may each be left empty, or the `for name in
+    /// iterable { ... }` loop.
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let next = self.tokens.get(self.current + 1).map(|token| &token.ty);
+        if let (TokenType::Identifier(_), Some(TokenType::Keyword(Keyword::In))) =
+            (&self.peek().ty, next)
+        {
+            let name = self.advance();
+            self.advance();
+            let (iterable, iterable_position) = self.positioned_expression()?;
+            let body = Box::new(self.block_statement("loop body")?);
+            return Ok(Stmt::ForIn(ForInStmt {
+                name,
+                iterable,
+                iterable_position,
+                body,
+            }));
+        }
+        let initializer = match self.peek().ty {
+            TokenType::Semicolon => {
+                self.advance();
+                None
+            }
+            TokenType::Keyword(Keyword::Let) => {
+                self.advance();
+                Some(Box::new(self.let_declaration()?))
+            }
+            _ => Some(Box::new(self.expression_statement()?)),
+        };
+        let condition = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "`;` after loop condition")?;
+        let increment = if self.check(&TokenType::LeftBrace) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let body = Box::new(self.block_statement("loop body")?);
+        Ok(Stmt::For(ForStmt {
+            initializer,
+            condition,
+            increment,
+            body,
+        }))
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let condition = self.expression()?;
+        let then_branch = Box::new(self.block_statement("if body")?);
+        let else_branch = if self
+            .match_any(&[TokenType::Keyword(Keyword::Else)])
+            .is_some()
+        {
+            if self.match_any(&[TokenType::Keyword(Keyword::If)]).is_some() {
+                Some(Box::new(self.if_statement()?))
+            } else {
+                Some(Box::new(self.block_statement("else body")?))
+            }
+        } else {
+            None
+        };
+        Ok(Stmt::If(IfStmt {
+            condition,
+            then_branch,
+            else_branch,
+        }))
+    }
+
+    /// Parses what follows `import`: either the name of a module next to the
+    /// importing file or the path to one, whose file name must be a valid
+    /// variable name to bind it to.
+    fn import_statement(&mut self, keyword: Token) -> Result<Stmt, ParseError> {
+        let (path, name) = match &self.peek().ty {
+            TokenType::Identifier(name) => (format!("{name}.fox"), self.peek().clone()),
+            TokenType::String(path) => {
+                let token = self.peek();
+                let stem = Path::new(path)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or_default();
+                let mut chars = stem.chars();
+                let is_identifier = chars
+                    .next()
+                    .is_some_and(|first| first.is_alphabetic() || first == '_')
+                    && chars.all(|ch| ch.is_alphanumeric() || ch == '_')
+                    && Keyword::from_str(stem).is_err();
+                if !is_identifier {
+                    return Err(Diag::error("parse::invalid_module_name")
+                        .arg("name", stem)
+                        .msg("Can't import `{name}` as a variable")
+                        .source(self.source)
+                        .primary(token.position.span(), "imported here")
+                        .help("rename the file so that its name is a valid variable name")
+                        .into());
+                }
+                let name = Token {
+                    ty: TokenType::Identifier(stem.to_string()),
+                    position: token.position.clone(),
+                };
+                (path.to_string(), name)
+            }
+            _ => return Err(self.unexpected("module name or path after `import`")),
+        };
+        let path_position = self.advance().position;
+        self.consume(TokenType::Semicolon, "`;` after import")?;
+        Ok(Stmt::Import(ImportStmt {
+            keyword,
+            path,
+            path_position,
+            name,
+        }))
+    }
+
+    fn return_statement(&mut self, keyword: Token) -> Result<Stmt, ParseError> {
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "`;` after return value")?;
+        Ok(Stmt::Return(ReturnStmt { keyword, value }))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftBrace, "`{` before try body")?;
+        let body = self.block()?;
+        self.consume(TokenType::Keyword(Keyword::Catch), "`catch` after try body")?;
+        let name = self.identifier("error variable name")?;
+        self.consume(TokenType::LeftBrace, "`{` before catch body")?;
+        let handler = self.block()?;
+        Ok(Stmt::Try(TryStmt {
+            body,
+            name,
+            handler,
+        }))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let condition = self.expression()?;
+        let body = Box::new(self.block_statement("loop body")?);
+        Ok(Stmt::While(WhileStmt { condition, body }))
+    }
+
+    /// Parses a `{ ... }` block that is mandatory in control flow statements.
+    fn block_statement(&mut self, kind: &str) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftBrace, &format!("`{{` before {kind}"))?;
+        Ok(Stmt::Block(self.block()?))
+    }
+
+    /// Parses the declarations of a block whose `{` has already been consumed.
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut stmts = vec![];
+        self.blocks += 1;
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            stmts.extend(self.recovering_declaration());
+        }
+        self.blocks -= 1;
+        self.consume(TokenType::RightBrace, "`}` after block")?;
+        Ok(stmts)
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "`;` after expression")?;
+        Ok(Stmt::Expression(expr))
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.assignment()
+    }
+
+    /// Parses an assignment. A compound assignment like `a.b += 1` is
+    /// desugared to `a.b = a.b + 1`, so the target's object is evaluated
+    /// twice.
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.conditional()?;
+        let operators = [
+            TokenType::Equal,
+            TokenType::PlusEq,
+            TokenType::MinusEq,
+            TokenType::StarEq,
+            TokenType::SlashEq,
+            TokenType::PercentEq,
+        ];
+        if let Some(equals) = self.match_any(&operators) {
+            let mut value = self.assignment()?;
+            let operator = match equals.ty {
+                TokenType::PlusEq => Some(TokenType::Plus),
+                TokenType::MinusEq => Some(TokenType::Minus),
+                TokenType::StarEq => Some(TokenType::Star),
+                TokenType::SlashEq => Some(TokenType::Slash),
+                TokenType::PercentEq => Some(TokenType::Percent),
+                _ => None,
+            };
+            if let Some(ty) = operator {
+                if let Expr::Variable(_) | Expr::Get(_) | Expr::Index(_) = expr {
+                    value = Expr::Binary(BinaryExpr {
+                        lhs: Box::new(expr.clone()),
+                        operator: Token {
+                            ty,
+                            position: equals.position.clone(),
+                        },
+                        rhs: Box::new(value),
+                    });
+                }
+            }
+            return match expr {
+                Expr::Variable(var) => Ok(Expr::Assign(AssignExpr {
+                    name: var.name,
+                    value: Box::new(value),
+                    depth: Default::default(),
+                })),
+                Expr::Get(get) => Ok(Expr::Set(SetExpr {
+                    object: get.object,
+                    name: get.name,
+                    value: Box::new(value),
+                })),
+                Expr::Index(index) => Ok(Expr::IndexSet(IndexSetExpr {
+                    object: index.object,
+                    bracket: index.bracket,
+                    index: index.index,
+                    index_position: index.index_position,
+                    value: Box::new(value),
+                })),
+                _ => Err(Diag::error("parse::invalid_assignment_target")
+                    .msg("Invalid assignment target")
+                    .source(self.source)
+                    .primary(equals.position.span(), "cannot assign with this `=`")
+                    .help("only variables and properties can be assigned to")
+                    .into()),
+            };
+        }
+        Ok(expr)
+    }
+
+    /// Parses `condition ? then : else`, which is right associative. The
+    /// then branch can be any expression, as it ends at the `:`.
+    fn conditional(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.logic_or()?;
+        let Some(question) = self.match_any(&[TokenType::Question]) else {
+            return Ok(condition);
+        };
+        let then_branch = self.expression()?;
+        if self.match_any(&[TokenType::Colon]).is_none() {
+            let token = self.peek();
+            return Err(Diag::error("parse::missing_conditional_colon")
+                .arg("found", token.lexeme())
+                .msg("Expected `:` in conditional expression, found `{found}`")
+                .source(self.source)
+                .primary(token.position.span(), "expected `:` here")
+                .secondary(question.position.span(), "to match this `?`")
+                .help("conditional expressions are written `condition ? then : else`")
+                .into());
+        }
+        let else_branch = self.conditional()?;
+        Ok(Expr::Conditional(ConditionalExpr {
+            condition: Box::new(condition),
+            question,
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        }))
+    }
+
+    fn logic_or(&mut self) -> Result<Expr, ParseError> {
+        self.logical(Self::logic_and, &[TokenType::Keyword(Keyword::Or)])
+    }
+
+    fn logic_and(&mut self) -> Result<Expr, ParseError> {
+        self.logical(Self::equality, &[TokenType::Keyword(Keyword::And)])
+    }
+
+    /// Like `binary`, for the operators which short-circuit.
+    fn logical<F>(&mut self, operand: F, operators: &[TokenType]) -> Result<Expr, ParseError>
+    where
+        F: Fn(&mut Self) -> Result<Expr, ParseError>,
+    {
+        let mut expr = operand(self)?;
+        while let Some(operator) = self.match_any(operators) {
+            let rhs = operand(self)?;
+            expr = Expr::Logical(LogicalExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(rhs),
+            });
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, ParseError> {
+        self.binary(Self::comparison, &[TokenType::BangEq, TokenType::EqualEq])
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        self.binary(
+            Self::range,
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEq,
+                TokenType::Less,
+                TokenType::LessEq,
+            ],
+        )
+    }
+
+    fn range(&mut self) -> Result<Expr, ParseError> {
+        self.binary(Self::bit_or, &[TokenType::DotDot, TokenType::DotDotEq])
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        self.binary(Self::bit_xor, &[TokenType::Pipe])
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, ParseError> {
+        self.binary(Self::bit_and, &[TokenType::Caret])
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        self.binary(Self::shift, &[TokenType::Ampersand])
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        self.binary(
+            Self::term,
+            &[TokenType::LessLess, TokenType::GreaterGreater],
+        )
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        self.binary(Self::factor, &[TokenType::Minus, TokenType::Plus])
+    }
+
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        self.binary(
+            Self::unary,
+            &[TokenType::Slash, TokenType::Star, TokenType::Percent],
+        )
+    }
+
+    /// Parses a left associative chain of `operand (operator operand)*`.
+    fn binary<F>(&mut self, operand: F, operators: &[TokenType]) -> Result<Expr, ParseError>
+    where
+        F: Fn(&mut Self) -> Result<Expr, ParseError>,
+    {
+        let mut expr = operand(self)?;
+        while let Some(operator) = self.match_any(operators) {
+            let rhs = operand(self)?;
+            expr = Expr::Binary(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(rhs),
+            });
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if let Some(operator) = self.match_any(&[TokenType::Bang, TokenType::Minus]) {
+            let rhs = self.unary()?;
+            return Ok(Expr::Unary(UnaryExpr {
+                operator,
+                rhs: Box::new(rhs),
+            }));
+        }
+        if let Some(keyword) = self.match_any(&[TokenType::Keyword(Keyword::Try)]) {
+            let expr = self.unary()?;
+            return Ok(Expr::Try(TryExpr {
+                keyword,
+                expr: Box::new(expr),
+            }));
+        }
+        self.power()
+    }
+
+    /// Parses `**`, which binds tighter than a unary operator on its left,
+    /// so that `-2 ** 2` is `-4`, and is right associative.
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.call()?;
+        if let Some(operator) = self.match_any(&[TokenType::StarStar]) {
+            let rhs = self.unary()?;
+            return Ok(Expr::Binary(BinaryExpr {
+                lhs: Box::new(expr),
+                operator,
+                rhs: Box::new(rhs),
+            }));
+        }
+        Ok(expr)
+    }
+
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        let postfix = [TokenType::LeftParen, TokenType::Dot, TokenType::LeftBracket];
+        while let Some(token) = self.match_any(&postfix) {
+            if token.ty == TokenType::LeftBracket {
+                let (index, index_position) = self.positioned_expression()?;
+                self.close(&token, "`]` after index")?;
+                expr = Expr::Index(IndexExpr {
+                    object: Box::new(expr),
+                    bracket: token,
+                    index: Box::new(index),
+                    index_position,
+                });
+                continue;
+            }
+            if token.ty == TokenType::Dot {
+                let name = self.identifier("property name after `.`")?;
+                expr = Expr::Get(GetExpr {
+                    object: Box::new(expr),
+                    name,
+                });
+                continue;
+            }
+            let mut arguments = vec![];
+            if !self.check(&TokenType::RightParen) {
+                loop {
+                    arguments.push(self.expression()?);
+                    if self.match_any(&[TokenType::Comma]).is_none() {
+                        break;
+                    }
+                }
+            }
+            let paren = self.close(&token, "`)` after arguments")?;
+            expr = Expr::Call(CallExpr {
+                callee: Box::new(expr),
+                paren,
+                arguments,
+            });
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let literal = match &self.peek().ty {
+            TokenType::Int(int) => Literal::Int(*int),
+            TokenType::Number(num) => Literal::Number(Some(*num)),
+            TokenType::String(str) => Literal::String(Some(str.to_string())),
+            TokenType::Keyword(Keyword::True) => Literal::Bool(true),
+            TokenType::Keyword(Keyword::False) => Literal::Bool(false),
+            TokenType::Keyword(Keyword::Nil) => Literal::Nil,
+            TokenType::Keyword(Keyword::This) => {
+                let keyword = self.advance();
+                return Ok(Expr::This(ThisExpr {
+                    keyword,
+                    depth: Default::default(),
+                }));
+            }
+            TokenType::Keyword(Keyword::Super) => {
+                let keyword = self.advance();
+                self.consume(TokenType::Dot, "`.` after `super`")?;
+                let method = self.identifier("superclass method name")?;
+                return Ok(Expr::Super(SuperExpr {
+                    keyword,
+                    method,
+                    depth: Default::default(),
+                }));
+            }
+            TokenType::Identifier(_) => {
+                let name = self.advance();
+                return Ok(Expr::Variable(VariableExpr {
+                    name,
+                    depth: Default::default(),
+                }));
+            }
+            TokenType::Interpolation(_) => return self.interpolation(),
+            TokenType::Keyword(Keyword::Match) => return self.match_expression(),
+            TokenType::LeftBracket => return self.list(),
+            TokenType::LeftBrace => return self.map(),
+            TokenType::LeftParen => {
+                let paren = self.advance();
+                let expr = self.expression()?;
+                self.close(&paren, "`)` after expression")?;
+                return Ok(Expr::Grouping(GroupingExpr {
+                    expr: Box::new(expr),
+                }));
+            }
+            _ => {
+                let token = self.peek();
+                return Err(Diag::error("parse::expected_expression")
+                    .arg("found", token.lexeme())
+                    .msg("Expected an expression, found `{found}`")
+                    .source(self.source)
+                    .primary(token.position.span(), "expression expected here")
+                    .help("expressions are literals, unary or binary operations, or parenthesized groups")
+                    .into());
+            }
+        };
+        self.advance();
+        Ok(Expr::Literal(literal))
+    }
+
+    /// Whether the `{` at the start of a statement opens a map literal rather
+    /// than a block, which is when it is followed by a literal and a `:`.
+    fn starts_map(&self) -> bool {
+        let key = self.tokens.get(self.current + 1).map(|token| &token.ty);
+        let colon = self.tokens.get(self.current + 2).map(|token| &token.ty);
+        matches!(
+            key,
+            Some(
+                TokenType::String(_)
+                    | TokenType::Int(_)
+                    | TokenType::Number(_)
+                    | TokenType::Keyword(Keyword::True | Keyword::False | Keyword::Nil)
+            )
+        ) && colon == Some(&TokenType::Colon)
+    }
+
+    /// Parses a map literal, whose entries can be followed by a trailing
+    /// comma.
+    fn map(&mut self) -> Result<Expr, ParseError> {
+        let brace = self.advance();
+        let mut entries = vec![];
+        while !self.check(&TokenType::RightBrace) {
+            let (key, key_position) = self.positioned_expression()?;
+            self.consume(TokenType::Colon, "`:` after map key")?;
+            let value = self.expression()?;
+            entries.push(MapEntry {
+                key,
+                key_position,
+                value,
+            });
+            if self.match_any(&[TokenType::Comma]).is_none() {
+                break;
+            }
+        }
+        self.close(&brace, "`,` or `}` after map entry")?;
+        Ok(Expr::Map(MapExpr { brace, entries }))
+    }
+
+    /// Parses an expression along with where it is in the source, for errors
+    /// about its value.
+    fn positioned_expression(&mut self) -> Result<(Expr, Position), ParseError> {
+        let first = self.peek().position.clone();
+        let expr = self.expression()?;
+        let position = first.join(&self.tokens[self.current - 1].position);
+        Ok((expr, position))
+    }
+
+    /// Parses a list literal, whose elements can be followed by a trailing
+    /// comma.
+    fn list(&mut self) -> Result<Expr, ParseError> {
+        let bracket = self.advance();
+        let mut elements = vec![];
+        while !self.check(&TokenType::RightBracket) {
+            elements.push(self.expression()?);
+            if self.match_any(&[TokenType::Comma]).is_none() {
+                break;
+            }
+        }
+        self.close(&bracket, "`,` or `]` after list element")?;
+        Ok(Expr::List(ListExpr { bracket, elements }))
+    }
+
+    /// Parses a `match` expression, whose arms can be followed by a trailing
+    /// comma.
+    fn match_expression(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.advance();
+        let (subject, subject_position) = self.positioned_expression()?;
+        let brace = self.consume(TokenType::LeftBrace, "`{` after match subject")?;
+        let mut arms = vec![];
+        while !self.check(&TokenType::RightBrace) {
+            let pattern = self.pattern()?;
+            let guard = match self.match_any(&[TokenType::Keyword(Keyword::If)]) {
+                Some(_) => Some(self.expression()?),
+                None => None,
+            };
+            self.consume(TokenType::FatArrow, "`=>` after pattern")?;
+            let body = self.expression()?;
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+            if self.match_any(&[TokenType::Comma]).is_none() {
+                break;
+            }
+        }
+        self.close(&brace, "`,` or `}` after match arm")?;
+        Ok(Expr::Match(MatchExpr {
+            keyword,
+            subject: Box::new(subject),
+            subject_position,
+            arms,
+        }))
+    }
+
+    /// Parses the pattern of a `match` arm: a literal, which can be a
+    /// negative number, `_` or a name to bind.
+    fn pattern(&mut self) -> Result<Pattern, ParseError> {
+        let minus = self.match_any(&[TokenType::Minus]);
+        let token = self.peek().clone();
+        let value = match (&token.ty, &minus) {
+            (TokenType::Int(int), Some(_)) => Literal::Int(-int),
+            (TokenType::Int(int), None) => Literal::Int(*int),
+            (TokenType::Number(num), Some(_)) => Literal::Number(Some(-num)),
+            (TokenType::Number(num), None) => Literal::Number(Some(*num)),
+            (TokenType::String(str), None) => Literal::String(Some(str.to_string())),
+            (TokenType::Keyword(Keyword::True), None) => Literal::Bool(true),
+            (TokenType::Keyword(Keyword::False), None) => Literal::Bool(false),
+            (TokenType::Keyword(Keyword::Nil), None) => Literal::Nil,
+            (TokenType::Identifier(name), None) => {
+                self.advance();
+                return Ok(match name.as_str() {
+                    "_" => Pattern::Wildcard(token),
+                    _ => Pattern::Binding(token),
+                });
+            }
+            (_, Some(_)) => return Err(self.unexpected("number after `-` in pattern")),
+            (_, None) => return Err(self.unexpected("pattern")),
+        };
+        self.advance();
+        let position = match minus {
+            Some(minus) => minus.position.join(&token.position),
+            None => token.position,
+        };
+        Ok(Pattern::Literal { value, position })
+    }
+
+    /// Parses an interpolated string, starting at the token up to its first
+    /// `${`. The lexer ends each embedded expression with a token holding
+    /// the string after its `}`.
+    fn interpolation(&mut self) -> Result<Expr, ParseError> {
+        let start = self.advance();
+        let mut parts = vec![];
+        let mut segment = start.ty.clone();
+        loop {
+            let (string, is_end) = match segment {
+                TokenType::Interpolation(string) => (string, false),
+                TokenType::InterpolationEnd(string) => (string, true),
+                _ => unreachable!(),
+            };
+            if !string.is_empty() {
+                parts.push(Expr::Literal(Literal::String(Some(string))));
+            }
+            if is_end {
+                break;
+            }
+            parts.push(self.expression()?);
+            segment = match self.peek().ty {
+                TokenType::Interpolation(_) | TokenType::InterpolationEnd(_) => self.advance().ty,
+                _ => return Err(self.unexpected("`}` after the interpolated expression")),
+            };
+        }
+        Ok(Expr::Interpolation(InterpolationExpr { start, parts }))
+    }
+
+    fn match_any(&mut self, types: &[TokenType]) -> Option<Token> {
+        if types.contains(&self.peek().ty) {
+            Some(self.advance())
+        } else {
+            None
+        }
+    }
+
+    fn check(&self, ty: &TokenType) -> bool {
+        &self.peek().ty == ty
+    }
+
+    fn consume(&mut self, ty: TokenType, expected: &str) -> Result<Token, ParseError> {
+        if self.check(&ty) {
+            Ok(self.advance())
+        } else if ty == TokenType::Semicolon && self.current > 0 {
+            let found = self.peek();
+            let found_span = found.position.span();
+            // The gap ends where the next token starts, which may be lines
+            // below, so only its start is pointed at.
+            let gap = self.tokens[self.current - 1]
+                .position
+                .span()
+                .between(&found_span);
+            Err(Diag::error("parse::missing_semicolon")
+                .arg("expected", expected)
+                .arg("found", found.lexeme())
+                .msg("Expected {expected}, found `{found}`")
+                .source(self.source)
+                .primary((gap.offset(), 0).into(), "expected {expected} here")
+                .secondary(found_span, "found `{found}` instead")
+                .into())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    /// Consumes the token closing the `(`, `[` or `{` token `open`, which
+    /// the error points back at if it is missing.
+    fn close(&mut self, open: &Token, expected: &'static str) -> Result<Token, ParseError> {
+        let (ty, delimiter, closing) = match open.ty {
+            TokenType::LeftParen => (TokenType::RightParen, '(', ')'),
+            TokenType::LeftBracket => (TokenType::RightBracket, '[', ']'),
+            _ => (TokenType::RightBrace, '{', '}'),
+        };
+        if self.check(&ty) {
+            return Ok(self.advance());
+        }
+        let found = self.peek();
+        Err(Diag::error("parse::unclosed")
+            .arg("expected", expected)
+            .arg("found", found.lexeme())
+            .arg("delimiter", delimiter)
+            .arg("closing", closing)
+            .msg("Expected {expected}, found `{found}`")
+            .source(self.source)
+            .primary(found.position.span(), "expected {expected} here")
+            .secondary(open.position.span(), "to match this `{delimiter}`")
+            .help("every `{delimiter}` needs a matching `{closing}`")
+            .into())
+    }
+
+    fn identifier(&mut self, expected: &str) -> Result<Token, ParseError> {
+        if let TokenType::Identifier(_) = self.peek().ty {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().ty == TokenType::Eof
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let token = self.peek();
+        Diag::error("parse::unexpected_token")
+            .arg("expected", expected)
+            .arg("found", token.lexeme())
+            .msg("Expected {expected}, found `{found}`")
+            .source(self.source)
+            .primary(token.position.span(), "expected {expected} here")
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{errors::ParseError, lexer::Lexer, source::SourceFile};
+
+    use super::Parser;
+
+    fn parse(source: &str) -> Result<String, Vec<ParseError>> {
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse()?;
+        Ok(stmts
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    #[test]
+    fn check_precedence() {
+        assert_eq!(parse("1 + 2 * 3;").unwrap(), "(; (+ 1 (* 2 3)))");
+        assert_eq!(parse("1 * 2 + 3;").unwrap(), "(; (+ (* 1 2) 3))");
+        assert_eq!(
+            parse("1 < 2 == 3 >= 4;").unwrap(),
+            "(; (== (< 1 2) (>= 3 4)))"
+        );
+        assert_eq!(parse("-1 * !true;").unwrap(), "(; (* (- 1) (! true)))");
+        assert_eq!(parse("-2 ** 2 % 3;").unwrap(), "(; (% (- (** 2 2)) 3))");
+        assert_eq!(
+            parse("1 | 2 ^ 3 & 4 << 5 + 6;").unwrap(),
+            "(; (| 1 (^ 2 (& 3 (<< 4 (+ 5 6))))))"
+        );
+        assert_eq!(parse("a & 1 == 0;").unwrap(), "(; (== (& a 1) 0))");
+        assert_eq!(
+            parse("a = b == 1 ? c = 2 : 3;").unwrap(),
+            "(; (= a (?: (== b 1) (= c 2) 3)))"
+        );
+        assert_eq!(
+            parse("a or b and c == d ? 1 : 2;").unwrap(),
+            "(; (?: (or a (and b (== c d))) 1 2))"
+        );
+    }
+
+    #[test]
+    fn check_associativity() {
+        assert_eq!(parse("1 - 2 - 3;").unwrap(), "(; (- (- 1 2) 3))");
+        assert_eq!(parse("8 / 4 / 2;").unwrap(), "(; (/ (/ 8 4) 2))");
+        assert_eq!(parse("!!false;").unwrap(), "(; (! (! false)))");
+        assert_eq!(parse("2 ** 3 ** 2;").unwrap(), "(; (** 2 (** 3 2)))");
+        assert_eq!(
+            parse("a ? b : c ? d : e;").unwrap(),
+            "(; (?: a b (?: c d e)))"
+        );
+        assert_eq!(
+            parse("a ? b ? c : d : e;").unwrap(),
+            "(; (?: a (?: b c d) e))"
+        );
+        assert_eq!(parse("2 ** -1;").unwrap(), "(; (** 2 (- 1)))");
+        assert_eq!(parse("1 >> 2 << 3;").unwrap(), "(; (<< (>> 1 2) 3))");
+        assert_eq!(parse("a = b = 1;").unwrap(), "(; (= a (= b 1)))");
+        assert_eq!(
+            parse("a += b *= 2;").unwrap(),
+            "(; (= a (+ a (= b (* b 2)))))"
+        );
+    }
+
+    #[test]
+    fn check_calls() {
+        assert_eq!(parse("f();").unwrap(), "(; (call f))");
+        assert_eq!(
+            parse("f(1, a + b)(c);").unwrap(),
+            "(; (call (call f 1 (+ a b)) c))"
+        );
+        assert_eq!(parse("-f(1) * 2;").unwrap(), "(; (* (- (call f 1)) 2))");
+        assert_eq!(
+            parse("let a = try f(1) + 2;").unwrap(),
+            "(let a (+ (try (call f 1)) 2))"
+        );
+        assert_eq!(parse("try f();").unwrap(), "(; (try (call f)))");
+        assert_eq!(parse("a.b(c).d;").unwrap(), "(; (. (call (. a b) c) d))");
+        assert_eq!(parse("a.b.c = 1;").unwrap(), "(; (= (. (. a b) c) 1))");
+        assert_eq!(
+            parse("a.b %= 1 - 2;").unwrap(),
+            "(; (= (. a b) (% (. a b) (- 1 2))))"
+        );
+    }
+
+    #[test]
+    fn check_grouping() {
+        assert_eq!(parse("(1 + 2) * 3;").unwrap(), "(; (* (group (+ 1 2)) 3))");
+        assert_eq!(parse("\"fox\" == nil;").unwrap(), "(; (== fox nil))");
+    }
+
+    #[test]
+    fn check_statements() {
+        assert_eq!(parse("let a = 1; print a;").unwrap(), "(let a 1) (print a)");
+        assert_eq!(
+            parse("if a { print 1; } else if b { print 2; } else { print 3; }").unwrap(),
+            "(if a (block (print 1)) (if b (block (print 2)) (block (print 3))))"
+        );
+        assert_eq!(
+            parse("while a < 3 { a = a + 1; }").unwrap(),
+            "(while (< a 3) (block (; (= a (+ a 1)))))"
+        );
+        assert_eq!(
+            parse("for let i = 0; i < 3; i = i + 1 { print i; }").unwrap(),
+            "(for (let i 0) (< i 3) (= i (+ i 1)) (block (print i)))"
+        );
+        assert_eq!(parse("for ;; { }").unwrap(), "(for nil nil nil (block))");
+        assert_eq!(
+            parse("for i in 0..n + 1 { }").unwrap(),
+            "(for i in (.. 0 (+ n 1)) (block))"
+        );
+        assert_eq!(
+            parse("for x in xs { print x; }").unwrap(),
+            "(for x in xs (block (print x)))"
+        );
+        assert_eq!(
+            parse("let s = match n { -1 => \"neg\", x if x > 9 => \"big\", _ => n, };").unwrap(),
+            "(let s (match n (-1 neg) (x if (> x 9) big) (_ n)))"
+        );
+        assert_eq!(
+            parse("import math; import \"../lib/strings.fox\"; export let pi = 3;").unwrap(),
+            "(import math.fox math) (import ../lib/strings.fox strings) (export (let pi 3))"
+        );
+        let errors = parse("import \"str-utils.fox\";").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::invalid_module_name"]);
+        assert_eq!(errors[0].value("name"), Some("str-utils"));
+        assert!(parse("export print 1;").is_err());
+        assert_eq!(
+            parse("fn add(a, b) { return a + b; }").unwrap(),
+            "(fn add (a b) (return (+ a b)))"
+        );
+        assert_eq!(
+            parse("class Fox { fn say() { print \"hi\"; } }").unwrap(),
+            "(class Fox (fn say () (print hi)))"
+        );
+        assert_eq!(
+            parse("class Fox < Animal { fn init(name) { this.name = name; super.init(); } }")
+                .unwrap(),
+            "(class Fox < Animal (fn init (name) (; (= (. this name) name)) (; (call (. super init)))))"
+        );
+        assert_eq!(
+            parse("guard a > 1 else { return; }").unwrap(),
+            "(guard (> a 1) (block (return)))"
+        );
+        assert_eq!(
+            parse("try { throw Error(1); } catch e { print e; }").unwrap(),
+            "(try (block (throw (call Error 1))) (catch e (block (print e))))"
+        );
+        assert_eq!(
+            parse("while a { if b { break; } continue; }").unwrap(),
+            "(while a (block (if b (block (break))) (continue)))"
+        );
+        assert_eq!(
+            parse("xs[0] = [1, [],][i + 1]; xs[0][1] += 2;").unwrap(),
+            "(; (= ([] xs 0) ([] (list 1 (list)) (+ i 1)))) (; (= ([] ([] xs 0) 1) (+ ([] ([] xs 0) 1) 2)))"
+        );
+        assert_eq!(
+            parse("let m = {\"a\": 1, 2: {},}; {\"b\": m}[\"b\"]; { print m; } {}").unwrap(),
+            "(let m (map (a 1) (2 (map)))) (; ([] (map (b m)) b)) (block (print m)) (block)"
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        let errors = parse("(1 + 2;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::unclosed"]);
+        assert_eq!(labels(&errors[0]), [(6, 1), (0, 1)]);
+        assert_eq!(errors[0].value("closing"), Some(")"));
+
+        let errors = parse("1 +;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::expected_expression"]);
+
+        let errors = parse("1 2;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::missing_semicolon"]);
+        assert_eq!(labels(&errors[0]), [(1, 0), (2, 1)]);
+
+        let errors = parse("let a = b ? 1;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::missing_conditional_colon"]);
+        assert_eq!(labels(&errors[0]), [(13, 1), (10, 1)]);
+
+        let errors = parse("1 -= 2;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::invalid_assignment_target"]);
+
+        let errors = parse("1 = 2;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::invalid_assignment_target"]);
+
+        let errors = parse("super;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::unexpected_token"]);
+
+        let errors = parse("f(1, 2;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::unclosed"]);
+        assert_eq!(labels(&errors[0])[1], (1, 1));
+
+        let errors = parse("if a print a;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::unexpected_token"]);
+
+        let errors = parse("try { } print e;").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::unexpected_token"]);
+    }
+
+    #[test]
+    fn check_recovery() {
+        let errors = parse("let = 1; print 1 +; let a = 1; fn f( { }").unwrap_err();
+        assert_eq!(
+            codes(&errors),
+            [
+                "parse::unexpected_token",
+                "parse::expected_expression",
+                "parse::unexpected_token"
+            ]
+        );
+
+        let errors = parse("fn f() { 1 + } let b = ; while a { print; }").unwrap_err();
+        assert_eq!(codes(&errors), ["parse::expected_expression"; 3]);
+    }
+
+    fn codes(errors: &[ParseError]) -> Vec<&str> {
+        errors.iter().map(|err| err.code).collect()
+    }
+
+    /// The offset and length of the labels of `err`, the primary one first.
+    fn labels(err: &ParseError) -> Vec<(usize, usize)> {
+        err.labels
+            .iter()
+            .map(|label| (label.offset, label.len))
+            .collect()
+    }
+}
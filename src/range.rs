@@ -0,0 +1,47 @@
+use std::{fmt, ops::RangeInclusive};
+
+/// The ints from `start` up to `end`, which is included if `inclusive`,
+/// written `start..end` or `start..=end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    /// The ints in the range, in increasing order.
+    pub fn ints(&self) -> RangeInclusive<i64> {
+        match (self.inclusive, self.end.checked_sub(1)) {
+            (true, _) => self.start..=self.end,
+            (false, Some(last)) => self.start..=last,
+            // Nothing is below `i64::MIN`.
+            #[allow(clippy::reversed_empty_ranges)]
+            (false, None) => 1..=0,
+        }
+    }
+
+    /// The positions the range covers in a list or string of `len`
+    /// elements, or `None` if it goes past either end. A range ending before
+    /// it starts covers nothing.
+    pub(crate) fn slice(&self, len: usize) -> Option<std::ops::Range<usize>> {
+        let start = usize::try_from(self.start).ok()?;
+        let end = usize::try_from(self.end).ok()?;
+        let end = if self.inclusive {
+            end.checked_add(1)?
+        } else {
+            end
+        };
+        if start > len || end > len {
+            return None;
+        }
+        Some(start..end.max(start))
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = if self.inclusive { "..=" } else { ".." };
+        write!(f, "{}{operator}{}", self.start, self.end)
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+
+/// A change to a variable or property, as recorded by a `Recorder`. The
+/// value is kept as it was printed at the time, since objects can change
+/// afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mutation {
+    /// Where the change was made, like `script.fox:3`.
+    pub(crate) location: String,
+    /// The variable changed, or `object.property` for a property.
+    pub(crate) target: String,
+    pub(crate) value: String,
+}
+
+/// Records the changes a script makes to its variables and properties, so
+/// that the debugger can step back through how the script got to where it
+/// is paused. Only the last `limit` changes are kept, which bounds the
+/// memory a long run takes.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    mutations: VecDeque<Mutation>,
+    limit: usize,
+    /// How many of the oldest changes were dropped to stay within `limit`.
+    dropped: usize,
+    /// How many of the kept changes the debugger has stepped through, or
+    /// `None` while the script runs.
+    cursor: Option<usize>,
+}
+
+impl Recorder {
+    pub(crate) fn new(limit: usize) -> Self {
+        Recorder {
+            mutations: VecDeque::new(),
+            limit,
+            dropped: 0,
+            cursor: None,
+        }
+    }
+
+    /// Records `mutation`, unless the debugger is stepping through the
+    /// changes, as code it runs isn't part of the script's run.
+    pub(crate) fn record(&mut self, mutation: Mutation) {
+        if self.cursor.is_some() || self.limit == 0 {
+            return;
+        }
+        if self.mutations.len() == self.limit {
+            self.mutations.pop_front();
+            self.dropped += 1;
+        }
+        self.mutations.push_back(mutation);
+    }
+
+    /// Starts stepping through the changes, from the last one.
+    pub(crate) fn pause(&mut self) {
+        self.cursor = Some(self.mutations.len());
+    }
+
+    pub(crate) fn resume(&mut self) {
+        self.cursor = None;
+    }
+
+    pub(crate) fn mutations(&self) -> &VecDeque<Mutation> {
+        &self.mutations
+    }
+
+    pub(crate) fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// How many changes have been stepped through, from 0 before the first
+    /// one kept to the number of changes after the last one.
+    pub(crate) fn cursor(&self) -> usize {
+        self.cursor.unwrap_or(self.mutations.len())
+    }
+
+    /// Steps forward through `steps` changes, or back if it is negative,
+    /// stopping at either end.
+    pub(crate) fn step(&mut self, steps: isize) {
+        let cursor = self.cursor().saturating_add_signed(steps);
+        self.cursor = Some(cursor.min(self.mutations.len()));
+    }
+
+    /// The last change stepped through, if any.
+    pub(crate) fn current(&self) -> Option<&Mutation> {
+        self.mutations.get(self.cursor().checked_sub(1)?)
+    }
+
+    /// The value of every variable and property changed before the cursor,
+    /// as of the cursor, sorted by name.
+    pub(crate) fn state(&self) -> Vec<(&str, &str)> {
+        let mut state: Vec<(&str, &str)> = vec![];
+        for mutation in self.mutations.iter().take(self.cursor()) {
+            let target = mutation.target.as_str();
+            match state.iter_mut().find(|(name, _)| *name == target) {
+                Some((_, value)) => *value = &mutation.value,
+                None => state.push((target, &mutation.value)),
+            }
+        }
+        state.sort_by_key(|(name, _)| *name);
+        state
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mutation, Recorder};
+
+    fn mutation(line: usize, target: &str, value: &str) -> Mutation {
+        Mutation {
+            location: format!("test.fox:{line}"),
+            target: target.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn check_steps() {
+        let mut recorder = Recorder::new(3);
+        for (line, target, value) in [(1, "a", "0"), (2, "b", "1"), (3, "a", "2"), (4, "c", "3")] {
+            recorder.record(mutation(line, target, value));
+        }
+        assert_eq!(recorder.dropped(), 1);
+        assert_eq!(recorder.mutations().len(), 3);
+
+        recorder.pause();
+        recorder.record(mutation(5, "d", "4"));
+        assert_eq!(recorder.cursor(), 3);
+        assert_eq!(recorder.state(), [("a", "2"), ("b", "1"), ("c", "3")]);
+        recorder.step(-1);
+        assert_eq!(recorder.current(), Some(&mutation(3, "a", "2")));
+        assert_eq!(recorder.state(), [("a", "2"), ("b", "1")]);
+        recorder.step(-5);
+        assert_eq!(recorder.cursor(), 0);
+        assert_eq!(recorder.current(), None);
+        assert!(recorder.state().is_empty());
+        recorder.step(5);
+        assert_eq!(recorder.cursor(), 3);
+
+        recorder.resume();
+        recorder.record(mutation(5, "d", "4"));
+        assert_eq!(recorder.mutations().len(), 3);
+        assert_eq!(recorder.dropped(), 2);
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::{
+    compiler::Compiler,
+    dump::{print_ast, print_tokens, AstFormat, TokenFormat},
+    engine,
+    errors::{FoxError, RuntimeError},
+    interpreter::{Interpreter, Value},
+    lexer::{Lexer, TokenType},
+    record::Mutation,
+    report_error, report_warnings,
+    source::SourceFile,
+    vm::Vm,
+    Backend, Fox,
+};
+
+const HELP: &str = "\
+Commands:
+  :help     Show this message
+  :env      List the global variables and their values
+  :tokens   Show the tokens of the last input
+  :ast      Show the syntax tree of the last input
+  :clear    Start over with a fresh session
+  :quit     Exit the prompt, like Ctrl-D";
+
+const DEBUG_HELP: &str = "\
+The script is paused by `debug()`, or where an uncaught error was raised
+when run with `--debug-on-error`. Code typed here sees the variables in
+scope there, and can change them.
+
+Commands:
+  :help      Show this message
+  :env       List the variables in scope and their values
+  :stack     List the functions being called, innermost first
+  :continue  Resume the script, like Ctrl-D
+
+When run with `--record`, the changes made to variables and properties so
+far can be stepped through, without undoing them:
+  :history       List the changes recorded
+  :back [n]      Step back through n changes, 1 by default, and show the
+                 values recorded as of then
+  :forward [n]   Step forward through n changes, 1 by default";
+
+const NOT_RECORDING: &str = "No changes recorded, run the script with `--record`";
+
+/// The state kept between the inputs of the prompt.
+struct Session {
+    backend: Backend,
+    /// Runs the inputs with the tree backend, keeping the globals they define.
+    fox: Fox,
+    /// Likewise with the VM backend.
+    vm: Vm,
+    /// The last input that was run, inspected by `:tokens` and `:ast`.
+    last_input: SourceFile,
+}
+
+impl Session {
+    fn new(backend: Backend) -> Self {
+        Session {
+            backend,
+            fox: Fox::new(),
+            vm: Vm::new(),
+            last_input: SourceFile::default(),
+        }
+    }
+
+    /// Runs `input`, printing its value if it ends with an expression whose
+    /// value isn't `nil`, like `1 + 2` or `greet("fox")`.
+    fn run(&mut self, input: String) {
+        match self.eval(input) {
+            Ok(Value::Nil) => {}
+            Ok(value) => println!("{value}"),
+            Err(err) => report_error(err),
+        }
+    }
+
+    fn eval(&mut self, input: String) -> Result<Value, FoxError> {
+        self.last_input = SourceFile::new("<repl>", input);
+        let input = &self.last_input;
+        match self.backend {
+            Backend::Tree => {
+                let result = self.fox.eval_source(input);
+                report_warnings(self.fox.take_warnings());
+                result
+            }
+            Backend::Vm => {
+                let (stmts, warnings) = engine::parse(input)?;
+                report_warnings(warnings);
+                let script = Compiler::new(input, self.vm.global_slots()).compile(&stmts)?;
+                Ok(self.vm.interpret(input, script)?)
+            }
+        }
+    }
+
+    /// Runs the meta-command `line`, returning `false` if the prompt should
+    /// exit.
+    fn command(&mut self, line: &str) -> bool {
+        match line.trim() {
+            ":help" => println!("{HELP}"),
+            ":env" => {
+                let globals = match self.backend {
+                    Backend::Tree => self.fox.interpreter().globals(),
+                    Backend::Vm => self.vm.globals(),
+                };
+                for (name, value) in globals {
+                    println!("{name} = {value}");
+                }
+            }
+            ":tokens" => {
+                let _ = print_tokens(&self.last_input, TokenFormat::Human);
+            }
+            ":ast" => {
+                let _ = print_ast(&self.last_input, AstFormat::Sexpr);
+            }
+            ":clear" => *self = Session::new(self.backend),
+            ":quit" => return false,
+            command => eprintln!("Unknown command `{command}`, see `:help`"),
+        }
+        true
+    }
+}
+
+/// Runs the interactive prompt until the user presses Ctrl-D. Lines can be
+/// edited in place and earlier ones recalled with the arrow keys. Ctrl-C
+/// discards the input being typed. Input that is obviously unfinished, like
+/// a function whose body isn't closed yet, continues on the next line and is
+/// only run once complete. Lines starting with `:` are meta-commands, see
+/// `HELP`. History is kept across sessions in the user's config directory.
+pub fn run(backend: Backend) {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("Couldn't start the prompt: {err}");
+            return;
+        }
+    };
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is no history yet on the first run.
+        let _ = editor.load_history(path);
+    }
+
+    let mut session = Session::new(backend);
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { "> " } else { "..> " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if input.is_empty() && line.trim().is_empty() {
+                    continue;
+                }
+                if input.is_empty() && line.starts_with(':') {
+                    let _ = editor.add_history_entry(line.as_str());
+                    if !session.command(&line) {
+                        break;
+                    }
+                    continue;
+                }
+                input.push_str(&line);
+                input.push('\n');
+                if !is_complete(&input) {
+                    continue;
+                }
+                let _ = editor.add_history_entry(input.trim_end());
+                session.run(std::mem::take(&mut input));
+            }
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Couldn't read the line: {err}");
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = history {
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(ReadlineError::from)
+            .and_then(|()| editor.save_history(&path));
+        if let Err(err) = saved {
+            eprintln!("Couldn't save the history to `{}`: {err}", path.display());
+        }
+    }
+}
+
+/// The prompt of the `debug()` builtin and of `--debug-on-error`, run until
+/// the user continues the script. Does nothing unless standard input is a
+/// terminal, so that scripts run by other programs are never paused.
+pub(crate) fn debug(interpreter: &mut Interpreter, error: Option<&RuntimeError>) {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("Couldn't start the debug prompt: {err}");
+            return;
+        }
+    };
+    match error {
+        Some(error) => println!("Paused on an uncaught error, see `:help`\n{error}"),
+        None => println!("Paused by `debug()`, see `:help`"),
+    }
+    loop {
+        match editor.readline("debug> ") {
+            Ok(line) => {
+                let _ = editor.add_history_entry(line.as_str());
+                if !debug_line(interpreter, &line) {
+                    break;
+                }
+            }
+            Err(ReadlineError::Interrupted) => {}
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Couldn't read the line: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/// Runs `line` of the `debug()` prompt, either a meta-command or code,
+/// returning `false` if the script should resume.
+fn debug_line(interpreter: &mut Interpreter, line: &str) -> bool {
+    match line.trim() {
+        "" => {}
+        ":help" => println!("{DEBUG_HELP}"),
+        ":env" => {
+            for (name, value) in interpreter.scope() {
+                println!("{name} = {value}");
+            }
+        }
+        ":stack" => {
+            let stack = interpreter.stack_trace();
+            if stack.is_empty() {
+                println!("Not in a function");
+            } else {
+                println!("{stack}");
+            }
+        }
+        ":continue" => return false,
+        ":history" => match &interpreter.recorder {
+            Some(recorder) => {
+                let first = recorder.dropped() + 1;
+                for (i, mutation) in recorder.mutations().iter().enumerate() {
+                    let Mutation {
+                        location,
+                        target,
+                        value,
+                    } = mutation;
+                    let marker = if i + 1 == recorder.cursor() { ">" } else { " " };
+                    println!("{marker}{:>5} {location}: {target} = {value}", first + i);
+                }
+            }
+            None => eprintln!("{NOT_RECORDING}"),
+        },
+        command
+            if let (name @ (":back" | ":forward"), count) =
+                command.split_once(' ').unwrap_or((command, "1")) =>
+        {
+            step_changes(interpreter, name, count.trim())
+        }
+        command if command.starts_with(':') => {
+            eprintln!("Unknown command `{command}`, see `:help`")
+        }
+        _ => {
+            let source = SourceFile::new("<debug>", line);
+            let result = engine::parse(&source).and_then(|(stmts, warnings)| {
+                report_warnings(warnings);
+                Ok(interpreter.eval(&source, &stmts)?)
+            });
+            match result {
+                Ok(Value::Nil) => {}
+                Ok(value) => println!("{value}"),
+                Err(err) => report_error(err),
+            }
+        }
+    }
+    true
+}
+
+/// Runs the `:back` or `:forward` command of the `debug()` prompt, named
+/// `name`, stepping through `count` recorded changes.
+fn step_changes(interpreter: &mut Interpreter, name: &str, count: &str) {
+    let Some(recorder) = &mut interpreter.recorder else {
+        eprintln!("{NOT_RECORDING}");
+        return;
+    };
+    let Ok(count) = count.parse::<isize>() else {
+        eprintln!("Expected a number of changes after `{name}`");
+        return;
+    };
+    recorder.step(if name == ":back" { -count } else { count });
+    let step = recorder.dropped() + recorder.cursor();
+    match recorder.current() {
+        Some(Mutation {
+            location,
+            target,
+            value,
+        }) => println!("After change {step}, at {location}: {target} = {value}"),
+        None => println!("Before the first change recorded"),
+    }
+    for (target, value) in recorder.state() {
+        println!("  {target} = {value}");
+    }
+}
+
+/// Whether `input` can be run as is, rather than needing more lines: it
+/// doesn't end inside a string or block comment, and every `{` and `(` is
+/// closed. Other errors are left for the parser to report.
+fn is_complete(input: &str) -> bool {
+    let (tokens, errors) = Lexer::new(&SourceFile::new("<repl>", input)).scan_all();
+    let unterminated = errors.iter().any(|err| {
+        err.code == "syntax::unterminated_string"
+            || err.code == "syntax::unterminated_block_comment"
+    });
+    if unterminated {
+        return false;
+    }
+    let mut depth = 0;
+    for token in tokens {
+        match token.ty {
+            TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+            TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0
+}
+
+/// Where the history of the prompt is stored, if the platform has a config
+/// directory.
+pub(crate) fn history_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("fox").join("history"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{interpreter::Value, Backend, Fox};
+
+    use super::{debug_line, is_complete, Session};
+
+    #[test]
+    fn check_is_complete() {
+        assert!(is_complete("print 1;\n"));
+        assert!(is_complete("fn f() {\n  return 1;\n}\n"));
+        assert!(!is_complete("fn f() {\n"));
+        assert!(!is_complete("class A {\n  fn f() {}\n"));
+        assert!(!is_complete("print f(1,\n"));
+        assert!(!is_complete("print \"fox\n"));
+        assert!(!is_complete("/* a comment\n"));
+        // Left for the parser to report.
+        assert!(is_complete("print 1 +;\n"));
+        assert!(is_complete("}\n"));
+        assert!(is_complete("print 1; @\n"));
+    }
+
+    #[test]
+    fn check_eval() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut session = Session::new(backend);
+            let mut eval = |input: &str| session.eval(input.to_string()).unwrap();
+            assert_eq!(eval("let a = 1;\n"), Value::Nil);
+            assert_eq!(eval("fn double(x) { return x * 2; }\n"), Value::Nil);
+            assert_eq!(eval("double(a) + 1;\n"), Value::Int(3));
+            assert_eq!(eval("a = 5; print a;\n"), Value::Nil);
+            assert_eq!(eval("a;\n"), Value::Int(5));
+            assert_eq!(eval("\"fox\";\n"), Value::String("fox".to_string()));
+            assert!(session.eval("missing;\n".to_string()).is_err());
+            assert_eq!(session.eval("a;\n".to_string()).unwrap(), Value::Int(5));
+        }
+    }
+
+    #[test]
+    fn check_commands() {
+        let mut session = Session::new(Backend::Tree);
+        session.run("let a = 1;\n".to_string());
+        session.run("a = a + 1;\n".to_string());
+        let global = |session: &mut Session| session.fox.interpreter().global("a");
+        assert_eq!(global(&mut session), Some(Value::Int(2)));
+        assert_eq!(session.last_input.text(), "a = a + 1;\n");
+
+        assert!(session.command(":clear"));
+        assert_eq!(global(&mut session), None);
+        assert!(session.last_input.text().is_empty());
+        assert!(session.command(":bogus"));
+        assert!(!session.command(":quit"));
+    }
+
+    #[test]
+    fn check_debug() {
+        let mut fox = Fox::new();
+        fox.interpreter().debugger = |interpreter, _| {
+            assert_eq!(
+                interpreter.scope(),
+                [
+                    ("local".to_string(), Value::Int(1)),
+                    ("x".to_string(), Value::Int(2))
+                ]
+            );
+            for line in ["x = x * local * 10;", "let temp = x;", ":bogus", "missing;"] {
+                assert!(debug_line(interpreter, line));
+            }
+            assert!(!debug_line(interpreter, ":continue"));
+        };
+        let script = "let x = 0; fn f(x) { let local = 1; debug(); return x; } f(2);";
+        assert_eq!(fox.eval(script).unwrap(), Value::Int(20));
+        assert_eq!(fox.eval("x;").unwrap(), Value::Int(0));
+        assert!(fox.eval("temp;").is_err());
+    }
+
+    #[test]
+    fn check_record() {
+        let mut fox = Fox::new();
+        fox.interpreter().set_recording(Some(4));
+        fox.interpreter().debugger = |interpreter, _| {
+            for line in ["let c = 9;", ":history", ":back 3", ":back x", ":forward"] {
+                assert!(debug_line(interpreter, line));
+            }
+            let recorder = interpreter.recorder.as_ref().unwrap();
+            assert_eq!(recorder.dropped(), 2);
+            assert_eq!(recorder.cursor(), 2);
+            assert_eq!(recorder.state(), [("a", "2"), ("b", "3")]);
+        };
+        let script = "
+            class P {}
+            let a = 1;
+            let b = 2;
+            a = a + 1;
+            b = b + a / 2;
+            let p = P();
+            p.x = 1;
+            debug();
+        ";
+        fox.eval(script).unwrap();
+        let recorder = fox.interpreter().recorder.as_ref().unwrap();
+        assert_eq!(recorder.mutations().len(), 4);
+        assert_eq!(recorder.mutations()[3].location, "<eval>:8");
+    }
+
+    #[test]
+    fn check_debug_on_error() {
+        static PAUSES: AtomicUsize = AtomicUsize::new(0);
+        let mut fox = Fox::new();
+        fox.interpreter().set_debug_on_error(true);
+        fox.interpreter().debugger = |interpreter, error| {
+            PAUSES.fetch_add(1, Ordering::Relaxed);
+            assert_eq!(error.unwrap().code, "runtime::invalid_operands");
+            assert_eq!(
+                interpreter.scope(),
+                [
+                    ("n".to_string(), Value::Int(2)),
+                    ("x".to_string(), Value::Nil)
+                ]
+            );
+            assert_eq!(interpreter.stack_trace(), "at f, called from line 1");
+            for line in [":stack", "x = n;", "missing;"] {
+                assert!(debug_line(interpreter, line));
+            }
+        };
+        let script = "fn f(x) { if true { let n = 2; return x + n; } } f(nil);";
+        assert!(fox.eval(script).is_err());
+        assert_eq!(PAUSES.load(Ordering::Relaxed), 1);
+        assert!(fox
+            .eval("let a; try { f(nil); } catch e { a = e.kind; } a;")
+            .is_ok());
+        assert_eq!(PAUSES.load(Ordering::Relaxed), 1);
+    }
+}
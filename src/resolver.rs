@@ -0,0 +1,747 @@
+use std::{cell::Cell, collections::HashMap};
+
+use miette::SourceSpan;
+
+use crate::{
+    diag::Diag,
+    errors::{ResolveError, ResolveWarning},
+    expr::{Expr, MatchExpr, Pattern},
+    interpreter::Value,
+    lexer::Token,
+    source::{SourceFile, Span},
+    stmt::{self, ClassStmt, FunctionStmt, Stmt},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Binding {
+    /// Whether the initializer of the binding has been resolved, i.e. whether
+    /// it is available for use.
+    defined: bool,
+    span: SourceSpan,
+    /// For functions that may return an `Error`, where they first do so.
+    fails: Option<SourceSpan>,
+}
+
+/// Static pass run between parsing and evaluation. It records, for every
+/// local variable use, how many scopes away its declaration is, and reports
+/// semantic errors that can be caught without running the program.
+///
+/// Globals are not tracked and are looked up dynamically by the interpreter.
+/// Only global functions that may return an `Error` are remembered, to warn
+/// about calls ignoring their result.
+pub struct Resolver<'a> {
+    source: &'a SourceFile,
+    scopes: Vec<HashMap<String, Binding>>,
+    function: FunctionKind,
+    class: ClassKind,
+    /// Where the function being resolved first may return an `Error`, either
+    /// by `try` or by returning a call to `Error`.
+    fails: Option<SourceSpan>,
+    /// Global functions that may return an `Error`, see `Binding::fails`.
+    failing_globals: HashMap<String, SourceSpan>,
+    /// Number of loops around the statement being resolved, within the
+    /// function being resolved.
+    loops: usize,
+    errors: Vec<ResolveError>,
+    warnings: Vec<ResolveWarning>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(source: &'a SourceFile) -> Self {
+        Resolver {
+            source,
+            scopes: vec![],
+            function: FunctionKind::None,
+            class: ClassKind::None,
+            fails: None,
+            failing_globals: HashMap::new(),
+            loops: 0,
+            errors: vec![],
+            warnings: vec![],
+        }
+    }
+
+    /// Resolves `stmts`, returning every error found, or else every warning.
+    pub fn resolve(mut self, stmts: &[Stmt]) -> Result<Vec<ResolveWarning>, Vec<ResolveError>> {
+        self.resolve_stmts(stmts);
+        if self.errors.is_empty() {
+            Ok(self.warnings)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(stmts) => {
+                self.begin_scope();
+                self.resolve_stmts(stmts);
+                self.end_scope();
+            }
+            Stmt::Class(stmt) => self.resolve_class(stmt),
+            Stmt::Expression(expr) => {
+                self.check_ignored_error(expr);
+                self.resolve_expr(expr);
+            }
+            Stmt::Print(stmt) => self.resolve_expr(&stmt.value),
+            Stmt::For(stmt) => {
+                self.begin_scope();
+                if let Some(initializer) = &stmt.initializer {
+                    self.resolve_stmt(initializer);
+                }
+                if let Some(condition) = &stmt.condition {
+                    self.resolve_expr(condition);
+                }
+                if let Some(increment) = &stmt.increment {
+                    self.resolve_expr(increment);
+                }
+                self.resolve_loop_body(&stmt.body);
+                self.end_scope();
+            }
+            Stmt::ForIn(stmt) => {
+                self.resolve_expr(&stmt.iterable);
+                self.begin_scope();
+                self.declare(&stmt.name);
+                self.define(&stmt.name);
+                self.set_fails(&stmt.name, None);
+                self.resolve_loop_body(&stmt.body);
+                self.end_scope();
+            }
+            Stmt::Function(stmt) => {
+                self.declare(&stmt.name);
+                self.define(&stmt.name);
+                let fails = self.resolve_function(stmt, FunctionKind::Function);
+                self.set_fails(&stmt.name, fails);
+            }
+            Stmt::Guard(stmt) => {
+                self.resolve_expr(&stmt.condition);
+                self.resolve_stmt(&stmt.else_branch);
+                if !stmt::diverges(&stmt.else_branch) {
+                    let span = stmt.keyword.position.span();
+                    self.errors.push(
+                        Diag::error("resolve::guard_falls_through")
+                            .msg("The `else` body of `guard` must not fall through")
+                            .source(self.source)
+                            .primary(span, "the body of this guard can continue past it")
+                            .help("end it with `return` or `throw`, or `break` or `continue` in a loop")
+                            .into(),
+                    );
+                }
+            }
+            Stmt::If(stmt) => {
+                self.resolve_expr(&stmt.condition);
+                self.resolve_stmt(&stmt.then_branch);
+                if let Some(else_branch) = &stmt.else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::Export(stmt) => {
+                if !self.scopes.is_empty() {
+                    self.errors.push(
+                        Diag::error("resolve::nested_export")
+                            .msg("Can only export declarations at the top level of a file")
+                            .source(self.source)
+                            .primary(stmt.keyword.position.span(), "exported here")
+                            .help("move the declaration out of the enclosing block or function")
+                            .into(),
+                    );
+                }
+                self.resolve_stmt(&stmt.declaration);
+            }
+            Stmt::Import(stmt) => {
+                self.declare(&stmt.name);
+                self.define(&stmt.name);
+                self.set_fails(&stmt.name, None);
+            }
+            Stmt::Let(stmt) => {
+                self.declare(&stmt.name);
+                if let Some(initializer) = &stmt.initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(&stmt.name);
+                self.set_fails(&stmt.name, None);
+            }
+            Stmt::Return(stmt) => {
+                if self.function == FunctionKind::None {
+                    self.errors.push(
+                        Diag::error("resolve::return_outside_function")
+                            .msg("Can't return from top-level code")
+                            .source(self.source)
+                            .primary(stmt.keyword.position.span(), "returned here")
+                            .help("`return` can only be used inside functions and methods")
+                            .into(),
+                    );
+                }
+                if let Some(value) = &stmt.value {
+                    if self.function == FunctionKind::Initializer {
+                        self.return_value_from_initializer(stmt.keyword.position.span());
+                    }
+                    if let Expr::Call(call) = value {
+                        if matches!(&*call.callee, Expr::Variable(callee) if callee.name.lexeme() == "Error")
+                        {
+                            self.fails.get_or_insert(stmt.keyword.position.span());
+                        }
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Throw(stmt) => self.resolve_expr(&stmt.value),
+            Stmt::Try(stmt) => {
+                self.begin_scope();
+                self.resolve_stmts(&stmt.body);
+                self.end_scope();
+                self.begin_scope();
+                self.declare(&stmt.name);
+                self.define(&stmt.name);
+                self.resolve_stmts(&stmt.handler);
+                self.end_scope();
+            }
+            Stmt::While(stmt) => {
+                self.resolve_expr(&stmt.condition);
+                self.resolve_loop_body(&stmt.body);
+            }
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => {
+                if self.loops == 0 {
+                    self.errors.push(
+                        Diag::error("resolve::outside_loop")
+                            .arg("keyword", keyword.lexeme())
+                            .msg("Can't use `{keyword}` outside of a loop")
+                            .source(self.source)
+                            .primary(keyword.position.span(), "used here")
+                            .help("`{keyword}` can only be used in the body of a `while` or `for` loop, outside of functions declared there")
+                            .into(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn resolve_loop_body(&mut self, body: &Stmt) {
+        self.loops += 1;
+        self.resolve_stmt(body);
+        self.loops -= 1;
+    }
+
+    fn resolve_class(&mut self, stmt: &ClassStmt) {
+        let enclosing = self.class;
+        self.class = ClassKind::Class;
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.lexeme() == stmt.name.lexeme() {
+                self.errors.push(
+                    Diag::error("resolve::inherits_itself")
+                        .msg("A class can't inherit from itself")
+                        .source(self.source)
+                        .primary(superclass.name.position.span(), "inherited here")
+                        .into(),
+                );
+            }
+            self.class = ClassKind::Subclass;
+            self.resolve_local(&superclass.name, &superclass.depth);
+            self.begin_scope();
+            self.define_synthetic("super", &superclass.name);
+        }
+
+        self.begin_scope();
+        self.define_synthetic("this", &stmt.name);
+        for method in &stmt.methods {
+            let kind = if method.name.lexeme() == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.resolve_function(method, kind);
+        }
+        self.end_scope();
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        self.class = enclosing;
+    }
+
+    /// Resolves the body of a function, returning where it first may return
+    /// an `Error`, if anywhere.
+    fn resolve_function(&mut self, stmt: &FunctionStmt, kind: FunctionKind) -> Option<SourceSpan> {
+        let enclosing = self.function;
+        let enclosing_fails = self.fails.take();
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        self.function = kind;
+        self.begin_scope();
+        for param in &stmt.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(&stmt.body);
+        self.end_scope();
+        self.function = enclosing;
+        self.loops = enclosing_loops;
+        std::mem::replace(&mut self.fails, enclosing_fails)
+    }
+
+    /// Records whether the variable `name` just declared holds a function
+    /// that may return an `Error`.
+    fn set_fails(&mut self, name: &Token, fails: Option<SourceSpan>) {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                if let Some(binding) = scope.get_mut(&name.lexeme()) {
+                    binding.fails = fails;
+                }
+            }
+            None => match fails {
+                Some(span) => {
+                    self.failing_globals.insert(name.lexeme(), span);
+                }
+                None => {
+                    self.failing_globals.remove(&name.lexeme());
+                }
+            },
+        }
+    }
+
+    /// Warns if `expr`, used as a statement, calls a function that may
+    /// return an `Error`, which would go unnoticed.
+    fn check_ignored_error(&mut self, expr: &Expr) {
+        let Expr::Call(call) = expr else {
+            return;
+        };
+        let Expr::Variable(callee) = &*call.callee else {
+            return;
+        };
+        let name = callee.name.lexeme();
+        let fails = match self.scopes.iter().rev().find_map(|scope| scope.get(&name)) {
+            Some(binding) => binding.fails,
+            None => self.failing_globals.get(&name).copied(),
+        };
+        if let Some(returned) = fails {
+            let span = callee.name.position.span();
+            self.warnings.push(
+                Diag::warning("resolve::ignored_error")
+                    .arg("name", name)
+                    .msg("Result of `{name}` is ignored")
+                    .source(self.source)
+                    .primary(span.join(&call.paren.position.span()), "result discarded here")
+                    .secondary(returned, "may return an `Error` here")
+                    .help("`{name}` can return an `Error`, propagate it using `try {name}(...)` or check the result")
+                    .into(),
+            );
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign(expr) => {
+                self.resolve_expr(&expr.value);
+                self.resolve_local(&expr.name, &expr.depth);
+            }
+            Expr::Binary(expr) => {
+                self.resolve_expr(&expr.lhs);
+                self.resolve_expr(&expr.rhs);
+            }
+            Expr::Call(expr) => {
+                self.resolve_expr(&expr.callee);
+                for argument in &expr.arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get(expr) => self.resolve_expr(&expr.object),
+            Expr::Index(expr) => {
+                self.resolve_expr(&expr.object);
+                self.resolve_expr(&expr.index);
+            }
+            Expr::IndexSet(expr) => {
+                self.resolve_expr(&expr.value);
+                self.resolve_expr(&expr.object);
+                self.resolve_expr(&expr.index);
+            }
+            Expr::List(expr) => {
+                for element in &expr.elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Map(expr) => {
+                for entry in &expr.entries {
+                    self.resolve_expr(&entry.key);
+                    self.resolve_expr(&entry.value);
+                }
+            }
+            Expr::Conditional(expr) => {
+                self.resolve_expr(&expr.condition);
+                self.resolve_expr(&expr.then_branch);
+                self.resolve_expr(&expr.else_branch);
+            }
+            Expr::Grouping(expr) => self.resolve_expr(&expr.expr),
+            Expr::Interpolation(expr) => {
+                for part in &expr.parts {
+                    self.resolve_expr(part);
+                }
+            }
+            Expr::Literal(_) => {}
+            Expr::Match(expr) => {
+                self.resolve_expr(&expr.subject);
+                self.check_unreachable_arms(expr);
+                for arm in &expr.arms {
+                    // Only arms with a binding get a scope, like they get an
+                    // environment in the interpreter.
+                    let binding = match &arm.pattern {
+                        Pattern::Binding(name) => Some(name),
+                        _ => None,
+                    };
+                    if let Some(name) = binding {
+                        self.begin_scope();
+                        self.declare(name);
+                        self.define(name);
+                        self.set_fails(name, None);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.resolve_expr(guard);
+                    }
+                    self.resolve_expr(&arm.body);
+                    if binding.is_some() {
+                        self.end_scope();
+                    }
+                }
+            }
+            Expr::Logical(expr) => {
+                self.resolve_expr(&expr.lhs);
+                self.resolve_expr(&expr.rhs);
+            }
+            Expr::Set(expr) => {
+                self.resolve_expr(&expr.value);
+                self.resolve_expr(&expr.object);
+            }
+            Expr::Super(expr) => match self.class {
+                ClassKind::None => self.outside_class(&expr.keyword),
+                ClassKind::Class => self.errors.push(
+                    Diag::error("resolve::super_without_superclass")
+                        .msg("Can't use `super` in a class with no superclass")
+                        .source(self.source)
+                        .primary(expr.keyword.position.span(), "used here")
+                        .help("a class can inherit from another using `class Name < Superclass`")
+                        .into(),
+                ),
+                ClassKind::Subclass => self.resolve_local(&expr.keyword, &expr.depth),
+            },
+            Expr::This(expr) => {
+                if self.class == ClassKind::None {
+                    self.outside_class(&expr.keyword);
+                } else {
+                    self.resolve_local(&expr.keyword, &expr.depth);
+                }
+            }
+            Expr::Try(expr) => {
+                let span = expr.keyword.position.span();
+                match self.function {
+                    FunctionKind::None => self.errors.push(
+                        Diag::error("resolve::try_outside_function")
+                            .msg("Can't use `try` outside of a function")
+                            .source(self.source)
+                            .primary(span, "used here")
+                            .help("`try` returns errors from the enclosing function, use `try {{ ... }} catch` at the top level")
+                            .into(),
+                    ),
+                    FunctionKind::Initializer => self.return_value_from_initializer(span),
+                    FunctionKind::Function | FunctionKind::Method => {
+                        self.fails.get_or_insert(span);
+                    }
+                }
+                self.resolve_expr(&expr.expr);
+            }
+            Expr::Unary(expr) => self.resolve_expr(&expr.rhs),
+            Expr::Variable(expr) => {
+                let name = expr.name.lexeme();
+                let declared_only = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name))
+                    .is_some_and(|binding| !binding.defined);
+                if declared_only {
+                    self.errors.push(
+                        Diag::error("resolve::read_in_own_initializer")
+                            .arg("name", name)
+                            .msg("Can't read `{name}` in its own initializer")
+                            .source(self.source)
+                            .primary(expr.name.position.span(), "read here")
+                            .help("if you meant to shadow an outer `{name}`, give the new variable another name")
+                            .into(),
+                    );
+                }
+                self.resolve_local(&expr.name, &expr.depth);
+            }
+        }
+    }
+
+    /// Warns about the arms of `expr` that can't ever be chosen: those after
+    /// an unguarded arm matching anything, and those matching the same value
+    /// as an earlier unguarded arm.
+    fn check_unreachable_arms(&mut self, expr: &MatchExpr) {
+        let mut catch_all = None;
+        let mut literals: Vec<(Value, SourceSpan)> = vec![];
+        for arm in &expr.arms {
+            let span = arm.pattern.position().span();
+            let earlier = catch_all.or_else(|| match &arm.pattern {
+                Pattern::Literal { value, .. } => {
+                    let value = Value::from(value);
+                    let earlier = literals.iter().find(|(literal, _)| *literal == value);
+                    earlier.map(|(_, span)| *span)
+                }
+                _ => None,
+            });
+            if let Some(earlier) = earlier {
+                self.warnings.push(
+                    Diag::warning("resolve::unreachable_arm")
+                        .msg("Unreachable `match` arm")
+                        .source(self.source)
+                        .primary(span, "never matched")
+                        .secondary(earlier, "already matched here")
+                        .help("remove the arm or move it above the arm matching its values")
+                        .into(),
+                );
+                continue;
+            }
+            if arm.guard.is_some() {
+                continue;
+            }
+            match &arm.pattern {
+                Pattern::Literal { value, .. } => literals.push((value.into(), span)),
+                Pattern::Wildcard(_) | Pattern::Binding(_) => catch_all = Some(span),
+            }
+        }
+    }
+
+    /// Records the distance to the innermost scope declaring `name`, leaving
+    /// `depth` unset if it is a global.
+    fn resolve_local(&mut self, name: &Token, depth: &Cell<Option<usize>>) {
+        let name = name.lexeme();
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name));
+        depth.set(found);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        let span = name.position.span();
+        if let Some(previous) = scope.get(&name.lexeme()) {
+            self.errors.push(
+                Diag::error("resolve::duplicate_declaration")
+                    .arg("name", name.lexeme())
+                    .msg("`{name}` is already declared in this scope")
+                    .source(self.source)
+                    .primary(span, "declared again here")
+                    .secondary(previous.span, "first declared here")
+                    .into(),
+            );
+            return;
+        }
+        scope.insert(
+            name.lexeme(),
+            Binding {
+                defined: false,
+                span,
+                fails: None,
+            },
+        );
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(binding) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme()))
+        {
+            binding.defined = true;
+        }
+    }
+
+    /// Defines an implicit binding like `this` or `super` in the innermost
+    /// scope, attributing it to `token` for diagnostics.
+    fn define_synthetic(&mut self, name: &str, token: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(
+                name.to_string(),
+                Binding {
+                    defined: true,
+                    span: token.position.span(),
+                    fails: None,
+                },
+            );
+        }
+    }
+
+    fn outside_class(&mut self, keyword: &Token) {
+        self.errors.push(
+            Diag::error("resolve::outside_class")
+                .arg("keyword", keyword.lexeme())
+                .msg("Can't use `{keyword}` outside of a class")
+                .source(self.source)
+                .primary(keyword.position.span(), "used here")
+                .into(),
+        );
+    }
+
+    fn return_value_from_initializer(&mut self, span: SourceSpan) {
+        self.errors.push(
+            Diag::error("resolve::return_value_from_initializer")
+                .msg("Can't return a value from an initializer")
+                .source(self.source)
+                .primary(span, "returned here")
+                .help("`init` always returns `this`, use a bare `return;` instead")
+                .into(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        errors::{ResolveError, ResolveWarning},
+        lexer::Lexer,
+        parser::Parser,
+        source::SourceFile,
+    };
+
+    use super::Resolver;
+
+    fn resolve(source: &str) -> Result<Vec<ResolveWarning>, Vec<ResolveError>> {
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        Resolver::new(source).resolve(&stmts)
+    }
+
+    #[test]
+    fn check_valid_programs() {
+        assert!(resolve("let a = 1; let a = a + 1; { let a = 2; }").is_ok());
+        assert!(resolve("fn f(a) { { let b = a; let a = b; } return a; }").is_ok());
+        assert!(resolve("class A { fn init() { this.a = 1; return; } }").is_ok());
+        assert!(resolve("class A {} class B < A { fn f() { return super.f; } }").is_ok());
+        assert!(resolve("try { let e = 1; } catch e { let a = e; }").is_ok());
+        assert!(resolve("while true { for ;; { break; } if a { continue; } }").is_ok());
+        assert!(resolve("fn f(a) { guard a else { return; } guard a > 1 else { if a { return 1; } else { throw a; } } }").is_ok());
+    }
+
+    #[test]
+    fn check_errors() {
+        for (source, expected) in [
+            ("{ let a = a; }", &["read_in_own_initializer"][..]),
+            (
+                "fn f(a) { let a = 1; let b; let b; }",
+                &["duplicate_declaration", "duplicate_declaration"],
+            ),
+            (
+                "export let a = 1; fn f() { export fn g() {} }",
+                &["nested_export"],
+            ),
+            ("return 1;", &["return_outside_function"]),
+            (
+                "class A { fn init() { return 1; } }",
+                &["return_value_from_initializer"],
+            ),
+            (
+                "this; fn f() { super.f(); }",
+                &["outside_class", "outside_class"],
+            ),
+            (
+                "class A { fn f() { super.f(); } }",
+                &["super_without_superclass"],
+            ),
+            ("try {} catch e { let e = 1; }", &["duplicate_declaration"]),
+            (
+                "fn f(a) { guard a else { print a; } guard a else { if a { return; } } }",
+                &["guard_falls_through", "guard_falls_through"],
+            ),
+            ("class A < A {}", &["inherits_itself"]),
+            (
+                "try f(); class A { fn init() { try f(); } }",
+                &["try_outside_function", "return_value_from_initializer"],
+            ),
+            (
+                "break; while true { fn f() { continue; } }",
+                &["outside_loop", "outside_loop"],
+            ),
+        ] {
+            let errors = resolve(source).unwrap_err();
+            let codes = errors
+                .iter()
+                .map(|err| err.code.trim_start_matches("resolve::"));
+            assert_eq!(codes.collect::<Vec<_>>(), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn check_warnings() {
+        let warnings = resolve(
+            "
+            fn parse(s) { if s == \"\" { return Error(\"parse\", \"empty\", nil); } return s; }
+            fn load(s) { let a = try parse(s); return a; }
+            fn run() {
+                fn inner() { return nil; }
+                inner();
+                load(\"a\");
+                try load(\"b\");
+                let c = load(\"c\");
+            }
+            parse(\"d\");
+            ",
+        )
+        .unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].rule(), Some("ignored_error"));
+        assert_eq!(warnings[1].rule(), Some("ignored_error"));
+        assert_eq!(warnings[0].value("name"), Some("load"));
+        assert_eq!(warnings[0].span().len(), "load(\"a\")".len());
+
+        assert!(resolve("fn f() { return Error(1, 2, 3); } let f = 1; f();")
+            .unwrap()
+            .is_empty());
+        assert!(
+            resolve("fn f() { fn g() { return Error(1, 2, 3); } return g; } f();")
+                .unwrap()
+                .is_empty()
+        );
+
+        let warnings =
+            resolve("match 1 { 1 if false => 0, 1.0 => 1, x if x => 2, 1 => 3, x => x, _ => 4 };")
+                .unwrap();
+        let offsets = warnings.iter().map(|warning| {
+            assert_eq!(warning.rule(), Some("unreachable_arm"));
+            (warning.labels[0].offset, warning.labels[1].offset)
+        });
+        assert_eq!(offsets.collect::<Vec<_>>(), [(50, 27), (66, 58)]);
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    cell::RefCell,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{interpreter::Value, native::NativeError};
+
+/// The snapshots of a test file, which `expect_snapshot(name, value)`
+/// compares values to. They are kept in `snapshots/<file>/<name>.snap`
+/// next to the test file, holding the printed value.
+pub(crate) struct Snapshots {
+    dir: PathBuf,
+    /// Whether snapshots are written rather than compared, to record new
+    /// ones or accept changed values.
+    bless: bool,
+    /// The snapshots that didn't match since last taken, shared with the
+    /// `expect_snapshot` builtin.
+    mismatches: Rc<RefCell<Vec<Mismatch>>>,
+}
+
+/// A value that didn't match its snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mismatch {
+    pub(crate) name: String,
+    pub(crate) path: String,
+    pub(crate) expected: String,
+    pub(crate) actual: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl Snapshots {
+    /// The snapshots of the test file at `path`.
+    pub(crate) fn new(path: &Path, bless: bool) -> Self {
+        let stem = path.file_stem().unwrap_or_default();
+        let dir = path.with_file_name("snapshots").join(stem);
+        Snapshots {
+            dir,
+            bless,
+            mismatches: Rc::default(),
+        }
+    }
+
+    /// The `expect_snapshot` builtin comparing values to these snapshots.
+    pub(crate) fn expect(&self) -> impl Fn(&[Value]) -> Result<(), NativeError> + 'static {
+        let (dir, bless) = (self.dir.clone(), self.bless);
+        let mismatches = Rc::clone(&self.mismatches);
+        move |args| {
+            let name = String::try_from(&args[0])?;
+            let valid = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '-';
+            if name.is_empty() || !name.chars().all(valid) {
+                let message =
+                    format!("invalid snapshot name `{name}`, use letters, digits, `_` and `-`");
+                return Err(NativeError::new("snapshot", &message, name.as_str()));
+            }
+            let path = dir.join(format!("{name}.snap"));
+            let io_error = |err| {
+                let message = format!("{}: {err}", path.display());
+                NativeError::new("io", &message, path.display().to_string())
+            };
+            let actual = format!("{}\n", args[1]);
+            if bless {
+                fs::create_dir_all(&dir).map_err(io_error)?;
+                return fs::write(&path, actual).map_err(io_error);
+            }
+            let expected = match fs::read_to_string(&path) {
+                Ok(expected) => expected,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    let message = format!(
+                        "no snapshot `{name}` at {}, run with FOX_BLESS=1 to record it",
+                        path.display()
+                    );
+                    return Err(NativeError::new("snapshot", &message, name.as_str()));
+                }
+                Err(err) => return Err(io_error(err)),
+            };
+            if expected == actual {
+                return Ok(());
+            }
+            let message = format!("value doesn't match snapshot `{name}`");
+            mismatches.borrow_mut().push(Mismatch {
+                name: name.clone(),
+                path: path.display().to_string(),
+                expected,
+                actual,
+            });
+            Err(NativeError::new("snapshot", &message, name.as_str()))
+        }
+    }
+
+    /// Returns the snapshots that didn't match since the last call.
+    pub(crate) fn take_mismatches(&self) -> Vec<Mismatch> {
+        std::mem::take(&mut self.mismatches.borrow_mut())
+    }
+}
+
+impl Mismatch {
+    /// Describes the mismatch by the lines of the snapshot that were
+    /// removed, in red if `color`, and the ones the value has instead, in
+    /// green.
+    pub(crate) fn render(&self, color: bool) -> String {
+        let mut out = format!(
+            "snapshot `{}` ({}), run with FOX_BLESS=1 to accept the new value:\n",
+            self.name, self.path
+        );
+        let paint = |code: &str, line: String| {
+            if color {
+                format!("\x1b[{code}m{line}\x1b[0m")
+            } else {
+                line
+            }
+        };
+        for line in diff_lines(&self.expected, &self.actual) {
+            let line = match line {
+                Line::Same(line) => format!(" {line}"),
+                Line::Removed(line) => paint("31", format!("-{line}")),
+                Line::Added(line) => paint("32", format!("+{line}")),
+            };
+            let _ = writeln!(out, "{line}");
+        }
+        out
+    }
+}
+
+/// The lines of `old` and `new`, with those that aren't in a longest
+/// common subsequence of both marked as removed or added.
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    // `common[i][j]` is the length of the longest common subsequence of
+    // `old[i..]` and `new[j..]`.
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff_lines, Line, Mismatch};
+
+    #[test]
+    fn check_diff_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\nd\n", "a\nc\nx\nd\n"),
+            [
+                Line::Same("a"),
+                Line::Removed("b"),
+                Line::Same("c"),
+                Line::Added("x"),
+                Line::Same("d"),
+            ]
+        );
+        assert_eq!(diff_lines("", "a\n"), [Line::Added("a")]);
+
+        let mismatch = Mismatch {
+            name: "sum".to_string(),
+            path: "snapshots/math_test/sum.snap".to_string(),
+            expected: "1\n".to_string(),
+            actual: "2\n".to_string(),
+        };
+        assert!(mismatch.render(false).ends_with(":\n-1\n+2\n"));
+        assert!(mismatch
+            .render(true)
+            .ends_with(":\n\x1b[31m-1\x1b[0m\n\x1b[32m+2\x1b[0m\n"));
+    }
+}
@@ -0,0 +1,251 @@
+use std::sync::Arc;
+
+use miette::{NamedSource, SourceSpan};
+
+/// The code of a script along with the name diagnostics refer to it by,
+/// like its path or `<repl>`. Every stage from the `Lexer` to the
+/// `Interpreter` takes one so that its errors can point into the script.
+///
+/// The code is shared rather than copied by clones and by the diagnostics
+/// of errors, so that a script with many errors isn't copied for each.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    name: Arc<str>,
+    text: Arc<str>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        SourceFile {
+            name: name.into().into(),
+            text: text.into().into(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The source code attached to the diagnostics of errors in the script.
+    pub fn named_source(&self) -> NamedSource {
+        NamedSource::new(&self.name, Arc::clone(&self.text))
+    }
+}
+
+impl Default for SourceFile {
+    fn default() -> Self {
+        SourceFile::new("", "")
+    }
+}
+
+/// The unit columns are counted in. Tools disagree: spans are in bytes,
+/// the LSP counts UTF-16 code units by default, and people count
+/// characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    Utf16,
+    Char,
+}
+
+/// A line and column, both counting from 0, with the column in some
+/// `PositionEncoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Converts between byte offsets into a script and lines and columns, in
+/// any `PositionEncoding`. A line ends after its `\n`, and a `\r` before
+/// it is not part of the columns of the line.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    text: Arc<str>,
+    /// Byte offset of the start of every line.
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &SourceFile) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex {
+            text: Arc::clone(&source.text),
+            line_starts,
+        }
+    }
+
+    /// Whether this is the index of `source`, rather than of another
+    /// script or of an older version of it.
+    pub fn indexes(&self, source: &SourceFile) -> bool {
+        Arc::ptr_eq(&self.text, &source.text)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The line and column of the byte `offset`, or `None` if it is past
+    /// the end of the script or inside a character.
+    pub fn line_col(&self, offset: usize, encoding: PositionEncoding) -> Option<LineCol> {
+        if !self.text.is_char_boundary(offset) {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let before = &self.text[self.line_starts[line]..offset];
+        let col = match encoding {
+            PositionEncoding::Utf8 => before.len(),
+            PositionEncoding::Utf16 => before.encode_utf16().count(),
+            PositionEncoding::Char => before.chars().count(),
+        };
+        Some(LineCol { line, col })
+    }
+
+    /// The byte offset of `position`, or `None` if it is past the end of its
+    /// line or inside a character.
+    pub fn offset(&self, position: LineCol, encoding: PositionEncoding) -> Option<usize> {
+        let start = *self.line_starts.get(position.line)?;
+        let end = self
+            .line_starts
+            .get(position.line + 1)
+            .map_or(self.text.len(), |&next| next);
+        let line = &self.text[start..end];
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        let mut col = 0;
+        for (i, ch) in line.char_indices() {
+            if col >= position.col {
+                return (col == position.col).then_some(start + i);
+            }
+            col += match encoding {
+                PositionEncoding::Utf8 => ch.len_utf8(),
+                PositionEncoding::Utf16 => ch.len_utf16(),
+                PositionEncoding::Char => 1,
+            };
+        }
+        (col == position.col).then_some(start + line.len())
+    }
+}
+
+/// Combines the spans diagnostics point at, in either order.
+pub trait Span {
+    /// The span from the start of the first of the two spans to the end of
+    /// the last one, covering both.
+    fn join(&self, other: &Self) -> Self;
+
+    /// The span from the end of the first of the two spans to the start of
+    /// the last one, which is empty if they touch or overlap.
+    fn between(&self, other: &Self) -> Self;
+}
+
+impl Span for SourceSpan {
+    fn join(&self, other: &Self) -> Self {
+        let start = self.offset().min(other.offset());
+        let end = (self.offset() + self.len()).max(other.offset() + other.len());
+        (start, end - start).into()
+    }
+
+    fn between(&self, other: &Self) -> Self {
+        let (first, last) = if self.offset() <= other.offset() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let start = first.offset() + first.len();
+        (start, last.offset().saturating_sub(start)).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use miette::SourceSpan;
+
+    use super::{LineCol, LineIndex, PositionEncoding, SourceFile, Span};
+
+    #[test]
+    fn check_spans() {
+        let a: SourceSpan = (2, 3).into();
+        let b: SourceSpan = (8, 2).into();
+        let joined = a.join(&b);
+        assert_eq!((joined.offset(), joined.len()), (2, 8));
+        assert_eq!(b.join(&a).offset(), 2);
+        let gap = b.between(&a);
+        assert_eq!((gap.offset(), gap.len()), (5, 3));
+        let overlap = a.between(&(3, 4).into());
+        assert_eq!((overlap.offset(), overlap.len()), (5, 0));
+    }
+
+    #[test]
+    fn check_sharing() {
+        let source = SourceFile::new("test.fox", "print 1;");
+        let clone = source.clone();
+        assert!(std::ptr::eq(source.text(), clone.text()));
+        assert_eq!(clone.name(), "test.fox");
+    }
+
+    #[test]
+    fn check_line_index() {
+        let sources = [
+            "",
+            "print 1;",
+            "let fox = \"🦊\";\r\nprint fox + \"é\";\n",
+            "\n\n// ¿qué dice el zorro?\nlet 狐 = \"𝕗𝕠𝕩\";",
+        ];
+        let encodings = [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Char,
+        ];
+        for text in sources {
+            let index = LineIndex::new(&SourceFile::new("test.fox", text));
+            assert_eq!(
+                index.line_count(),
+                text.lines().count().max(1) + text.ends_with('\n') as usize
+            );
+            for offset in 0..=text.len() + 1 {
+                for encoding in encodings {
+                    let position = index.line_col(offset, encoding);
+                    if !text.is_char_boundary(offset) {
+                        assert_eq!(position, None);
+                        continue;
+                    }
+                    let position = position.unwrap();
+                    // Between a `\r` and its `\n` is past the end of the line.
+                    let in_crlf =
+                        text[..offset].ends_with('\r') && text[offset..].starts_with('\n');
+                    let expected = (!in_crlf).then_some(offset);
+                    assert_eq!(
+                        index.offset(position, encoding),
+                        expected,
+                        "{text:?} {offset}"
+                    );
+                }
+            }
+        }
+
+        let source = SourceFile::new("test.fox", "a\n🦊b");
+        let index = LineIndex::new(&source);
+        assert!(index.indexes(&source.clone()));
+        assert!(!index.indexes(&SourceFile::new("test.fox", "a\n🦊b")));
+        let utf16 = PositionEncoding::Utf16;
+        assert_eq!(index.line_col(6, utf16), Some(LineCol { line: 1, col: 2 }));
+        assert_eq!(
+            index.line_col(6, PositionEncoding::Utf8),
+            Some(LineCol { line: 1, col: 4 })
+        );
+        assert_eq!(
+            index.line_col(6, PositionEncoding::Char),
+            Some(LineCol { line: 1, col: 1 })
+        );
+        assert_eq!(index.offset(LineCol { line: 1, col: 1 }, utf16), None);
+        assert_eq!(index.offset(LineCol { line: 1, col: 4 }, utf16), None);
+        assert_eq!(index.offset(LineCol { line: 2, col: 0 }, utf16), None);
+    }
+}
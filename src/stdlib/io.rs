@@ -0,0 +1,34 @@
+use std::{fs, io};
+
+use crate::{interpreter::Value, native::NativeError};
+
+use super::{function, Members};
+
+pub(super) fn members() -> Members {
+    vec![
+        function("read_file", 1, |args| {
+            let path = String::try_from(&args[0])?;
+            fs::read_to_string(&path).map_err(|err| io_error(&path, err))
+        }),
+        function("write_file", 2, |args| {
+            let path = String::try_from(&args[0])?;
+            let text = String::try_from(&args[1])?;
+            fs::write(&path, text).map_err(|err| io_error(&path, err))
+        }),
+        function("read_line", 0, |_| {
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) => Ok(Value::Nil),
+                Ok(_) => Ok(Value::String(
+                    line.trim_end_matches(['\n', '\r']).to_string(),
+                )),
+                Err(err) => Err(io_error("<stdin>", err)),
+            }
+        }),
+    ]
+}
+
+/// An error of kind `io` with the path it happened at as data.
+fn io_error(path: &str, err: io::Error) -> NativeError {
+    NativeError::new("io", &format!("{path}: {err}"), path)
+}
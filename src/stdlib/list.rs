@@ -0,0 +1,152 @@
+use std::cmp::Ordering;
+
+use crate::{
+    interpreter::Value,
+    list::List,
+    native::{new_list, CallError, NativeError},
+};
+
+use super::{function, higher_order, Members};
+
+pub(super) fn members() -> Members {
+    vec![
+        function("len", 1, |args| {
+            Ok(list(&args[0])?.elements.borrow().len() as i64)
+        }),
+        function("contains", 2, |args| {
+            Ok(list(&args[0])?.elements.borrow().contains(&args[1]))
+        }),
+        function("join", 2, |args| {
+            let separator = String::try_from(&args[1])?;
+            let elements = list(&args[0])?.elements.borrow();
+            let elements = elements.iter().map(Value::to_string);
+            Ok(elements.collect::<Vec<_>>().join(&separator))
+        }),
+        function("push", 2, |args| {
+            let list = mutable_list(&args[0])?;
+            list.elements.borrow_mut().push(args[1].clone());
+            Ok(())
+        }),
+        function("pop", 1, |args| {
+            let list = mutable_list(&args[0])?;
+            let popped = list.elements.borrow_mut().pop();
+            popped.ok_or_else(|| {
+                NativeError::new("index", "can't pop from an empty list", Value::Nil)
+            })
+        }),
+        // The elements are copied out before calling back, so that the
+        // function called can change the list without affecting the loop.
+        higher_order("map", 2, 0, |interpreter, args| {
+            let mut mapped = vec![];
+            for element in elements(&args[0])? {
+                mapped.push(interpreter.call_back(&args[1], vec![element])?);
+            }
+            Ok(new_list(mapped))
+        }),
+        higher_order("filter", 2, 0, |interpreter, args| {
+            let mut kept = vec![];
+            for element in elements(&args[0])? {
+                if interpreter
+                    .call_back(&args[1], vec![element.clone()])?
+                    .is_truthy()
+                {
+                    kept.push(element);
+                }
+            }
+            Ok(new_list(kept))
+        }),
+        higher_order("reduce", 3, 0, |interpreter, args| {
+            let mut accumulator = args[2].clone();
+            for element in elements(&args[0])? {
+                accumulator = interpreter.call_back(&args[1], vec![accumulator, element])?;
+            }
+            Ok(accumulator)
+        }),
+        higher_order("sort", 2, 1, |interpreter, args| {
+            mutable_list(&args[0])?;
+            let sorted = match args.get(1) {
+                Some(compare) => sort(elements(&args[0])?, &mut |a, b| {
+                    let order = interpreter.call_back(compare, vec![a.clone(), b.clone()])?;
+                    match order {
+                        Value::Int(int) => Ok(int.cmp(&0)),
+                        Value::Float(float) => Ok(float.total_cmp(&0.0)),
+                        order => Err(format!(
+                            "expected the comparator to return a number, found {}",
+                            order.type_name()
+                        )
+                        .into()),
+                    }
+                })?,
+                None => sort(elements(&args[0])?, &mut |a, b| Ok(compare(a, b)?))?,
+            };
+            // The comparator may have frozen the list.
+            *mutable_list(&args[0])?.elements.borrow_mut() = sorted;
+            Ok(())
+        }),
+    ]
+}
+
+/// The list `value` is, or an error naming what it is instead.
+fn list(value: &Value) -> Result<&List, NativeError> {
+    match value {
+        Value::List(list) => Ok(list),
+        value => Err(format!("expected a list, found {}", value.type_name()).into()),
+    }
+}
+
+/// Like `list`, but fails if the list is frozen.
+fn mutable_list(value: &Value) -> Result<&List, NativeError> {
+    let list = list(value)?;
+    if list.frozen.get() {
+        let message = "can't change a frozen list, consider copying it instead";
+        return Err(NativeError::new("frozen", message, Value::Nil));
+    }
+    Ok(list)
+}
+
+/// A copy of the elements of the list `value`.
+fn elements(value: &Value) -> Result<Vec<Value>, NativeError> {
+    Ok(list(value)?.elements.borrow().clone())
+}
+
+/// The order of `a` and `b` when sorted without a comparator, which only
+/// numbers and strings have.
+fn compare(a: &Value, b: &Value) -> Result<Ordering, NativeError> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Ok(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (a, b) if let (Some(x), Some(y)) = (a.as_float(), b.as_float()) => Ok(x.total_cmp(&y)),
+        (a, b) => Err(format!(
+            "can't compare {} and {}, pass a comparator to sort them",
+            a.type_name(),
+            b.type_name()
+        )
+        .into()),
+    }
+}
+
+/// Sorts `elements` stably by `compare` using a merge sort, which unlike
+/// the one of the standard library stops at the first error and doesn't
+/// panic if `compare` isn't a total order.
+fn sort(
+    mut elements: Vec<Value>,
+    compare: &mut dyn FnMut(&Value, &Value) -> Result<Ordering, CallError>,
+) -> Result<Vec<Value>, CallError> {
+    if elements.len() <= 1 {
+        return Ok(elements);
+    }
+    let right = elements.split_off(elements.len() / 2);
+    let (left, right) = (sort(elements, compare)?, sort(right, compare)?);
+    let mut sorted = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        let next = if compare(b, a)? == Ordering::Less {
+            right.next()
+        } else {
+            left.next()
+        };
+        sorted.extend(next);
+    }
+    sorted.extend(left.chain(right));
+    Ok(sorted)
+}
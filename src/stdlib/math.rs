@@ -0,0 +1,39 @@
+use std::f64::consts;
+
+use crate::{interpreter::Value, native::NativeError};
+
+use super::{function, Members};
+
+pub(super) fn members() -> Members {
+    vec![
+        ("pi", Value::Float(consts::PI)),
+        ("e", Value::Float(consts::E)),
+        function("abs", 1, |args| match &args[0] {
+            Value::Int(int) => int
+                .checked_abs()
+                .map(Value::Int)
+                .ok_or_else(|| NativeError::new("overflow", "integer overflow", Value::Nil)),
+            value => Ok(Value::Float(f64::try_from(value)?.abs())),
+        }),
+        function("sqrt", 1, |args| Ok(f64::try_from(&args[0])?.sqrt())),
+        function("pow", 2, |args| {
+            Ok(f64::try_from(&args[0])?.powf(f64::try_from(&args[1])?))
+        }),
+        function("floor", 1, |args| Ok(f64::try_from(&args[0])?.floor())),
+        function("ceil", 1, |args| Ok(f64::try_from(&args[0])?.ceil())),
+        function("round", 1, |args| Ok(f64::try_from(&args[0])?.round())),
+        function("min", 2, |args| pick(args, |a, b| a <= b)),
+        function("max", 2, |args| pick(args, |a, b| a >= b)),
+    ]
+}
+
+/// The first of two numbers if `keep_first` holds for them, or else the
+/// second, as is.
+fn pick(args: &[Value], keep_first: fn(f64, f64) -> bool) -> Result<Value, NativeError> {
+    let (a, b) = (f64::try_from(&args[0])?, f64::try_from(&args[1])?);
+    Ok(if keep_first(a, b) {
+        args[0].clone()
+    } else {
+        args[1].clone()
+    })
+}
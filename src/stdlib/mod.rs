@@ -0,0 +1,158 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, Value},
+    module::Module,
+    native::{CallError, NativeError, NativeFn, NativeFunction},
+    source::SourceFile,
+};
+
+mod io;
+mod list;
+mod math;
+mod string;
+mod time;
+
+/// The variables a native module exports, by name.
+type Members = Vec<(&'static str, Value)>;
+
+/// Creates the members of a native module.
+type Definition = fn() -> Members;
+
+/// The modules of the standard library, available as `std.<name>`. Adding
+/// a module takes a file returning its members and an entry here, and adding
+/// a function to one only takes its file.
+const MODULES: [(&str, Definition); 5] = [
+    ("io", io::members),
+    ("list", list::members),
+    ("math", math::members),
+    ("string", string::members),
+    ("time", time::members),
+];
+
+/// The `std` module every interpreter defines as a global, which exports
+/// the modules of the standard library.
+pub(crate) fn module() -> Module {
+    let modules = MODULES.iter().map(|(name, members)| {
+        let module = native_module(&format!("std.{name}"), members());
+        (*name, Value::Module(Rc::new(module)))
+    });
+    native_module("std", modules.collect())
+}
+
+fn native_module(name: &str, members: Members) -> Module {
+    let mut globals = Environment::new();
+    for (member, value) in &members {
+        globals.define(member, value.clone());
+    }
+    let source = SourceFile::new(format!("<{name}>"), "");
+    let module = Module::new(name.to_string(), source, Rc::new(RefCell::new(globals)));
+    for (member, _) in members {
+        module.export(member.to_string());
+    }
+    module
+}
+
+/// A member that is a function taking `arity` arguments, see
+/// `Interpreter::define_native`.
+fn function<F, R>(name: &'static str, arity: usize, function: F) -> (&'static str, Value)
+where
+    F: Fn(&[Value]) -> Result<R, NativeError> + 'static,
+    R: Into<Value>,
+{
+    let native = NativeFunction {
+        name: name.to_string(),
+        arity,
+        optional: 0,
+        function: NativeFn::Plain(Box::new(move |args| function(args).map(Into::into))),
+    };
+    (name, Value::Native(Rc::new(native)))
+}
+
+/// A member that is a function calling back into Fox code, taking `arity`
+/// arguments of which the last `optional` can be left out.
+fn higher_order<F, R>(
+    name: &'static str,
+    arity: usize,
+    optional: usize,
+    function: F,
+) -> (&'static str, Value)
+where
+    F: Fn(&mut Interpreter, &[Value]) -> Result<R, CallError> + 'static,
+    R: Into<Value>,
+{
+    let native = NativeFunction {
+        name: name.to_string(),
+        arity,
+        optional,
+        function: NativeFn::HigherOrder(Box::new(move |interpreter, args| {
+            function(interpreter, args).map(Into::into)
+        })),
+    };
+    (name, Value::Native(Rc::new(native)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::{engine::Fox, errors::FoxError, interpreter::Value};
+
+    #[test]
+    fn check_std() {
+        let mut fox = Fox::new();
+        let mut eval = |source: &str| fox.eval(source).unwrap();
+        assert_eq!(eval("std.math.sqrt(16);"), Value::Float(4.0));
+        assert_eq!(eval("std.math.max(2, 3);"), Value::Int(3));
+        assert_eq!(eval("std.math.min(2, 0.5);"), Value::Float(0.5));
+        assert_eq!(eval("std.math.abs(-3);"), Value::Int(3));
+        assert_eq!(eval("std.math.floor(std.math.pi);"), Value::Float(3.0));
+        assert_eq!(
+            eval("std.string.upper(\"fox\") + std.string.trim(\" !\\n\");"),
+            Value::String("FOX!".to_string())
+        );
+        assert_eq!(eval("std.string.len(\"ünï\");"), Value::Int(3));
+        assert_eq!(
+            eval("std.string.starts_with(\"foxes\", \"fox\");"),
+            Value::Bool(true)
+        );
+        assert_eq!(eval("std.list.len([1, 2, 3]);"), Value::Int(3));
+        assert_eq!(
+            eval("std.list.contains([1, \"a\"], \"a\");"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval("std.list.join([1, \"a\", nil], \", \");"),
+            Value::String("1, a, nil".to_string())
+        );
+        assert_eq!(eval("std.time.now() > 1000000000;"), Value::Bool(true));
+        assert_eq!(
+            eval("\"${std} ${std.math}\";"),
+            Value::String("<module std> <module std.math>".to_string())
+        );
+
+        let path = std::env::temp_dir().join(format!("fox-std-{}.txt", std::process::id()));
+        let path = path.display().to_string().replace('\\', "/");
+        assert_eq!(
+            eval(&format!(
+                "std.io.write_file(\"{path}\", \"hi\"); std.io.read_file(\"{path}\");"
+            )),
+            Value::String("hi".to_string())
+        );
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            eval(&format!(
+                "let kind; try {{ std.io.read_file(\"{path}\"); }} catch e {{ kind = e.kind; }} kind;"
+            )),
+            Value::String("io".to_string())
+        );
+
+        assert!(matches!(
+            fox.eval("std.math.tau;"),
+            Err(FoxError::Runtime(err)) if err.code == "runtime::not_exported"
+                && err.value("name") == Some("tau")
+                && err.value("module") == Some("std.math")
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{
+    interpreter::Value,
+    native::{new_list, NativeError},
+    range::Range,
+};
+
+use super::{function, Members};
+
+pub(super) fn members() -> Members {
+    vec![
+        function("len", 1, |args| {
+            Ok(String::try_from(&args[0])?.chars().count() as i64)
+        }),
+        function("upper", 1, |args| {
+            Ok(String::try_from(&args[0])?.to_uppercase())
+        }),
+        function("lower", 1, |args| {
+            Ok(String::try_from(&args[0])?.to_lowercase())
+        }),
+        function("trim", 1, |args| {
+            Ok(String::try_from(&args[0])?.trim().to_string())
+        }),
+        function("contains", 2, |args| {
+            Ok(String::try_from(&args[0])?.contains(&String::try_from(&args[1])?))
+        }),
+        function("starts_with", 2, |args| {
+            Ok(String::try_from(&args[0])?.starts_with(&String::try_from(&args[1])?))
+        }),
+        function("ends_with", 2, |args| {
+            Ok(String::try_from(&args[0])?.ends_with(&String::try_from(&args[1])?))
+        }),
+        function("split", 2, |args| {
+            let (string, separator) = (String::try_from(&args[0])?, String::try_from(&args[1])?);
+            let parts = if separator.is_empty() {
+                string.chars().map(|char| char.to_string().into()).collect()
+            } else {
+                string.split(&separator).map(Value::from).collect()
+            };
+            Ok(new_list(parts))
+        }),
+        function("slice", 3, |args| {
+            let string = String::try_from(&args[0])?;
+            let range = Range {
+                start: i64::try_from(&args[1])?,
+                end: i64::try_from(&args[2])?,
+                inclusive: false,
+            };
+            let len = string.chars().count();
+            let Some(positions) = range.slice(len) else {
+                let message = format!("range {range} is out of bounds for a length of {len}");
+                return Err(NativeError::new("index", &message, Value::Nil));
+            };
+            let chars = string.chars().skip(positions.start);
+            Ok(chars.take(positions.len()).collect::<String>())
+        }),
+    ]
+}
@@ -0,0 +1,22 @@
+use std::{
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{function, Members};
+
+pub(super) fn members() -> Members {
+    vec![
+        function("now", 0, |_| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH);
+            Ok(now.map_or(0.0, |now| now.as_secs_f64()))
+        }),
+        function("sleep", 1, |args| {
+            let seconds = f64::try_from(&args[0])?;
+            let duration = Duration::try_from_secs_f64(seconds)
+                .map_err(|_| format!("expected a duration in seconds, found {seconds}"))?;
+            thread::sleep(duration);
+            Ok(())
+        }),
+    ]
+}
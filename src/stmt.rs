@@ -0,0 +1,318 @@
+use std::{fmt::Display, rc::Rc};
+
+use serde::Serialize;
+
+use crate::{
+    expr::{Expr, VariableExpr},
+    lexer::{Position, Token},
+};
+
+#[derive(Serialize)]
+pub enum Stmt {
+    Block(Vec<Stmt>),
+    /// `break`, holding its keyword.
+    Break(Token),
+    Class(ClassStmt),
+    /// `continue`, holding its keyword.
+    Continue(Token),
+    Export(ExportStmt),
+    Expression(Expr),
+    For(ForStmt),
+    ForIn(ForInStmt),
+    Function(Rc<FunctionStmt>),
+    Guard(GuardStmt),
+    If(IfStmt),
+    Import(ImportStmt),
+    Let(LetStmt),
+    Print(PrintStmt),
+    Return(ReturnStmt),
+    Throw(ThrowStmt),
+    Try(TryStmt),
+    While(WhileStmt),
+}
+
+#[derive(Serialize)]
+pub struct ClassStmt {
+    pub name: Token,
+    pub superclass: Option<VariableExpr>,
+    pub methods: Vec<Rc<FunctionStmt>>,
+}
+
+/// `export` followed by a `class`, `fn` or `let` declaration, which modules
+/// importing the file can then use.
+#[derive(Serialize)]
+pub struct ExportStmt {
+    pub keyword: Token,
+    pub declaration: Box<Stmt>,
+}
+
+impl ExportStmt {
+    /// The name of the exported class, function or variable.
+    pub fn name(&self) -> &Token {
+        match &*self.declaration {
+            Stmt::Class(stmt) => &stmt.name,
+            Stmt::Function(stmt) => &stmt.name,
+            Stmt::Let(stmt) => &stmt.name,
+            _ => unreachable!("the parser only exports declarations"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ForStmt {
+    pub initializer: Option<Box<Stmt>>,
+    pub condition: Option<Expr>,
+    pub increment: Option<Expr>,
+    pub body: Box<Stmt>,
+}
+
+/// `for name in iterable { ... }`, running the body once for every value
+/// of the iterable, see `Iter`.
+#[derive(Serialize)]
+pub struct ForInStmt {
+    pub name: Token,
+    pub iterable: Expr,
+    pub iterable_position: Position,
+    pub body: Box<Stmt>,
+}
+
+#[derive(Serialize)]
+pub struct FunctionStmt {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+/// `guard condition else { ... }`, running the block only if the condition
+/// is falsy. The block must not fall through, see `diverges`.
+#[derive(Serialize)]
+pub struct GuardStmt {
+    pub keyword: Token,
+    pub condition: Expr,
+    pub else_branch: Box<Stmt>,
+}
+
+#[derive(Serialize)]
+pub struct IfStmt {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+/// `import "path/to/module.fox";` or `import module;`, which is short for
+/// `import "module.fox";`. Binds the module to `name`, the name of its file
+/// without the extension. The path is relative to the importing file.
+#[derive(Serialize)]
+pub struct ImportStmt {
+    pub keyword: Token,
+    pub path: String,
+    pub path_position: Position,
+    pub name: Token,
+}
+
+#[derive(Serialize)]
+pub struct LetStmt {
+    pub name: Token,
+    pub initializer: Option<Expr>,
+}
+
+#[derive(Serialize)]
+pub struct PrintStmt {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+#[derive(Serialize)]
+pub struct ReturnStmt {
+    pub keyword: Token,
+    pub value: Option<Expr>,
+}
+
+#[derive(Serialize)]
+pub struct ThrowStmt {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+#[derive(Serialize)]
+pub struct TryStmt {
+    pub body: Vec<Stmt>,
+    /// Variable the caught error is bound to in `handler`.
+    pub name: Token,
+    pub handler: Vec<Stmt>,
+}
+
+#[derive(Serialize)]
+pub struct WhileStmt {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+}
+
+impl Display for FunctionStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|p| p.lexeme())
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(f, "(fn {} ({params})", self.name.lexeme())?;
+        for stmt in &self.body {
+            write!(f, " {stmt}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Whether running `stmt` always ends by leaving the enclosing function or
+/// loop iteration, or throwing, rather than falling through to the next
+/// statement.
+pub fn diverges(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Block(stmts) => stmts.iter().any(diverges),
+        Stmt::If(stmt) => {
+            diverges(&stmt.then_branch) && stmt.else_branch.as_deref().is_some_and(diverges)
+        }
+        Stmt::Break(_) | Stmt::Continue(_) | Stmt::Return(_) | Stmt::Throw(_) => true,
+        Stmt::Try(stmt) => stmt.body.iter().any(diverges) && stmt.handler.iter().any(diverges),
+        _ => false,
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut string = String::new();
+        match self {
+            Stmt::Block(stmts) => {
+                string.push_str("(block");
+                for stmt in stmts {
+                    string.push(' ');
+                    string.push_str(&stmt.to_string());
+                }
+                string.push(')');
+            }
+            Stmt::Class(stmt) => {
+                string.push_str("(class ");
+                string.push_str(&stmt.name.lexeme());
+                if let Some(superclass) = &stmt.superclass {
+                    string.push_str(" < ");
+                    string.push_str(&superclass.name.lexeme());
+                }
+                for method in &stmt.methods {
+                    string.push(' ');
+                    string.push_str(&method.to_string());
+                }
+                string.push(')');
+            }
+            Stmt::Expression(expr) => {
+                string.push_str("(; ");
+                string.push_str(&expr.to_string());
+                string.push(')');
+            }
+            Stmt::For(stmt) => {
+                let or_nil = |part: Option<String>| part.unwrap_or_else(|| "nil".to_string());
+                string.push_str("(for ");
+                string.push_str(&or_nil(stmt.initializer.as_ref().map(|s| s.to_string())));
+                string.push(' ');
+                string.push_str(&or_nil(stmt.condition.as_ref().map(|e| e.to_string())));
+                string.push(' ');
+                string.push_str(&or_nil(stmt.increment.as_ref().map(|e| e.to_string())));
+                string.push(' ');
+                string.push_str(&stmt.body.to_string());
+                string.push(')');
+            }
+            Stmt::ForIn(stmt) => {
+                string.push_str("(for ");
+                string.push_str(&stmt.name.lexeme());
+                string.push_str(" in ");
+                string.push_str(&stmt.iterable.to_string());
+                string.push(' ');
+                string.push_str(&stmt.body.to_string());
+                string.push(')');
+            }
+            Stmt::Export(stmt) => {
+                string.push_str("(export ");
+                string.push_str(&stmt.declaration.to_string());
+                string.push(')');
+            }
+            Stmt::Function(stmt) => string.push_str(&stmt.to_string()),
+            Stmt::Guard(stmt) => {
+                string.push_str("(guard ");
+                string.push_str(&stmt.condition.to_string());
+                string.push(' ');
+                string.push_str(&stmt.else_branch.to_string());
+                string.push(')');
+            }
+            Stmt::If(stmt) => {
+                string.push_str("(if ");
+                string.push_str(&stmt.condition.to_string());
+                string.push(' ');
+                string.push_str(&stmt.then_branch.to_string());
+                if let Some(else_branch) = &stmt.else_branch {
+                    string.push(' ');
+                    string.push_str(&else_branch.to_string());
+                }
+                string.push(')');
+            }
+            Stmt::Import(stmt) => {
+                string.push_str("(import ");
+                string.push_str(&stmt.path);
+                string.push(' ');
+                string.push_str(&stmt.name.lexeme());
+                string.push(')');
+            }
+            Stmt::Let(stmt) => {
+                string.push_str("(let ");
+                string.push_str(&stmt.name.lexeme());
+                if let Some(initializer) = &stmt.initializer {
+                    string.push(' ');
+                    string.push_str(&initializer.to_string());
+                }
+                string.push(')');
+            }
+            Stmt::Print(stmt) => {
+                string.push_str("(print ");
+                string.push_str(&stmt.value.to_string());
+                string.push(')');
+            }
+            Stmt::Return(stmt) => {
+                string.push_str("(return");
+                if let Some(value) = &stmt.value {
+                    string.push(' ');
+                    string.push_str(&value.to_string());
+                }
+                string.push(')');
+            }
+            Stmt::Break(_) => string.push_str("(break)"),
+            Stmt::Continue(_) => string.push_str("(continue)"),
+            Stmt::Throw(stmt) => {
+                string.push_str("(throw ");
+                string.push_str(&stmt.value.to_string());
+                string.push(')');
+            }
+            Stmt::Try(stmt) => {
+                string.push_str("(try (block");
+                for stmt in &stmt.body {
+                    string.push(' ');
+                    string.push_str(&stmt.to_string());
+                }
+                string.push_str(") (catch ");
+                string.push_str(&stmt.name.lexeme());
+                string.push_str(" (block");
+                for stmt in &stmt.handler {
+                    string.push(' ');
+                    string.push_str(&stmt.to_string());
+                }
+                string.push_str(")))");
+            }
+            Stmt::While(stmt) => {
+                string.push_str("(while ");
+                string.push_str(&stmt.condition.to_string());
+                string.push(' ');
+                string.push_str(&stmt.body.to_string());
+                string.push(')');
+            }
+        }
+        write!(f, "{string}")
+    }
+}
@@ -0,0 +1,208 @@
+use std::ops::Range;
+
+use miette::SourceSpan;
+
+use crate::{
+    diag::Diag,
+    errors::{ResolveWarning, RULES},
+    lexer::{Lexer, Token, TokenType},
+    source::SourceFile,
+};
+
+/// A rule named by a `// fox: allow(...)` comment.
+struct Allowed {
+    name: String,
+    span: SourceSpan,
+    used: bool,
+}
+
+/// A `// fox: allow(...)` comment. At the end of a line, it covers that
+/// line. On a line of its own, it covers the next line, and if that line
+/// opens a block, like a function or a loop, the whole block.
+struct Suppression {
+    range: Range<usize>,
+    rules: Vec<Allowed>,
+}
+
+/// Drops the warnings in `warnings` allowed by a `// fox: allow(...)`
+/// comment of `source`, and adds a warning for every rule allowed that
+/// doesn't exist or didn't suppress anything.
+pub(crate) fn apply(source: &SourceFile, warnings: Vec<ResolveWarning>) -> Vec<ResolveWarning> {
+    let mut suppressions = suppressions(source);
+    let mut warnings = warnings
+        .into_iter()
+        .filter(|warning| {
+            let Some(rule) = warning.rule() else {
+                return true;
+            };
+            let offset = warning.span().offset();
+            let mut suppressed = false;
+            for suppression in &mut suppressions {
+                if !suppression.range.contains(&offset) {
+                    continue;
+                }
+                for allowed in &mut suppression.rules {
+                    if allowed.name == rule {
+                        allowed.used = true;
+                        suppressed = true;
+                    }
+                }
+            }
+            !suppressed
+        })
+        .collect::<Vec<_>>();
+
+    for allowed in suppressions.into_iter().flat_map(|s| s.rules) {
+        if !RULES.contains(&allowed.name.as_str()) {
+            warnings.push(
+                Diag::warning("resolve::unknown_rule")
+                    .arg("rule", allowed.name)
+                    .arg("rules", RULES.join(", "))
+                    .msg("Unknown rule `{rule}`")
+                    .source(source)
+                    .primary(allowed.span, "not a rule")
+                    .help("the rules that can be allowed are: {rules}")
+                    .into(),
+            );
+        } else if !allowed.used {
+            warnings.push(
+                Diag::warning("resolve::unused_suppression")
+                    .arg("rule", allowed.name)
+                    .msg("Rule `{rule}` is allowed but never reported here")
+                    .source(source)
+                    .primary(allowed.span, "unused")
+                    .help("remove it from the `fox: allow(...)` comment")
+                    .into(),
+            );
+        }
+    }
+    warnings
+}
+
+fn suppressions(source: &SourceFile) -> Vec<Suppression> {
+    let mut lexer = Lexer::new(source);
+    let source = source.text();
+    let mut tokens = vec![];
+    while let Some(item) = lexer.scan_token() {
+        if let Ok(token) = item {
+            tokens.push(token);
+        }
+    }
+
+    let mut suppressions = vec![];
+    for (i, comment) in tokens.iter().enumerate() {
+        if comment.ty != TokenType::Comment {
+            continue;
+        }
+        let Some(rules) = allowed_rules(source, comment) else {
+            continue;
+        };
+        let line_start = line_start(source, comment.position.start);
+        let trailing = tokens[..i]
+            .iter()
+            .rev()
+            .find(|t| t.ty != TokenType::Comment)
+            .is_some_and(|t| t.position.end > line_start);
+        let range = if trailing {
+            line_start..comment.position.start
+        } else {
+            covered(source, &tokens[i + 1..])
+        };
+        suppressions.push(Suppression { range, rules });
+    }
+    suppressions
+}
+
+/// Parses the rules of `comment` if it is a `// fox: allow(...)` comment.
+fn allowed_rules(source: &str, comment: &Token) -> Option<Vec<Allowed>> {
+    let text = &source[comment.position.start..comment.position.end];
+    let body = text.strip_prefix("//")?.trim_start();
+    let list = body
+        .strip_prefix("fox:")?
+        .trim_start()
+        .strip_prefix("allow(")?;
+    let list = &list[..list.find(')')?];
+    let mut offset = comment.position.start + (text.len() - body.len());
+    offset += body.find("allow(")? + "allow(".len();
+
+    let mut rules = vec![];
+    for part in list.split(',') {
+        let name = part.trim();
+        if !name.is_empty() {
+            let leading = part.len() - part.trim_start().len();
+            rules.push(Allowed {
+                name: name.to_string(),
+                span: (offset + leading, name.len()).into(),
+                used: false,
+            });
+        }
+        offset += part.len() + 1;
+    }
+    Some(rules)
+}
+
+/// The source covered by a suppression on its own line, given the tokens
+/// after it: the next line of code, extended to the end of the block it
+/// opens.
+fn covered(source: &str, tokens: &[Token]) -> Range<usize> {
+    let mut tokens = tokens.iter().filter(|t| t.ty != TokenType::Comment);
+    let Some(first) = tokens.next() else {
+        return 0..0;
+    };
+    let start = line_start(source, first.position.start);
+    let mut end = first.position.start;
+    let mut depth = 0;
+    for token in std::iter::once(first).chain(tokens) {
+        let same_line = !source[end..token.position.start].contains('\n');
+        if depth == 0 && token.ty == TokenType::Eof || depth == 0 && !same_line {
+            break;
+        }
+        match token.ty {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace => depth -= 1,
+            _ => {}
+        }
+        end = token.position.end;
+    }
+    start..end
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{engine, errors::ResolveWarning, source::SourceFile};
+
+    const FAILING: &str = "fn f() { return Error(1, 2, 3); }\n";
+
+    fn warnings(source: &str) -> Vec<ResolveWarning> {
+        let source = SourceFile::new("test.fox", format!("{FAILING}{source}"));
+        engine::parse(&source).unwrap().1
+    }
+
+    #[test]
+    fn check_suppressions() {
+        assert!(warnings("f(); // fox: allow(ignored_error)").is_empty());
+        assert!(warnings("// fox: allow(ignored_error)\nf();").is_empty());
+        assert!(warnings("//fox:allow( ignored_error )\nf();").is_empty());
+        assert!(warnings("// fox: allow(ignored_error)\nfn g() {\n  f();\n  f();\n}").is_empty());
+        assert_eq!(
+            codes(&warnings("// fox: allow(ignored_error)\nprint 1;\nf();")),
+            ["resolve::ignored_error", "resolve::unused_suppression"]
+        );
+        assert_eq!(
+            codes(&warnings("f();\nf(); // fox: allow(ignored_error)")),
+            ["resolve::ignored_error"]
+        );
+        let warnings = warnings("// fox: allow(ignored_error, unused_variable)\nf();");
+        assert_eq!(codes(&warnings), ["resolve::unknown_rule"]);
+        assert_eq!(warnings[0].value("rule"), Some("unused_variable"));
+        assert_eq!(warnings[0].span().offset(), FAILING.len() + 29);
+    }
+
+    fn codes(warnings: &[ResolveWarning]) -> Vec<&str> {
+        warnings.iter().map(|warning| warning.code).collect()
+    }
+}
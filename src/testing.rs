@@ -0,0 +1,585 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use miette::Report;
+
+use crate::{
+    check,
+    diff::diff,
+    engine,
+    errors::FoxError,
+    interpreter::{Interpreter, Value},
+    native::NativeError,
+    snapshot::{Mismatch, Snapshots},
+    source::SourceFile,
+    stmt::Stmt,
+};
+
+/// How `fox test` runs the tests it finds.
+#[derive(Debug)]
+pub(crate) struct TestOptions {
+    /// How many test files run at once, one per available core if `None`.
+    pub(crate) threads: Option<usize>,
+    /// Only the tests whose name contains it are run, if given.
+    pub(crate) filter: Option<String>,
+    /// How long a single test, or the top level of its file, can take.
+    pub(crate) timeout: Duration,
+    /// Whether snapshots are recorded rather than compared, see `Snapshots`.
+    pub(crate) bless: bool,
+    /// Whether to color the differences of mismatched snapshots.
+    pub(crate) color: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Passed,
+    Failed,
+    TimedOut,
+}
+
+/// The outcome of a test, named after its file and function, like
+/// `math_test.fox::test_add`. A test file that fails to load is reported as
+/// a failed test named after the file.
+#[derive(Debug)]
+struct TestResult {
+    name: String,
+    outcome: Outcome,
+    /// What the test printed, which is only shown if it failed.
+    output: String,
+    /// The errors that made it fail, rendered by the thread running the test
+    /// as they can hold values that can't be sent to another thread.
+    diagnostics: Vec<String>,
+    /// The snapshots the test didn't match.
+    mismatches: Vec<Mismatch>,
+}
+
+/// The tests of one file, in the order they are declared.
+#[derive(Debug)]
+struct FileTests {
+    results: Vec<TestResult>,
+    /// How many tests were skipped for not matching the filter.
+    filtered_out: usize,
+}
+
+/// Runs the tests found in `args` and reports them like `cargo test` does.
+/// Arguments are found like the ones of `fox check`, except that only the
+/// files named `*_test.fox` are taken from directories and globs. A test is
+/// a top-level function whose name starts with `test_`, which passes unless
+/// it raises an uncaught error. Every file runs in its own interpreter once
+/// its top level has, and its tests run in order, while files run in
+/// parallel. A file can declare the hooks `before_all`, `before_each`,
+/// `after_each` and `after_all`, see `Suite::run`, and its tests can call
+/// `test_dir()` for a directory of its own, which is removed once they are
+/// done, `assert_eq(actual, expected)`, see `assert_eq`, and
+/// `expect_snapshot(name, value)`, see `Snapshots`. The report is printed
+/// once every file is done, sorted by path, so it is the same on every run.
+/// Returns `Err` if any test failed.
+pub(crate) fn test(args: &[String], options: &TestOptions) -> Result<(), ()> {
+    let paths = match check::discover(args, false) {
+        Ok(paths) => paths,
+        Err(err) => {
+            eprintln!("{:?}", Report::new(err));
+            return Err(());
+        }
+    };
+    let paths = paths
+        .into_iter()
+        .filter(|path| is_test_file(path) || args.iter().any(|arg| Path::new(arg) == path))
+        .collect::<Vec<_>>();
+    let files = run_files(&paths, options);
+    let results = files.iter().flat_map(|file| &file.results);
+    let total = results.clone().count();
+    println!(
+        "\nrunning {total} test{}",
+        if total == 1 { "" } else { "s" }
+    );
+    for result in results.clone() {
+        let outcome = match result.outcome {
+            Outcome::Passed => "ok",
+            Outcome::Failed => "FAILED",
+            Outcome::TimedOut => "FAILED (timed out)",
+        };
+        println!("test {} ... {outcome}", result.name);
+    }
+
+    let failures = results
+        .filter(|result| result.outcome != Outcome::Passed)
+        .collect::<Vec<_>>();
+    if !failures.is_empty() {
+        println!("\nfailures:\n");
+        for failure in &failures {
+            println!("---- {} ----", failure.name);
+            print!("{}", failure.output);
+            for diagnostic in &failure.diagnostics {
+                println!("{diagnostic}");
+            }
+            for mismatch in &failure.mismatches {
+                print!("{}", mismatch.render(options.color));
+            }
+            println!();
+        }
+        println!("failures:");
+        for failure in &failures {
+            println!("    {}", failure.name);
+        }
+    }
+    let filtered_out = files.iter().map(|file| file.filtered_out).sum::<usize>();
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {filtered_out} filtered out\n",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        total - failures.len(),
+        failures.len(),
+    );
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with("_test.fox"))
+}
+
+/// Runs the tests of `paths` using `options.threads` threads, returning them
+/// in the same order.
+fn run_files(paths: &[PathBuf], options: &TestOptions) -> Vec<FileTests> {
+    let threads = options
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from));
+    check::in_parallel(paths, threads, |path| run_file(path, options))
+}
+
+fn run_file(path: &Path, options: &TestOptions) -> FileTests {
+    let file = path.display().to_string();
+    let parsed = fs::read_to_string(path)
+        .map_err(|error| FoxError::Io {
+            path: file.clone(),
+            error,
+        })
+        .and_then(|text| {
+            let source = SourceFile::new(file.clone(), text);
+            engine::parse(&source).map(|(stmts, _)| (source, stmts))
+        });
+    let (source, stmts) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => return FileTests::failed(file, err, String::new()),
+    };
+
+    let functions = stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Function(function) => Some(function.name.lexeme()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let names = functions
+        .iter()
+        .filter(|name| name.starts_with("test_"))
+        .cloned()
+        .collect::<Vec<_>>();
+    let found = names.len();
+    let selected = names
+        .into_iter()
+        .map(|name| (format!("{file}::{name}"), name))
+        .filter(|(id, _)| {
+            options
+                .filter
+                .as_ref()
+                .is_none_or(|filter| id.contains(filter))
+        })
+        .collect::<Vec<_>>();
+    let mut tests = FileTests {
+        results: vec![],
+        filtered_out: found - selected.len(),
+    };
+    if selected.is_empty() {
+        return tests;
+    }
+
+    let dir = env::temp_dir().join(format!(
+        "fox-test-{}-{}",
+        process::id(),
+        SUITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut suite = Suite {
+        interpreter: Interpreter::new(),
+        functions: functions.into_iter().collect(),
+        timeout: options.timeout,
+        snapshots: Snapshots::new(path, options.bless),
+    };
+    suite.interpreter.capture_output();
+    let expect_snapshot = suite.snapshots.expect();
+    suite
+        .interpreter
+        .define_native("expect_snapshot", 2, expect_snapshot);
+    let test_dir = dir.display().to_string();
+    suite.interpreter.define_native("test_dir", 0, move |_| {
+        fs::create_dir_all(&test_dir).map_err(|err| {
+            NativeError::new("io", &format!("{test_dir}: {err}"), test_dir.as_str())
+        })?;
+        Ok(test_dir.clone())
+    });
+    suite.interpreter.define_native("assert_eq", 2, assert_eq);
+    tests.results = suite.run(&file, &source, &stmts, selected);
+    // Whatever failed, the files a suite wrote don't outlive it.
+    let _ = fs::remove_dir_all(dir);
+    tests
+}
+
+/// The `assert_eq(actual, expected)` builtin, which fails listing every
+/// field and element where `actual` differs from `expected`, as found by
+/// `diff`, rather than just the two values.
+fn assert_eq(args: &[Value]) -> Result<(), NativeError> {
+    let differences = diff(&args[1], &args[0]);
+    if differences.is_empty() {
+        return Ok(());
+    }
+    let mut message = "values aren't equal, expected -> actual:".to_string();
+    for difference in differences {
+        message.push_str(&format!("\n  {difference}"));
+    }
+    Err(NativeError::new("assert", &message, args[0].clone()))
+}
+
+/// Counts the suites run, to give each its own directory.
+static SUITES: AtomicUsize = AtomicUsize::new(0);
+
+/// The interpreter running the tests of a file, along with the names of
+/// the functions it declares, which tell which hooks it has.
+struct Suite {
+    interpreter: Interpreter,
+    functions: HashSet<String>,
+    timeout: Duration,
+    snapshots: Snapshots,
+}
+
+impl Suite {
+    /// Runs the top level of the file, then `before_all`, then every test of
+    /// `tests`, named by id, between `before_each` and `after_each`, and last
+    /// `after_all`. Hooks the file doesn't declare are skipped. Teardown
+    /// hooks run even if the ones before them or the test failed, and a
+    /// failing hook fails the test it runs for, or is reported as a test of
+    /// its own for `before_all` and `after_all`.
+    fn run(
+        &mut self,
+        file: &str,
+        source: &SourceFile,
+        stmts: &[Stmt],
+        tests: Vec<(String, String)>,
+    ) -> Vec<TestResult> {
+        self.interpreter.set_timeout(Some(self.timeout));
+        if let Err(err) = self.interpreter.interpret(source, stmts) {
+            let output = self.interpreter.take_output();
+            return vec![TestResult::new(file.to_string(), vec![err.into()], output)];
+        }
+        let mut results = vec![];
+        match self.hook("before_all") {
+            Ok(()) => {
+                self.interpreter.take_output();
+                for (id, name) in tests {
+                    let mut errors = vec![];
+                    match self.hook("before_each") {
+                        Ok(()) => errors.extend(self.call(&name).err()),
+                        Err(err) => errors.push(err),
+                    }
+                    errors.extend(self.hook("after_each").err());
+                    let output = self.interpreter.take_output();
+                    let mut result = TestResult::new(id, errors, output);
+                    result.mismatches = self.snapshots.take_mismatches();
+                    results.push(result);
+                }
+            }
+            Err(err) => {
+                let output = self.interpreter.take_output();
+                results.push(TestResult::new(
+                    format!("{file}::before_all"),
+                    vec![err],
+                    output,
+                ));
+            }
+        }
+        if let Err(err) = self.hook("after_all") {
+            let output = self.interpreter.take_output();
+            results.push(TestResult::new(
+                format!("{file}::after_all"),
+                vec![err],
+                output,
+            ));
+        }
+        results
+    }
+
+    /// Calls the hook `name` if the file declares it.
+    fn hook(&mut self, name: &str) -> Result<(), FoxError> {
+        if self.functions.contains(name) {
+            self.call(name)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<(), FoxError> {
+        self.interpreter.set_timeout(Some(self.timeout));
+        self.interpreter.call(name, &[])?;
+        Ok(())
+    }
+}
+
+impl FileTests {
+    fn failed(name: String, err: FoxError, output: String) -> Self {
+        FileTests {
+            results: vec![TestResult::new(name, vec![err], output)],
+            filtered_out: 0,
+        }
+    }
+}
+
+impl TestResult {
+    /// The result of the test `name`, which passed unless it raised any of
+    /// `errors`.
+    fn new(name: String, errors: Vec<FoxError>, output: String) -> Self {
+        let timed_out = |err: &FoxError| matches!(err, FoxError::Runtime(err) if err.is_timeout());
+        let outcome = if errors.is_empty() {
+            Outcome::Passed
+        } else if errors.iter().any(timed_out) {
+            Outcome::TimedOut
+        } else {
+            Outcome::Failed
+        };
+        TestResult {
+            name,
+            outcome,
+            output,
+            mismatches: vec![],
+            diagnostics: errors
+                .into_iter()
+                .flat_map(FoxError::into_reports)
+                .map(|report| format!("{report:?}"))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path, time::Duration};
+
+    use crate::{interpreter::Value, native::new_list};
+
+    use super::{assert_eq, is_test_file, run_files, Outcome, TestOptions};
+
+    #[test]
+    fn check_tests() {
+        let dir = std::env::temp_dir().join(format!("fox-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files = [
+            (
+                "math_test.fox",
+                "fn add(a, b) { return a + b; }
+                fn test_add() { assert_eq(add(1, 2), 3); }
+                fn test_mul() { assert_eq([2 * 2, {\"a\": 2 * 3}], [4, {\"a\": 7}]); }
+                fn test_sub() { print \"subtracting\"; throw Error(\"assert\", \"no sub\", nil); }
+                fn helper() { throw Error(\"assert\", \"not a test\", nil); }",
+            ),
+            (
+                "loop_test.fox",
+                "fn test_spin() { try { while true {} } catch e {} }
+                fn test_recurse() { test_recurse(); }
+                fn test_after() { print \"ran\"; }",
+            ),
+            ("broken_test.fox", "let = 1;"),
+        ];
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        let paths =
+            ["broken_test.fox", "loop_test.fox", "math_test.fox"].map(|name| dir.join(name));
+        let mut options = TestOptions {
+            threads: Some(2),
+            filter: None,
+            timeout: Duration::from_millis(100),
+            bless: false,
+            color: false,
+        };
+        let outcomes = |options: &TestOptions| {
+            run_files(&paths, options)
+                .into_iter()
+                .flat_map(|file| file.results)
+                .map(|result| {
+                    let name = Path::new(&result.name).file_name().unwrap().to_owned();
+                    (name.into_string().unwrap(), result.outcome, result.output)
+                })
+                .collect::<Vec<_>>()
+        };
+        let ok = |name: &str, output: &str| (name.to_string(), Outcome::Passed, output.to_string());
+        let failed =
+            |name: &str, outcome, output: &str| (name.to_string(), outcome, output.to_string());
+        assert_eq!(
+            outcomes(&options),
+            [
+                failed("broken_test.fox", Outcome::Failed, ""),
+                failed("loop_test.fox::test_spin", Outcome::TimedOut, ""),
+                failed("loop_test.fox::test_recurse", Outcome::Failed, ""),
+                ok("loop_test.fox::test_after", "ran\n"),
+                ok("math_test.fox::test_add", ""),
+                failed("math_test.fox::test_mul", Outcome::Failed, ""),
+                failed("math_test.fox::test_sub", Outcome::Failed, "subtracting\n"),
+            ]
+        );
+
+        options.filter = Some("math_test.fox::test_a".to_string());
+        let files = run_files(&paths, &options);
+        assert_eq!(
+            files
+                .iter()
+                .map(|file| file.filtered_out)
+                .collect::<Vec<_>>(),
+            [0, 3, 2]
+        );
+        assert_eq!(outcomes(&options)[1], ok("math_test.fox::test_add", ""));
+
+        assert!(is_test_file(Path::new("lib/math_test.fox")));
+        assert!(!is_test_file(Path::new("lib/math.fox")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn check_fixtures() {
+        let dir = std::env::temp_dir().join(format!("fox-fixtures-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log.txt").display().to_string().replace('\\', "/");
+        let files = [
+            (
+                "files_test.fox",
+                format!(
+                    "let log = \"\";
+                    fn before_all() {{
+                        log = \"A\";
+                        std.io.write_file(test_dir() + \"/data.txt\", \"fox\");
+                    }}
+                    fn before_each() {{ log = log + \"b\"; }}
+                    fn after_each() {{ log = log + \"a\"; }}
+                    fn after_all() {{ std.io.write_file(\"{log}\", log + \" \" + test_dir()); }}
+                    fn test_read() {{
+                        log = log + std.io.read_file(test_dir() + \"/data.txt\");
+                    }}
+                    fn test_fail() {{ log = log + \"!\"; throw Error(\"assert\", \"failed\", nil); }}"
+                ),
+            ),
+            (
+                "hooks_test.fox",
+                "fn before_all() { throw Error(\"setup\", \"no database\", nil); }
+                fn test_never() {}
+                fn after_all() { print \"tore down\"; }"
+                    .to_string(),
+            ),
+        ];
+        for (name, source) in &files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        let paths = files.map(|(name, _)| dir.join(name));
+        let options = TestOptions {
+            threads: None,
+            filter: None,
+            timeout: Duration::from_secs(10),
+            bless: false,
+            color: false,
+        };
+        let results = run_files(&paths, &options)
+            .into_iter()
+            .flat_map(|file| file.results)
+            .map(|result| {
+                let name = Path::new(&result.name).file_name().unwrap().to_owned();
+                (name.into_string().unwrap(), result.outcome)
+            })
+            .collect::<Vec<_>>();
+        let result = |name: &str, outcome| (name.to_string(), outcome);
+        assert_eq!(
+            results,
+            [
+                result("files_test.fox::test_read", Outcome::Passed),
+                result("files_test.fox::test_fail", Outcome::Failed),
+                result("hooks_test.fox::before_all", Outcome::Failed),
+            ]
+        );
+
+        let log = fs::read_to_string(&log).unwrap();
+        let (log, test_dir) = log.split_once(' ').unwrap();
+        assert_eq!(log, "Abfoxab!a");
+        assert!(!Path::new(test_dir).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn check_assert_eq() {
+        assert!(assert_eq(&[Value::Int(1), Value::Int(1)]).is_ok());
+        let actual = new_list(vec![Value::Int(4), Value::Int(6)]);
+        let expected = new_list(vec![Value::Int(4), Value::Float(7.0)]);
+        let err = assert_eq(&[actual, expected]).unwrap_err();
+        assert_eq!(
+            (err.kind.as_str(), err.message.as_str()),
+            (
+                "assert",
+                "values aren't equal, expected -> actual:\n  [1]: 7 (float) -> 6 (int)"
+            )
+        );
+    }
+
+    #[test]
+    fn check_snapshots() {
+        let dir = std::env::temp_dir().join(format!("fox-snapshots-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("report_test.fox");
+        let write = |value: &str| {
+            let source = format!(
+                "fn test_report() {{ expect_snapshot(\"report\", {value}); }}
+                fn test_name() {{ expect_snapshot(\"../escape\", 1); }}"
+            );
+            fs::write(&path, source).unwrap();
+        };
+        let mut options = TestOptions {
+            threads: Some(1),
+            filter: None,
+            timeout: Duration::from_secs(10),
+            bless: false,
+            color: false,
+        };
+        let run = |options: &TestOptions| {
+            let mut files = run_files(&[path.clone()], options);
+            files.remove(0).results
+        };
+
+        write("\"fox\\nlang\"");
+        let results = run(&options);
+        assert_eq!(results[0].outcome, Outcome::Failed);
+        assert!(results[0].mismatches.is_empty());
+        assert_eq!(results[1].outcome, Outcome::Failed);
+
+        options.bless = true;
+        assert_eq!(run(&options)[0].outcome, Outcome::Passed);
+        let snapshot = dir.join("snapshots/report_test/report.snap");
+        assert_eq!(fs::read_to_string(&snapshot).unwrap(), "fox\nlang\n");
+
+        options.bless = false;
+        assert_eq!(run(&options)[0].outcome, Outcome::Passed);
+        write("\"fox\\nlanguage\"");
+        let results = run(&options);
+        assert_eq!(results[0].outcome, Outcome::Failed);
+        let mismatch = &results[0].mismatches[0];
+        assert_eq!(
+            (mismatch.expected.as_str(), mismatch.actual.as_str()),
+            ("fox\nlang\n", "fox\nlanguage\n")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,894 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use miette::SourceSpan;
+
+use crate::{
+    chunk::{Chunk, GlobalSlots, Op, Prototype},
+    errors::RuntimeError,
+    interpreter::Value,
+    iter::Iter,
+    list::{self, List},
+    map::{Entries, Map},
+    number::{NumericError, NumericOp},
+    output::{Output, OutputLimits},
+    source::SourceFile,
+};
+
+/// Maximum number of nested calls before the VM gives up with a stack
+/// overflow.
+const FRAMES_MAX: usize = 1024;
+
+/// A function compiled for the `Vm` along with the variables it captures.
+pub struct Closure {
+    pub prototype: Rc<Prototype>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.prototype.name)
+    }
+}
+
+/// A variable captured by a closure. It refers to the variable's stack slot
+/// while it is in scope, and holds the variable itself once it is not.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    /// Index of the stack slot holding the called function, followed by its
+    /// arguments and locals.
+    base: usize,
+}
+
+/// Stack-based virtual machine running bytecode produced by the `Compiler`.
+/// An alternative to the tree-walking `Interpreter`, which it is faster than
+/// at the cost of not supporting everything yet, see `Compiler`.
+pub struct Vm {
+    source: SourceFile,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    /// The value of every global by slot, or `None` if it isn't defined.
+    globals: Vec<Option<Value>>,
+    global_slots: GlobalSlots,
+    /// Upvalues still pointing into the stack, which need to be closed when
+    /// their slot is popped.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    output: Output,
+    /// When the scripts being run must have finished, along with how long
+    /// they were given, if limited.
+    deadline: Option<(Instant, Duration)>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm {
+            source: SourceFile::default(),
+            stack: vec![],
+            frames: vec![],
+            globals: vec![],
+            global_slots: GlobalSlots::default(),
+            open_upvalues: vec![],
+            output: Output::default(),
+            deadline: None,
+        }
+    }
+
+    /// The slots of the globals of the VM, which scripts are compiled
+    /// against before they run on it.
+    pub fn global_slots(&mut self) -> &mut GlobalSlots {
+        &mut self.global_slots
+    }
+
+    /// Returns every global variable with its value, sorted by name, like
+    /// `Interpreter::globals`.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals = self
+            .globals
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, value)| {
+                let name = self.global_slots.name(slot as u16);
+                Some((name.to_string(), value.clone()?))
+            })
+            .collect::<Vec<_>>();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        globals
+    }
+
+    /// Limits how much the scripts run from now on can print, like
+    /// `Interpreter::set_output_limits`.
+    pub fn set_output_limits(&mut self, limits: OutputLimits) {
+        self.output.set_limits(limits);
+    }
+
+    /// Limits how long the scripts run from now on can take in total, or
+    /// lifts the limit if `None`, like `Interpreter::set_timeout`. It is
+    /// checked by loops and calls.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+    }
+
+    /// Runs `script`, stopping at the first runtime error, and returns its
+    /// value. `source` is the code the script was compiled from and is used
+    /// for diagnostics. Globals defined by earlier scripts stay defined.
+    pub fn interpret(
+        &mut self,
+        source: &SourceFile,
+        script: Prototype,
+    ) -> Result<Value, RuntimeError> {
+        self.source = source.clone();
+        self.globals.resize(self.global_slots.len(), None);
+        let closure = Rc::new(Closure {
+            prototype: Rc::new(script),
+            upvalues: vec![],
+        });
+        self.stack.push(Value::Closure(Rc::clone(&closure)));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: 0,
+        });
+        let result = self.run();
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let op = frame.closure.prototype.chunk.code[frame.ip];
+            frame.ip += 1;
+            match op {
+                Op::Constant(index) => {
+                    let value = self.chunk().constants[index as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Dup => self.stack.push(self.peek(0).clone()),
+                Op::GetLocal(slot) => {
+                    let value = self.stack[self.frame().base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let slot = self.frame().base + slot as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                Op::GetUpvalue(index) => {
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index as usize]);
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetUpvalue(index) => {
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index as usize]);
+                    let value = self.peek(0).clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    };
+                }
+                Op::DefineGlobal(slot) => {
+                    let value = self.pop();
+                    self.globals[slot as usize] = Some(value);
+                }
+                Op::GetGlobal(slot) => {
+                    let Some(value) = &self.globals[slot as usize] else {
+                        return Err(self.undefined_variable(slot));
+                    };
+                    self.stack.push(value.clone());
+                }
+                Op::SetGlobal(slot) => {
+                    let value = self.peek(0).clone();
+                    let Some(global) = &mut self.globals[slot as usize] else {
+                        return Err(self.undefined_variable(slot));
+                    };
+                    *global = value;
+                }
+                Op::Equal => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(Value::Bool(lhs == rhs));
+                }
+                Op::Greater
+                | Op::GreaterEqual
+                | Op::Less
+                | Op::LessEqual
+                | Op::Add
+                | Op::Subtract
+                | Op::Multiply
+                | Op::Divide
+                | Op::Remainder
+                | Op::Power
+                | Op::BitAnd
+                | Op::BitOr
+                | Op::BitXor
+                | Op::ShiftLeft
+                | Op::ShiftRight
+                | Op::Range
+                | Op::RangeInclusive => self.binary(op)?,
+                Op::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                Op::Negate => {
+                    let value = self.pop();
+                    match value.negate() {
+                        Ok(negated) => self.stack.push(negated),
+                        Err(NumericError::NotANumber) => {
+                            let span = self.span();
+                            return Err(RuntimeError::invalid_operand(
+                                &self.source,
+                                span,
+                                &self.lexeme(span),
+                                value.type_name(),
+                            ));
+                        }
+                        Err(NumericError::Overflow | NumericError::DivisionByZero) => {
+                            return Err(self.overflow())
+                        }
+                    }
+                }
+                Op::Interpolate(count) => {
+                    let parts = self.stack.split_off(self.stack.len() - count as usize);
+                    let string = parts.iter().map(Value::to_string).collect();
+                    self.stack.push(Value::String(string));
+                }
+                Op::List(count) => {
+                    let elements = self.stack.split_off(self.stack.len() - count as usize);
+                    self.stack.push(Value::List(Rc::new(List::new(elements))));
+                }
+                Op::Map => {
+                    let map = Map::new(Entries::default());
+                    self.stack.push(Value::Map(Rc::new(map)));
+                }
+                Op::MapEntry => {
+                    let value = self.pop();
+                    let key = self.pop();
+                    let key = match list::key(&key) {
+                        Ok(key) => key,
+                        Err(err) => return Err(err.error(&self.source, self.span())),
+                    };
+                    let Value::Map(map) = self.peek(0) else {
+                        unreachable!("entries are only inserted into map literals");
+                    };
+                    map.entries.borrow_mut().insert(key, value);
+                }
+                Op::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    match list::index(&object, &index) {
+                        Ok(value) => self.stack.push(value),
+                        Err(err) => return Err(err.error(&self.source, self.span())),
+                    }
+                }
+                Op::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    if let Err(err) = list::set_index(&object, &index, value.clone()) {
+                        return Err(err.error(&self.source, self.span()));
+                    }
+                    self.stack.push(value);
+                }
+                Op::Print => {
+                    let value = self.pop();
+                    if let Err(exceeded) = self.output.print(&value) {
+                        let span = self.span();
+                        return Err(exceeded.error(&self.source, span));
+                    }
+                }
+                Op::Jump(offset) => self.frame_mut().ip += offset as usize,
+                Op::JumpIfFalse(offset) => {
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                Op::NoMatch => {
+                    return Err(RuntimeError::no_match(
+                        &self.source,
+                        self.span(),
+                        &self.peek(0).to_string(),
+                    ))
+                }
+                Op::Iterate => {
+                    let value = self.pop();
+                    let iterable = match Iter::new(&value) {
+                        Ok(Iter::List { list, .. }) => Value::List(list),
+                        Ok(Iter::Range(_)) => value,
+                        Ok(values) => Value::List(Rc::new(List::new(values.collect()))),
+                        Err(found) => {
+                            return Err(RuntimeError::not_iterable(
+                                &self.source,
+                                self.span(),
+                                found,
+                            ))
+                        }
+                    };
+                    self.stack.push(iterable);
+                }
+                Op::ForIn(slot, offset) => {
+                    let slot = self.frame().base + slot as usize;
+                    let Value::Int(next) = self.stack[slot + 1] else {
+                        unreachable!("`for-in` loops keep their position in a local");
+                    };
+                    let value = match &self.stack[slot] {
+                        Value::List(list) => list.elements.borrow().get(next as usize).cloned(),
+                        Value::Range(range) => range
+                            .start
+                            .checked_add(next)
+                            .filter(|int| range.ints().contains(int))
+                            .map(Value::Int),
+                        _ => unreachable!("`Op::Iterate` leaves a list or range"),
+                    };
+                    match value {
+                        Some(value) => {
+                            self.stack[slot + 1] = Value::Int(next + 1);
+                            self.stack.push(value);
+                        }
+                        None => self.frame_mut().ip += offset as usize,
+                    }
+                }
+                Op::Loop(offset) => {
+                    self.check_deadline()?;
+                    self.frame_mut().ip -= offset as usize;
+                }
+                Op::Call(count) => {
+                    let callee = self.peek(count as usize).clone();
+                    self.call(callee, count as usize)?;
+                }
+                Op::Closure(index) => {
+                    let prototype = Rc::clone(&self.chunk().prototypes[index as usize]);
+                    let base = self.frame().base;
+                    let upvalues = prototype
+                        .upvalues
+                        .iter()
+                        .map(|source| {
+                            if source.is_local {
+                                self.capture_upvalue(base + source.index as usize)
+                            } else {
+                                Rc::clone(&self.frame().closure.upvalues[source.index as usize])
+                            }
+                        })
+                        .collect();
+                    self.stack.push(Value::Closure(Rc::new(Closure {
+                        prototype,
+                        upvalues,
+                    })));
+                }
+                Op::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                Op::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.stack.push(result);
+                }
+            }
+        }
+    }
+
+    /// Executes a binary operator other than `==`, which works on any
+    /// operands.
+    fn binary(&mut self, op: Op) -> Result<(), RuntimeError> {
+        let rhs = self.pop();
+        let lhs = self.pop();
+        if let (Op::Add, Value::String(lhs), Value::String(rhs)) = (op, &lhs, &rhs) {
+            self.stack.push(Value::String(format!("{lhs}{rhs}")));
+            return Ok(());
+        }
+        let numeric_op = match op {
+            Op::Greater => NumericOp::Greater,
+            Op::GreaterEqual => NumericOp::GreaterEqual,
+            Op::Less => NumericOp::Less,
+            Op::LessEqual => NumericOp::LessEqual,
+            Op::Add => NumericOp::Add,
+            Op::Subtract => NumericOp::Subtract,
+            Op::Multiply => NumericOp::Multiply,
+            Op::Divide => NumericOp::Divide,
+            Op::Remainder => NumericOp::Remainder,
+            Op::Power => NumericOp::Power,
+            Op::BitAnd => NumericOp::BitAnd,
+            Op::BitOr => NumericOp::BitOr,
+            Op::BitXor => NumericOp::BitXor,
+            Op::ShiftLeft => NumericOp::ShiftLeft,
+            Op::ShiftRight => NumericOp::ShiftRight,
+            Op::Range => NumericOp::Range,
+            Op::RangeInclusive => NumericOp::RangeInclusive,
+            _ => unreachable!(),
+        };
+        match lhs.numeric(numeric_op, &rhs) {
+            Ok(value) => self.stack.push(value),
+            Err(NumericError::NotANumber) => {
+                let expected = match numeric_op {
+                    NumericOp::Add => "two numbers or two strings",
+                    op if op.takes_ints() => "ints",
+                    _ => "numbers",
+                };
+                let span = self.span();
+                return Err(RuntimeError::invalid_operands(
+                    &self.source,
+                    span,
+                    &self.lexeme(span),
+                    expected,
+                    &format!("{} and {}", lhs.type_name(), rhs.type_name()),
+                ));
+            }
+            Err(NumericError::Overflow) => return Err(self.overflow()),
+            Err(NumericError::DivisionByZero) => {
+                let span = self.span();
+                return Err(RuntimeError::division_by_zero(
+                    &self.source,
+                    span,
+                    &self.lexeme(span),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn overflow(&self) -> RuntimeError {
+        let span = self.span();
+        RuntimeError::integer_overflow(&self.source, span, &self.lexeme(span))
+    }
+
+    /// Pushes a frame calling `callee`, which is followed by `count`
+    /// arguments on the stack.
+    fn call(&mut self, callee: Value, count: usize) -> Result<(), RuntimeError> {
+        let Value::Closure(closure) = callee else {
+            return Err(RuntimeError::not_callable(
+                &self.source,
+                self.span(),
+                callee.type_name(),
+            ));
+        };
+        if count != closure.prototype.arity {
+            return Err(RuntimeError::arity_mismatch(
+                &self.source,
+                self.span(),
+                &closure.prototype.name,
+                closure.prototype.arity,
+                count,
+            ));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(RuntimeError::stack_overflow(
+                &self.source,
+                self.span(),
+                FRAMES_MAX,
+            ));
+        }
+        self.check_deadline()?;
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: self.stack.len() - count - 1,
+        });
+        Ok(())
+    }
+
+    /// Returns the upvalue referring to the stack slot `slot`, reusing the
+    /// open one if there is one so that closures share captured variables.
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Closes every open upvalue referring to `from` or a slot above it.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= from => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap()
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - distance - 1]
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn chunk(&self) -> &Chunk {
+        &self.frame().closure.prototype.chunk
+    }
+
+    fn check_deadline(&self) -> Result<(), RuntimeError> {
+        match self.deadline {
+            Some((deadline, limit)) if Instant::now() >= deadline => {
+                Err(RuntimeError::timed_out(limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Span of the instruction being executed.
+    fn span(&self) -> SourceSpan {
+        let frame = self.frame();
+        frame.closure.prototype.chunk.spans[frame.ip - 1]
+    }
+
+    fn lexeme(&self, span: SourceSpan) -> String {
+        self.source.text()[span.offset()..span.offset() + span.len()].to_string()
+    }
+
+    fn undefined_variable(&self, slot: u16) -> RuntimeError {
+        RuntimeError::undefined_variable(&self.source, self.span(), self.global_slots.name(slot))
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        compiler::Compiler, errors::RuntimeError, interpreter::Value, lexer::Lexer, parser::Parser,
+        source::SourceFile,
+    };
+
+    use std::time::Duration;
+
+    use super::Vm;
+
+    /// Runs `source` and returns the value of the global `result`.
+    fn run(source: &str) -> Result<Value, RuntimeError> {
+        run_on(&mut Vm::new(), source)
+    }
+
+    /// Like `run`, but on `vm`, which keeps the globals of earlier scripts.
+    fn run_on(vm: &mut Vm, source: &str) -> Result<Value, RuntimeError> {
+        let source = &SourceFile::new("test.fox", source);
+        let tokens = Lexer::new(source).map(|t| t.unwrap()).collect();
+        let stmts = Parser::new(source, tokens).parse().unwrap();
+        let script = Compiler::new(source, vm.global_slots())
+            .compile(&stmts)
+            .unwrap();
+        vm.interpret(source, script)?;
+        let result = vm.globals().into_iter().find(|(name, _)| name == "result");
+        Ok(result.map_or(Value::Nil, |(_, value)| value))
+    }
+
+    /// Asserts that `result` failed with the diagnostic `code` and returns the
+    /// error for further checks.
+    fn failed(result: Result<Value, RuntimeError>, code: &str) -> RuntimeError {
+        let error = result.unwrap_err();
+        assert_eq!(error.code, code, "{error}");
+        error
+    }
+
+    #[test]
+    fn check_expressions() {
+        assert_eq!(
+            run("let result = (1 + 2) * 3 - 4 / 2;").unwrap(),
+            Value::Float(7.0)
+        );
+        assert_eq!(
+            run("let result = \"fox\" + \"es\";").unwrap(),
+            Value::String("foxes".to_string())
+        );
+        assert_eq!(
+            run(r#"let n = 2; let result = "${n} + ${n} = ${n + n}, ${"${nil}!"}";"#).unwrap(),
+            Value::String("2 + 2 = 4, nil!".to_string())
+        );
+        assert_eq!(
+            run("let result = !(1 >= 2) == (3 != 4);").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(run("let result = -2 <= 1;").unwrap(), Value::Bool(true));
+        assert_eq!(
+            run("let n = -3; let result = n > 0 ? 1 : n < 0 ? -1 : 0;").unwrap(),
+            Value::Int(-1)
+        );
+        assert_eq!(
+            run("let result = nil or 0 or missing;").unwrap(),
+            Value::Int(0)
+        );
+        assert_eq!(
+            run("let result = 1 and false and missing;").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            run("let result = \"fox\" and nil or \"default\";").unwrap(),
+            Value::String("default".to_string())
+        );
+    }
+
+    #[test]
+    fn check_numbers() {
+        assert!(matches!(
+            run("let result = 0; for let i = 0; i < 3; i = i + 1 { result = result + i; }"),
+            Ok(Value::Int(3))
+        ));
+        assert!(matches!(run("let result = 7 / 2;"), Ok(Value::Float(n)) if n == 3.5));
+        assert!(matches!(run("let result = 2 * 1.5;"), Ok(Value::Float(n)) if n == 3.0));
+        assert_eq!(
+            run("let result = 17 % 5 + (6 & 3 | 8) ^ -2 ** 2 >> 1;").unwrap(),
+            Value::Int(-14)
+        );
+        failed(run("let result = 1 % 0;"), "runtime::division_by_zero");
+        failed(
+            run("let result = 0x7fff_ffff_ffff_ffff + 1;"),
+            "runtime::integer_overflow",
+        );
+    }
+
+    #[test]
+    fn check_statements() {
+        assert_eq!(
+            run("let result = 0; for let i = 0; i < 5; i = i + 1 { result = result + i; }")
+                .unwrap(),
+            Value::Int(10)
+        );
+        assert_eq!(
+            run("let result = 1; while result < 100 { result = result * 2; }").unwrap(),
+            Value::Int(128)
+        );
+        assert_eq!(
+            run(
+                "let result; if nil { result = 1; } else if 0 { result = 2; } else { result = 3; }"
+            )
+            .unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            run("let result = 1; { let result = 2; { let a = result; result = a + 1; } }").unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            run("let result = 0; for let i = 0; i < 10; i += 1 { let odd = i % 2; if odd == 0 { continue; } let j = i; if j > 6 { break; } result += j; }")
+                .unwrap(),
+            Value::Int(9)
+        );
+        assert_eq!(
+            run("let result = 0; { let a = 0; while true { a += 1; { let b = a; if b < 3 { continue; } } result = a; break; } }")
+                .unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            run("let result = 0; fn f() { let fs = 0; for let i = 0; i < 3; i += 1 { fn g() { return i; } fs = g; if i == 1 { break; } } return fs(); } result = f();")
+                .unwrap(),
+            Value::Int(1)
+        );
+    }
+
+    #[test]
+    fn check_for_in() {
+        assert_eq!(
+            run("let result = \"\"; for k in {\"b\": 1, \"a\": 2} { result += k; } for c in \"fox\" { result += c; }").unwrap(),
+            Value::String("bafox".to_string())
+        );
+        assert_eq!(
+            run("let result = 0; for x in [1, 2, 3, 4, 5] { let y = x; if y == 2 { continue; } if y == 5 { break; } result += y; }").unwrap(),
+            Value::Int(8)
+        );
+        assert_eq!(
+            run("let result; { let fs = [nil, nil]; for i in [0, 1] { fn f() { return i; } fs[i] = f; } result = fs[0](); }").unwrap(),
+            Value::Int(0)
+        );
+        let error = failed(run("for x in nil { }"), "runtime::not_iterable");
+        assert_eq!(error.value("found"), Some("nil"));
+    }
+
+    #[test]
+    fn check_match() {
+        assert_eq!(
+            run("let result = \"\"; for n in [1, 2, 3, 4] { result += match n % 3 { 0 => \"fizz\", 1 if n > 1 => \"late\", _ => \"${n}\" }; }").unwrap(),
+            Value::String("12fizzlate".to_string())
+        );
+        failed(run("let result = match 3 { 1 => 1 };"), "runtime::no_match");
+    }
+
+    #[test]
+    fn check_ranges() {
+        assert_eq!(
+            run("let result = 0; for i in 1..=4 { result += i; } for i in 0x7fff_ffff_ffff_fffe..0x7fff_ffff_ffff_ffff { result += 1; }").unwrap(),
+            Value::Int(11)
+        );
+        assert_eq!(
+            run("let xs = [1, 2, 3]; let result = \"${xs[1..3]} ${\"fox\"[0..1]}\";").unwrap(),
+            Value::String("[2, 3] f".to_string())
+        );
+        let error = failed(
+            run("let result = [1][0..=1];"),
+            "runtime::range_out_of_bounds",
+        );
+        assert_eq!(error.value("len"), Some("1"));
+    }
+
+    #[test]
+    fn check_lists() {
+        assert_eq!(
+            run("let xs = [1, [2, 3]]; xs[1][0] += xs[0]; let result = \"${xs}\";").unwrap(),
+            Value::String("[1, [3, 3]]".to_string())
+        );
+        assert_eq!(
+            run("let result; { let xs = [0, 0]; for let i = 0; i < 2; i += 1 { xs[i] = i * 2; } result = xs[1]; }")
+                .unwrap(),
+            Value::Int(2)
+        );
+        let error = failed(
+            run("let xs = []; let result = xs[0];"),
+            "runtime::index_out_of_bounds",
+        );
+        assert_eq!(error.value("index"), Some("0"));
+        assert_eq!(error.value("len"), Some("0"));
+        let error = failed(
+            run("let result = nil; result[0] = 1;"),
+            "runtime::not_indexable",
+        );
+        assert_eq!(error.value("found"), Some("nil"));
+    }
+
+    #[test]
+    fn check_maps() {
+        assert_eq!(
+            run("let m = {\"a\": [1], 2: {}}; m[2][\"b\"] = m[\"a\"][0] + 1; let result = \"${m}\";")
+                .unwrap(),
+            Value::String("{\"a\": [1], 2: {\"b\": 2}}".to_string())
+        );
+        failed(
+            run("let m = {}; let result = m[\"a\"];"),
+            "runtime::missing_key",
+        );
+        let error = failed(run("let result = {[]: 1};"), "runtime::invalid_key");
+        assert_eq!(error.value("found"), Some("list"));
+    }
+
+    #[test]
+    fn check_functions() {
+        assert_eq!(
+            run("fn fib(n) { if n < 2 { return n; } return fib(n - 1) + fib(n - 2); } let result = fib(15);")
+                .unwrap(),
+            Value::Int(610)
+        );
+        assert_eq!(run("fn f() {} let result = f();").unwrap(), Value::Nil);
+        assert_eq!(
+            run("fn sign(n) { guard n != 0 else { return 0; } guard n > 0 else { return -1; } return 1; } let result = sign(0) + sign(-5) * 10 + sign(3) * 100;")
+                .unwrap(),
+            Value::Int(90)
+        );
+        assert_eq!(
+            run("let result; { fn f(a, b) { return a - b; } let g = f; result = g(f(3, 1), 1); }")
+                .unwrap(),
+            Value::Int(1)
+        );
+    }
+
+    #[test]
+    fn check_closures() {
+        let source = "
+            fn make_counter() {
+                let count = 0;
+                fn increment() {
+                    count += 1;
+                    return count;
+                }
+                return increment;
+            }
+            let counter = make_counter();
+            counter();
+            let result = counter() + make_counter()();
+        ";
+        assert_eq!(run(source).unwrap(), Value::Int(3));
+
+        let source = "
+            let get;
+            let set;
+            {
+                let a = 1;
+                fn g() { return a; }
+                fn s(v) { a = v; }
+                get = g;
+                set = s;
+            }
+            set(5);
+            let result = get();
+        ";
+        assert_eq!(run(source).unwrap(), Value::Int(5));
+
+        let source = "
+            fn outer() {
+                let x = 1;
+                fn middle() {
+                    fn inner() { return x; }
+                    return inner;
+                }
+                x = 2;
+                return middle();
+            }
+            let result = outer()();
+        ";
+        assert_eq!(run(source).unwrap(), Value::Int(2));
+    }
+
+    #[test]
+    fn check_errors() {
+        let error = failed(run("-\"fox\";"), "runtime::invalid_operand");
+        assert_eq!(error.value("operator"), Some("-"));
+        let error = failed(run("1 >= nil;"), "runtime::invalid_operands");
+        assert_eq!(error.value("operator"), Some(">="));
+        failed(run("a;"), "runtime::undefined_variable");
+        failed(run("a = 1;"), "runtime::undefined_variable");
+        failed(run("\"fox\"();"), "runtime::not_callable");
+        failed(run("fn f(a) {} f();"), "runtime::arity_mismatch");
+        failed(run("fn f() { f(); } f();"), "runtime::stack_overflow");
+    }
+
+    #[test]
+    fn check_globals() {
+        let mut vm = Vm::new();
+        assert_eq!(run_on(&mut vm, "let a = 1; let b;").unwrap(), Value::Nil);
+        assert_eq!(
+            run_on(&mut vm, "let result = a + 1;").unwrap(),
+            Value::Int(2)
+        );
+        let error = failed(run_on(&mut vm, "c = 1;"), "runtime::undefined_variable");
+        assert_eq!(error.value("name"), Some("c"));
+        let names = vm.globals().into_iter().map(|(name, _)| name);
+        assert_eq!(names.collect::<Vec<_>>(), ["a", "b", "result"]);
+    }
+
+    #[test]
+    fn check_timeout() {
+        for source in ["while true {}", "fn f() {} f();"] {
+            let mut vm = Vm::new();
+            vm.set_timeout(Some(Duration::ZERO));
+            assert!(run_on(&mut vm, source).unwrap_err().is_timeout());
+        }
+    }
+}